@@ -1853,6 +1853,586 @@ impl ContainerState {
         }
     }
 }
+/// Generated client implementations.
+pub mod runtime_service_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// Runtime service defines the public APIs for remote container runtimes
+    #[derive(Debug, Clone)]
+    pub struct RuntimeServiceClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl RuntimeServiceClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> RuntimeServiceClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> RuntimeServiceClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            RuntimeServiceClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Version returns the runtime name, runtime version, and runtime API version.
+        pub async fn version(
+            &mut self,
+            request: impl tonic::IntoRequest<super::VersionRequest>,
+        ) -> Result<tonic::Response<super::VersionResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/Version",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// RunPodSandbox creates and starts a pod-level sandbox. Runtimes must ensure
+        /// the sandbox is in the ready state on success.
+        pub async fn run_pod_sandbox(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RunPodSandboxRequest>,
+        ) -> Result<tonic::Response<super::RunPodSandboxResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/RunPodSandbox",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// StopPodSandbox stops any running process that is part of the sandbox and
+        /// reclaims network resources (e.g., IP addresses) allocated to the sandbox.
+        /// If there are any running containers in the sandbox, they must be forcibly
+        /// terminated.
+        /// This call is idempotent, and must not return an error if all relevant
+        /// resources have already been reclaimed. kubelet will call StopPodSandbox
+        /// at least once before calling RemovePodSandbox. It will also attempt to
+        /// reclaim resources eagerly, as soon as a sandbox is not needed. Hence,
+        /// multiple StopPodSandbox calls are expected.
+        pub async fn stop_pod_sandbox(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StopPodSandboxRequest>,
+        ) -> Result<tonic::Response<super::StopPodSandboxResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/StopPodSandbox",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// RemovePodSandbox removes the sandbox. If there are any running containers
+        /// in the sandbox, they must be forcibly terminated and removed.
+        /// This call is idempotent, and must not return an error if the sandbox has
+        /// already been removed.
+        pub async fn remove_pod_sandbox(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemovePodSandboxRequest>,
+        ) -> Result<tonic::Response<super::RemovePodSandboxResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/RemovePodSandbox",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// PodSandboxStatus returns the status of the PodSandbox. If the PodSandbox is not
+        /// present, returns an error.
+        pub async fn pod_sandbox_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PodSandboxStatusRequest>,
+        ) -> Result<tonic::Response<super::PodSandboxStatusResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/PodSandboxStatus",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ListPodSandbox returns a list of PodSandboxes.
+        pub async fn list_pod_sandbox(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListPodSandboxRequest>,
+        ) -> Result<tonic::Response<super::ListPodSandboxResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ListPodSandbox",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// CreateContainer creates a new container in specified PodSandbox
+        pub async fn create_container(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CreateContainerRequest>,
+        ) -> Result<tonic::Response<super::CreateContainerResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/CreateContainer",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// StartContainer starts the container.
+        pub async fn start_container(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StartContainerRequest>,
+        ) -> Result<tonic::Response<super::StartContainerResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/StartContainer",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// StopContainer stops a running container with a grace period (i.e., timeout).
+        /// This call is idempotent, and must not return an error if the container has
+        /// already been stopped.
+        /// The runtime must forcibly kill the container after the grace period is
+        /// reached.
+        pub async fn stop_container(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StopContainerRequest>,
+        ) -> Result<tonic::Response<super::StopContainerResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/StopContainer",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// RemoveContainer removes the container. If the container is running, the
+        /// container must be forcibly removed.
+        /// This call is idempotent, and must not return an error if the container has
+        /// already been removed.
+        pub async fn remove_container(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveContainerRequest>,
+        ) -> Result<tonic::Response<super::RemoveContainerResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/RemoveContainer",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ListContainers lists all containers by filters.
+        pub async fn list_containers(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListContainersRequest>,
+        ) -> Result<tonic::Response<super::ListContainersResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ListContainers",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ContainerStatus returns status of the container. If the container is not
+        /// present, returns an error.
+        pub async fn container_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ContainerStatusRequest>,
+        ) -> Result<tonic::Response<super::ContainerStatusResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ContainerStatus",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// UpdateContainerResources updates ContainerConfig of the container synchronously.
+        /// If runtime fails to transactionally update the requested resources, an error is returned.
+        pub async fn update_container_resources(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateContainerResourcesRequest>,
+        ) -> Result<tonic::Response<super::UpdateContainerResourcesResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/UpdateContainerResources",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ReopenContainerLog asks runtime to reopen the stdout/stderr log file
+        /// for the container. This is often called after the log file has been
+        /// rotated. If the container is not running, container runtime can choose
+        /// to either create a new log file and return nil, or return an error.
+        /// Once it returns error, new container log file MUST NOT be created.
+        pub async fn reopen_container_log(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ReopenContainerLogRequest>,
+        ) -> Result<tonic::Response<super::ReopenContainerLogResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ReopenContainerLog",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ExecSync runs a command in a container synchronously.
+        pub async fn exec_sync(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExecSyncRequest>,
+        ) -> Result<tonic::Response<super::ExecSyncResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ExecSync",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Exec prepares a streaming endpoint to execute a command in the container.
+        pub async fn exec(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExecRequest>,
+        ) -> Result<tonic::Response<super::ExecResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/Exec",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Attach prepares a streaming endpoint to attach to a running container.
+        pub async fn attach(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AttachRequest>,
+        ) -> Result<tonic::Response<super::AttachResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/Attach",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// PortForward prepares a streaming endpoint to forward ports from a PodSandbox.
+        pub async fn port_forward(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PortForwardRequest>,
+        ) -> Result<tonic::Response<super::PortForwardResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/PortForward",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ContainerStats returns stats of the container. If the container does not
+        /// exist, the call returns an error.
+        pub async fn container_stats(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ContainerStatsRequest>,
+        ) -> Result<tonic::Response<super::ContainerStatsResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ContainerStats",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ListContainerStats returns stats of all running containers.
+        pub async fn list_container_stats(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListContainerStatsRequest>,
+        ) -> Result<tonic::Response<super::ListContainerStatsResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ListContainerStats",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// PodSandboxStats returns stats of the pod sandbox. If the pod sandbox does not
+        /// exist, the call returns an error.
+        pub async fn pod_sandbox_stats(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PodSandboxStatsRequest>,
+        ) -> Result<tonic::Response<super::PodSandboxStatsResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/PodSandboxStats",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ListPodSandboxStats returns stats of the pod sandboxes matching a filter.
+        pub async fn list_pod_sandbox_stats(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListPodSandboxStatsRequest>,
+        ) -> Result<tonic::Response<super::ListPodSandboxStatsResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/ListPodSandboxStats",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// UpdateRuntimeConfig updates the runtime configuration based on the given request.
+        pub async fn update_runtime_config(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateRuntimeConfigRequest>,
+        ) -> Result<tonic::Response<super::UpdateRuntimeConfigResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/UpdateRuntimeConfig",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// Status returns the status of the runtime.
+        pub async fn status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StatusRequest>,
+        ) -> Result<tonic::Response<super::StatusResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.RuntimeService/Status",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+}
 /// Generated server implementations.
 pub mod runtime_service_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -3062,6 +3642,182 @@ pub mod runtime_service_server {
         const NAME: &'static str = "runtime.v1alpha2.RuntimeService";
     }
 }
+/// Generated client implementations.
+pub mod image_service_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    /// ImageService defines the public APIs for managing images.
+    #[derive(Debug, Clone)]
+    pub struct ImageServiceClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl ImageServiceClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: std::convert::TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> ImageServiceClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> ImageServiceClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
+        {
+            ImageServiceClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// ListImages lists existing images.
+        pub async fn list_images(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListImagesRequest>,
+        ) -> Result<tonic::Response<super::ListImagesResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.ImageService/ListImages",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ImageStatus returns the status of the image. If the image is not
+        /// present, returns a response with ImageStatusResponse.Image set to
+        /// nil.
+        pub async fn image_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImageStatusRequest>,
+        ) -> Result<tonic::Response<super::ImageStatusResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.ImageService/ImageStatus",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// PullImage pulls an image with authentication config.
+        pub async fn pull_image(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PullImageRequest>,
+        ) -> Result<tonic::Response<super::PullImageResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.ImageService/PullImage",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// RemoveImage removes the image.
+        /// This call is idempotent, and must not return an error if the image has
+        /// already been removed.
+        pub async fn remove_image(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveImageRequest>,
+        ) -> Result<tonic::Response<super::RemoveImageResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.ImageService/RemoveImage",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        /// ImageFSInfo returns information of the filesystem that is used to store images.
+        pub async fn image_fs_info(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImageFsInfoRequest>,
+        ) -> Result<tonic::Response<super::ImageFsInfoResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/runtime.v1alpha2.ImageService/ImageFsInfo",
+            );
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+    }
+}
 /// Generated server implementations.
 pub mod image_service_server {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
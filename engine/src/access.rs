@@ -0,0 +1,222 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::hooks;
+use crate::method::{MethodBody, MethodInfo};
+
+/// CRI methods that only read state, every other method is treated as mutating.
+const READ_ONLY_METHODS: [&str; 14] = [
+    "version",
+    "status",
+    "list_pod_sandbox",
+    "pod_sandbox_status",
+    "pod_sandbox_stats",
+    "list_pod_sandbox_stats",
+    "list_containers",
+    "container_status",
+    "container_stats",
+    "list_container_stats",
+    "get_container_events",
+    "list_images",
+    "image_status",
+    "image_fs_info",
+];
+
+/// Whether `method` changes runtime state according to the built-in table, unknown methods
+/// are mutating.
+pub(crate) fn is_mutating(method: &str) -> bool {
+    !READ_ONLY_METHODS.contains(&method)
+}
+
+/// Checks the peer credentials of the request against the struct's `CriPolicy` before the
+/// call is forwarded, a rejection is the result of the call for the other features.
+pub(crate) fn authorize_body(body: &mut MethodBody, method: &MethodInfo, mutating: bool) {
+    let Some(request) = method.request() else {
+        return;
+    };
+    let name = method.name.to_string();
+    let access = if mutating {
+        quote! { Mutating }
+    } else {
+        quote! { ReadOnly }
+    };
+
+    let call = &body.call;
+    body.call = quote! {
+        match super::CriPolicy::authorize(self.cri_policy(), &#request, #name, super::CriAccess::#access) {
+            Ok(()) => #call,
+            Err(e) => Err(e),
+        }
+    };
+}
+
+/// Emits `CriCredentials`, read by both `authorize` and `audit`.
+pub(crate) fn expand_credentials() -> TokenStream {
+    quote! {
+        /// `SO_PEERCRED` of the process on the other end of a unix socket connection.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct CriCredentials {
+            pub uid: u32,
+            pub gid: u32,
+            pub pid: Option<i32>,
+        }
+
+        impl CriCredentials {
+            /// Credentials of the caller, `None` when the request did not come over a unix socket.
+            pub fn get<T>(request: &tonic::Request<T>) -> Option<Self> {
+                #[cfg(unix)]
+                {
+                    request
+                        .extensions()
+                        .get::<tonic::transport::server::UdsConnectInfo>()
+                        .and_then(|it| it.peer_cred)
+                        .map(|it| CriCredentials {
+                            uid: it.uid(),
+                            gid: it.gid(),
+                            pid: it.pid(),
+                        })
+                }
+                #[cfg(not(unix))]
+                {
+                    let _ = request;
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Emits `CriAccess`, `CriAllowList`, `CriPolicy` and the `cri_policy()` accessor of the
+/// struct, which uses a `CriPolicy` field when the struct has one and `CriPolicy::default()`
+/// otherwise.
+pub(crate) fn expand_authorize(struct_ast: &syn::ItemStruct) -> TokenStream {
+    let struct_name = &struct_ast.ident;
+    let policy_ty = syn::parse_quote! { CriPolicy };
+    let accessor = match hooks::field_of_type(struct_ast, &policy_ty) {
+        Some(field) => quote! { &self.#field },
+        None => quote! {
+            static POLICY: ::std::sync::OnceLock<CriPolicy> = ::std::sync::OnceLock::new();
+            POLICY.get_or_init(Default::default)
+        },
+    };
+
+    quote! {
+        /// Whether a CRI method only reads state or changes it.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum CriAccess {
+            ReadOnly,
+            Mutating,
+        }
+
+        /// Users and groups allowed to call a class of methods.
+        #[derive(Clone, Debug, Default)]
+        pub struct CriAllowList {
+            uids: Vec<u32>,
+            gids: Vec<u32>,
+        }
+
+        impl CriAllowList {
+            /// An empty list, allowing no one.
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            pub fn uid(mut self, uid: u32) -> Self {
+                self.uids.push(uid);
+                self
+            }
+
+            pub fn gid(mut self, gid: u32) -> Self {
+                self.gids.push(gid);
+                self
+            }
+
+            pub fn allows(&self, credentials: &CriCredentials) -> bool {
+                self.uids.contains(&credentials.uid) || self.gids.contains(&credentials.gid)
+            }
+        }
+
+        /// Who may call the generated methods, checked before every call is forwarded.
+        ///
+        /// Read-only and mutating methods have separate allow-lists, and a callback given with
+        /// `CriPolicy::from_fn` replaces both. Calls are rejected with `PermissionDenied`.
+        #[derive(Clone)]
+        pub struct CriPolicy {
+            read_only: CriAllowList,
+            mutating: CriAllowList,
+            check: Option<::std::sync::Arc<dyn Fn(&CriCredentials, &str, CriAccess) -> bool + Send + Sync>>,
+        }
+
+        impl CriPolicy {
+            pub fn new(read_only: CriAllowList, mutating: CriAllowList) -> Self {
+                CriPolicy {
+                    read_only,
+                    mutating,
+                    check: None,
+                }
+            }
+
+            /// Decides every call with `check(credentials, method, access)`.
+            pub fn from_fn<F>(check: F) -> Self
+            where
+                F: Fn(&CriCredentials, &str, CriAccess) -> bool + Send + Sync + 'static,
+            {
+                CriPolicy {
+                    read_only: CriAllowList::new(),
+                    mutating: CriAllowList::new(),
+                    check: Some(::std::sync::Arc::new(check)),
+                }
+            }
+
+            #[allow(clippy::result_large_err)]
+            pub fn authorize<T>(
+                &self,
+                request: &tonic::Request<T>,
+                method: &str,
+                access: CriAccess,
+            ) -> Result<(), tonic::Status> {
+                let credentials = match CriCredentials::get(request) {
+                    Some(it) => it,
+                    None => return Err(tonic::Status::permission_denied("peer credentials are unavailable")),
+                };
+                let allowed = match (&self.check, access) {
+                    (Some(check), _) => check(&credentials, method, access),
+                    (None, CriAccess::ReadOnly) => self.read_only.allows(&credentials),
+                    (None, CriAccess::Mutating) => self.mutating.allows(&credentials),
+                };
+                if allowed {
+                    Ok(())
+                } else {
+                    Err(tonic::Status::permission_denied(format!(
+                        "uid {} gid {} may not call {}",
+                        credentials.uid, credentials.gid, method
+                    )))
+                }
+            }
+        }
+
+        /// Only root may call anything.
+        impl Default for CriPolicy {
+            fn default() -> Self {
+                CriPolicy::new(CriAllowList::new().uid(0), CriAllowList::new().uid(0))
+            }
+        }
+
+        impl ::std::fmt::Debug for CriPolicy {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.debug_struct("CriPolicy")
+                    .field("read_only", &self.read_only)
+                    .field("mutating", &self.mutating)
+                    .field("check", &self.check.is_some())
+                    .finish()
+            }
+        }
+
+        impl #struct_name {
+            /// Policy the generated impls authorize every call against.
+            fn cri_policy(&self) -> &CriPolicy {
+                #accessor
+            }
+        }
+    }
+}
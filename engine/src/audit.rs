@@ -0,0 +1,311 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+
+use crate::message;
+use crate::method::{MethodBody, MethodInfo};
+
+/// Records a mutating call in the struct's `CriAuditLog` once it is done.
+///
+/// The targets are the ids of the request, plus the ids a successful response returns, such
+/// as the `pod_sandbox_id` of `run_pod_sandbox`.
+pub(crate) fn audit_body(body: &mut MethodBody, file: &syn::File, container: &Ident, trait_name: &Ident, method: &MethodInfo) {
+    let (Some(request), Some(request_ty)) = (method.request(), &method.request_ty) else {
+        return;
+    };
+    let name = method.name.to_string();
+    let version = container.to_string();
+    let service = trait_name.to_string();
+    let request_ids = message::target_ids(file, request_ty, request).into_iter().map(|(id, expr)| {
+        quote! { (#id, #expr.to_string()), }
+    });
+    let response = quote::format_ident!("it");
+    let response_ids = method
+        .response_ty()
+        .filter(|_| !method.is_stream)
+        .map(|ty| message::target_ids(file, &ty, &response))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(id, expr)| {
+            quote! { targets.push((#id, #expr.to_string())); }
+        })
+        .collect::<Vec<_>>();
+    let response_ids = if response_ids.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            if let Ok(it) = &response {
+                #(#response_ids)*
+            }
+        }
+    };
+
+    body.before.push(quote! {
+        let audit_credentials = super::CriCredentials::get(&#request);
+        let audit_remote_addr = #request.remote_addr();
+        #[allow(unused_mut)]
+        let mut targets: Vec<(&'static str, String)> = vec![#(#request_ids)*];
+    });
+    body.after.push(quote! {
+        #response_ids
+        let (code, message) = match &response {
+            Ok(_) => (tonic::Code::Ok, ""),
+            Err(e) => (e.code(), e.message()),
+        };
+        self.cri_audit()
+            .record(&super::CriAuditRecord {
+                method: #name,
+                service: #service,
+                version: #version,
+                credentials: audit_credentials,
+                remote_addr: audit_remote_addr,
+                targets: &targets,
+                code,
+                message,
+            })
+            .await;
+    });
+}
+
+/// Emits `CriAuditLog`, `CriAuditRecord` and the `cri_audit()` accessor returning the
+/// `CriAuditLog` field of the struct.
+pub(crate) fn expand_audit(struct_ast: &syn::ItemStruct, field: &TokenStream) -> TokenStream {
+    let struct_name = &struct_ast.ident;
+
+    quote! {
+        /// A finished mutating call.
+        #[derive(Debug)]
+        pub struct CriAuditRecord<'a> {
+            pub method: &'a str,
+            pub service: &'a str,
+            pub version: &'a str,
+            pub credentials: Option<CriCredentials>,
+            pub remote_addr: Option<::std::net::SocketAddr>,
+            /// Ids of the pod sandboxes, containers or images the call targeted.
+            pub targets: &'a [(&'static str, String)],
+            pub code: tonic::Code,
+            pub message: &'a str,
+        }
+
+        /// Append-only JSON-lines audit log of mutating CRI calls, rotated by size.
+        ///
+        /// Every line carries the SHA-256 of the previous one in `prev` and its own in `hash`,
+        /// computed over the line up to `,"hash"`, so edited, removed or reordered records break
+        /// the chain. The chain continues across rotated files (`<path>.1` is the newest).
+        #[derive(Clone, Debug)]
+        pub struct CriAuditLog {
+            file: ::std::sync::Arc<::std::sync::Mutex<CriAuditFile>>,
+        }
+
+        #[derive(Debug)]
+        struct CriAuditFile {
+            path: ::std::path::PathBuf,
+            file: ::std::fs::File,
+            size: u64,
+            max_bytes: u64,
+            max_files: usize,
+            last_hash: String,
+        }
+
+        impl CriAuditLog {
+            /// Appends to `path`, keeping up to `max_files` rotated files of about `max_bytes` each.
+            pub fn open(path: impl Into<::std::path::PathBuf>, max_bytes: u64, max_files: usize) -> ::std::io::Result<Self> {
+                let path = path.into();
+                if let Some(parent) = path.parent() {
+                    ::std::fs::create_dir_all(parent)?;
+                }
+                let file = Self::open_file(&path)?;
+                let size = file.metadata()?.len();
+                let last_hash = [path.clone(), Self::rotated(&path, 1)]
+                    .iter()
+                    .find_map(|it| Self::last_hash(it))
+                    .unwrap_or_else(|| "0".repeat(64));
+
+                Ok(CriAuditLog {
+                    file: ::std::sync::Arc::new(::std::sync::Mutex::new(CriAuditFile {
+                        path,
+                        file,
+                        size,
+                        max_bytes,
+                        max_files,
+                        last_hash,
+                    })),
+                })
+            }
+
+            /// Creates or appends to the file at `path`, only readable by its owner.
+            fn open_file(path: &::std::path::Path) -> ::std::io::Result<::std::fs::File> {
+                use ::std::os::unix::fs::OpenOptionsExt;
+
+                const AUDIT_MODE: u32 = 0o600;
+                ::std::fs::OpenOptions::new().create(true).append(true).mode(AUDIT_MODE).open(path)
+            }
+
+            /// Writes `record` on the blocking thread pool of `tokio`, a failure is logged as a
+            /// `tracing` error of the `cri` target rather than returned to the caller whose call
+            /// already happened.
+            pub async fn record(&self, record: &CriAuditRecord<'_>) {
+                let line = Self::line(record);
+                let log = self.clone();
+                let error = match tokio::task::spawn_blocking(move || log.write(line)).await {
+                    Ok(Ok(())) => return,
+                    Ok(Err(e)) => e.to_string(),
+                    Err(e) => e.to_string(),
+                };
+                tracing::error!(
+                    target: "cri",
+                    method = record.method,
+                    service = record.service,
+                    version = record.version,
+                    error = %error,
+                    "audit record not written",
+                );
+            }
+
+            /// The line of `record` up to its `prev` hash.
+            fn line(record: &CriAuditRecord<'_>) -> String {
+                use ::std::fmt::Write as _;
+
+                let mut line = String::new();
+                let _ = write!(line, "{{\"ts\":\"{}\"", Self::timestamp());
+                let _ = write!(line, ",\"method\":\"{}\",\"service\":\"{}\",\"version\":\"{}\"", record.method, record.service, record.version);
+                match &record.credentials {
+                    Some(it) => {
+                        let pid = it.pid.map_or("null".to_string(), |it| it.to_string());
+                        let _ = write!(line, ",\"uid\":{},\"gid\":{},\"pid\":{}", it.uid, it.gid, pid);
+                    }
+                    None => line.push_str(",\"uid\":null,\"gid\":null,\"pid\":null"),
+                }
+                match &record.remote_addr {
+                    Some(it) => {
+                        let _ = write!(line, ",\"remote_addr\":\"{}\"", it);
+                    }
+                    None => line.push_str(",\"remote_addr\":null"),
+                }
+                line.push_str(",\"targets\":{");
+                for (index, (name, id)) in record.targets.iter().enumerate() {
+                    if index > 0 {
+                        line.push(',');
+                    }
+                    let _ = write!(line, "\"{}\":\"{}\"", name, Self::escape(id));
+                }
+                let _ = write!(line, "}},\"code\":\"{:?}\",\"message\":\"{}\"", record.code, Self::escape(record.message));
+                line
+            }
+
+            /// Chains `line` to the last record and appends it, rotating the file when full.
+            fn write(&self, mut line: String) -> ::std::io::Result<()> {
+                use ::std::fmt::Write as _;
+                use ::std::io::Write as _;
+                use sha2::Digest;
+
+                let mut file = self.file.lock().unwrap_or_else(|it| it.into_inner());
+                let _ = write!(line, ",\"prev\":\"{}\"", file.last_hash);
+
+                let hash = sha2::Sha256::digest(line.as_bytes())
+                    .iter()
+                    .fold(String::new(), |mut out, it| {
+                        let _ = write!(out, "{:02x}", it);
+                        out
+                    });
+                let _ = writeln!(line, ",\"hash\":\"{}\"}}", hash);
+
+                if file.size > 0 && file.size + line.len() as u64 > file.max_bytes {
+                    file.rotate()?;
+                }
+                file.file.write_all(line.as_bytes())?;
+                file.file.flush()?;
+                file.size += line.len() as u64;
+                file.last_hash = hash;
+                Ok(())
+            }
+
+            fn rotated(path: &::std::path::Path, index: usize) -> ::std::path::PathBuf {
+                let mut name = path.as_os_str().to_owned();
+                name.push(format!(".{}", index));
+                name.into()
+            }
+
+            /// `hash` of the last record in `path`.
+            fn last_hash(path: &::std::path::Path) -> Option<String> {
+                let content = ::std::fs::read_to_string(path).ok()?;
+                let line = content.lines().last()?;
+                let start = line.rfind("\"hash\":\"")? + "\"hash\":\"".len();
+                line.get(start..start + 64).map(str::to_string)
+            }
+
+            fn escape(value: &str) -> String {
+                let mut out = String::with_capacity(value.len());
+                for it in value.chars() {
+                    match it {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        it if (it as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", it as u32)),
+                        it => out.push(it),
+                    }
+                }
+                out
+            }
+
+            /// Current time as RFC 3339 in UTC with milliseconds.
+            fn timestamp() -> String {
+                let now = ::std::time::SystemTime::now()
+                    .duration_since(::std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                let secs = now.as_secs();
+                let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+
+                // days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+                let z = days + 719_468;
+                let era = z.div_euclid(146_097);
+                let doe = z.rem_euclid(146_097);
+                let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+                let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+                let mp = (5 * doy + 2) / 153;
+                let day = doy - (153 * mp + 2) / 5 + 1;
+                let month = if mp < 10 { mp + 3 } else { mp - 9 };
+                let year = yoe + era * 400 + i64::from(month <= 2);
+
+                format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+                    year,
+                    month,
+                    day,
+                    rem / 3_600,
+                    rem % 3_600 / 60,
+                    rem % 60,
+                    now.subsec_millis()
+                )
+            }
+        }
+
+        impl CriAuditFile {
+            /// Shifts `<path>.N` to `<path>.N+1`, dropping the oldest, and starts a new `<path>`.
+            fn rotate(&mut self) -> ::std::io::Result<()> {
+                if self.max_files == 0 {
+                    ::std::fs::remove_file(&self.path)?;
+                } else {
+                    for index in (1..self.max_files).rev() {
+                        match ::std::fs::rename(CriAuditLog::rotated(&self.path, index), CriAuditLog::rotated(&self.path, index + 1)) {
+                            Err(e) if e.kind() != ::std::io::ErrorKind::NotFound => return Err(e),
+                            _ => {}
+                        }
+                    }
+                    ::std::fs::rename(&self.path, CriAuditLog::rotated(&self.path, 1))?;
+                }
+                self.file = CriAuditLog::open_file(&self.path)?;
+                self.size = 0;
+                Ok(())
+            }
+        }
+
+        impl #struct_name {
+            /// Audit log the generated impls record mutating calls in.
+            fn cri_audit(&self) -> &CriAuditLog {
+                &self.#field
+            }
+        }
+    }
+}
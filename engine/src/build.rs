@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use proc_macro2::{Span, TokenStream};
+
+use crate::{Config, Protos};
+
+/// Expansions a build script writes to `$OUT_DIR`, each `include!`d by the crate in place of
+/// an `#[auto_impl(...)]` struct. The expansion only runs when the build script does, rather
+/// than parsing the protos on every build of the crate, and the generated files can be read in
+/// `target/`:
+///
+/// ```ignore
+/// // build.rs
+/// auto_impl_trait_engine::Build::new()
+///     .expand("square.rs", "image, mock", "pub struct Square { side: i32 }")
+///     .generate()
+///     .unwrap();
+///
+/// // src/lib.rs, clippy lints included code, unlike the output of the attribute
+/// #[allow(clippy::all)]
+/// mod square {
+///     include!(concat!(env!("OUT_DIR"), "/square.rs"));
+/// }
+/// pub use square::*;
+/// ```
+#[derive(Default)]
+pub struct Build {
+    /// `Protos::bundled()` when not given.
+    proto_dir: Option<PathBuf>,
+    /// `$OUT_DIR` when not given.
+    out_dir: Option<PathBuf>,
+    /// File names with the attribute arguments and struct of their expansion.
+    files: Vec<(String, String, String)>,
+}
+
+impl Build {
+    /// No files yet, the bundled protos `#[auto_impl]` expands against.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the files of `Protos::read` from `dir` instead of the bundled protos, running the
+    /// build script again when any of them changes.
+    pub fn proto_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.proto_dir = Some(dir.into());
+        self
+    }
+
+    /// Writes the files to `dir` instead of `$OUT_DIR`.
+    pub fn out_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(dir.into());
+        self
+    }
+
+    /// Writes to `$OUT_DIR/name` what `#[auto_impl(attr)]` on `item` generates, `attr` and
+    /// `item` as Rust source, e.g. `"image, mock"` and `"pub struct Square;"`.
+    pub fn expand(mut self, name: impl Into<String>, attr: impl Into<String>, item: impl Into<String>) -> Self {
+        self.files.push((name.into(), attr.into(), item.into()));
+        self
+    }
+
+    /// Generates every file, returning their paths. Files whose content did not change are
+    /// left alone, so what includes them is not rebuilt.
+    pub fn generate(self) -> syn::Result<Vec<PathBuf>> {
+        let error = |message: String| syn::Error::new(Span::call_site(), message);
+        let out_dir = self
+            .out_dir
+            .clone()
+            .or_else(|| std::env::var_os("OUT_DIR").map(PathBuf::from))
+            .ok_or_else(|| {
+                error("`OUT_DIR` is not set, `Build::generate` runs in a build script or is given `out_dir`".to_string())
+            })?;
+        let protos = match &self.proto_dir {
+            Some(dir) => {
+                for name in Protos::files() {
+                    println!("cargo:rerun-if-changed={}", dir.join(name).display());
+                }
+                Protos::read(dir)?
+            }
+            None => Protos::bundled(),
+        };
+        let mut paths = Vec::new();
+        for (name, attr, item) in &self.files {
+            let tokens = |source: &str| {
+                source
+                    .parse::<TokenStream>()
+                    .map_err(|e| error(format!("{}: invalid tokens `{}`: {}", name, source, e)))
+            };
+            let config = Config::from_attribute(tokens(attr)?, tokens(item)?, protos.clone())?;
+            let source = config.to_source()?;
+
+            let path = out_dir.join(name);
+            if std::fs::read_to_string(&path).ok().as_deref() != Some(source.as_str()) {
+                std::fs::write(&path, source).map_err(|e| error(format!("write {} failed: {}", path.display(), e)))?;
+            }
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
@@ -0,0 +1,551 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::Type;
+
+use crate::message::{messages, segment};
+use crate::method::MethodInfo;
+
+/// Field types whose values `CriChaosMessage::fields` lists with `to_string`.
+const SCALARS: [&str; 8] = ["String", "bool", "i32", "i64", "u32", "u64", "f32", "f64"];
+
+/// `CriChaos`'s implementation of one trait method, which applies the fault picked for the
+/// call around the wrapped implementation.
+pub(crate) fn chaos_method(
+    m: &syn::TraitItemMethod,
+    method: &MethodInfo,
+    container: &Ident,
+    trait_alias: &Ident,
+) -> Option<TokenStream> {
+    let request = method.request()?;
+    let name = &method.name;
+    let sig = &m.sig;
+    let method_name = name.to_string();
+    let version = container.to_string();
+
+    let response = if method.is_stream {
+        quote! {
+            let drop_after = fault.and_then(|it| it.drop_after);
+            response.map(|it| it.map(|it| super::CriChaosStream::new(it, drop_after)))
+        }
+    } else {
+        quote! {
+            match fault.and_then(|it| it.truncate) {
+                Some(keep) => response.map(|mut it| {
+                    super::CriChaosMessage::truncate(it.get_mut(), keep);
+                    it
+                }),
+                None => response,
+            }
+        }
+    };
+
+    Some(quote! {
+        #sig {
+            let fault = self.faults.pick(#version, #method_name, #request.get_ref());
+            if let Some(latency) = fault.as_ref().and_then(|it| it.latency) {
+                tokio::time::sleep(latency).await;
+            }
+            if let Some(fault) = &fault {
+                if let Some(code) = fault.code {
+                    let message = fault.message.as_deref().unwrap_or("fault injected by CriChaos");
+                    return Err(tonic::Status::new(code, message));
+                }
+            }
+            let response = super::#trait_alias::#name(&*self.inner, #request).await;
+            #response
+        }
+    })
+}
+
+/// Emits `CriChaos`'s impl of one service trait inside a version mod.
+pub(crate) fn expand_chaos_service(trait_alias: &Ident, streams: &[&syn::TraitItemType], items: &[TokenStream]) -> TokenStream {
+    let streams = streams.iter().map(|it| {
+        let name = &it.ident;
+        quote! {
+            type #name = super::CriChaosStream<<T as super::#trait_alias>::#name>;
+        }
+    });
+    quote! {
+        #[tonic::async_trait]
+        impl<T: super::#trait_alias> super::#trait_alias for super::CriChaos<T> {
+            #(#streams)*
+            #(#items)*
+        }
+    }
+}
+
+/// Emits `CriChaosMessage` impls for every message of a version mod.
+///
+/// Scalar fields are listed with their path, string maps with one entry per key, and nested
+/// messages recursively. Lists are truncated at the top level only, as the lists of a response
+/// are.
+pub(crate) fn expand_chaos_impls(file: &syn::File) -> TokenStream {
+    let names = messages(file).map(|it| it.ident.to_string()).collect::<Vec<_>>();
+    let is_message = |ty: &Type| matches!(segment(ty), Some((name, args)) if args.is_empty() && names.contains(&name));
+    let is_scalar = |ty: &Type| matches!(segment(ty), Some((name, args)) if args.is_empty() && SCALARS.contains(&name.as_str()));
+
+    let impls = messages(file).map(|message| {
+        let fields = message.fields.iter().filter_map(|field| {
+            let name = field.ident.as_ref()?;
+            let (outer, args) = segment(&field.ty)?;
+            let field_name = name.to_string();
+
+            match (outer.as_str(), args.as_slice()) {
+                ("Option", [it]) if is_message(it) => Some(quote! {
+                    if let Some(it) = &self.#name {
+                        super::CriChaosMessage::fields(it, &super::cri_field_path(path, #field_name), out);
+                    }
+                }),
+                ("HashMap", [key, value]) if is_scalar(key) && is_scalar(value) => Some(quote! {
+                    for (key, value) in &self.#name {
+                        out.push((format!("{}.{}", super::cri_field_path(path, #field_name), key), value.to_string()));
+                    }
+                }),
+                _ if is_message(&field.ty) => Some(quote! {
+                    super::CriChaosMessage::fields(&self.#name, &super::cri_field_path(path, #field_name), out);
+                }),
+                _ if is_scalar(&field.ty) => Some(quote! {
+                    out.push((super::cri_field_path(path, #field_name), self.#name.to_string()));
+                }),
+                _ => None,
+            }
+        });
+        let truncates = message.fields.iter().filter_map(|field| {
+            let name = field.ident.as_ref()?;
+            match segment(&field.ty)? {
+                // `Vec<u8>` is a bytes field
+                (outer, args) if outer == "Vec" && !matches!(segment(args[0]), Some((it, _)) if it == "u8") => Some(quote! {
+                    self.#name.truncate(keep);
+                }),
+                _ => None,
+            }
+        });
+
+        let ident = &message.ident;
+        quote! {
+            impl super::CriChaosMessage for #ident {
+                #[allow(unused_variables)]
+                fn fields(&self, path: &str, out: &mut Vec<(String, String)>) {
+                    #(#fields)*
+                }
+
+                #[allow(unused_variables)]
+                fn truncate(&mut self, keep: usize) {
+                    #(#truncates)*
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#impls)*
+    }
+}
+
+/// Emits `CriChaos`, `CriFaults`, `CriFault`, `CriChaosStream` and `CriChaosMessage`,
+/// implemented by every CRI message of both versions.
+pub(crate) fn expand_chaos() -> TokenStream {
+    quote! {
+        /// Wraps an implementation of the generated traits and injects the faults of its
+        /// `CriFaults` into the calls, the wrapped handlers are unchanged.
+        pub struct CriChaos<T> {
+            inner: ::std::sync::Arc<T>,
+            faults: CriFaults,
+        }
+
+        impl<T> CriChaos<T> {
+            /// Wraps `inner`, with no faults until some are added through `faults()`.
+            pub fn new(inner: T) -> Self {
+                Self::from_arc(::std::sync::Arc::new(inner))
+            }
+
+            pub fn from_arc(inner: ::std::sync::Arc<T>) -> Self {
+                CriChaos {
+                    inner,
+                    faults: CriFaults::default(),
+                }
+            }
+
+            pub fn inner(&self) -> &::std::sync::Arc<T> {
+                &self.inner
+            }
+
+            /// The faults injected into the calls, clones control the same faults.
+            pub fn faults(&self) -> &CriFaults {
+                &self.faults
+            }
+        }
+
+        impl<T> Clone for CriChaos<T> {
+            fn clone(&self) -> Self {
+                CriChaos {
+                    inner: self.inner.clone(),
+                    faults: self.faults.clone(),
+                }
+            }
+        }
+
+        /// A fault injected into the calls it applies to.
+        ///
+        /// It is written as a query string, such as
+        /// `method=list_containers&probability=0.5&latency=2s&code=unavailable`, with the keys:
+        ///
+        /// - `method`, `version`: the calls it applies to, any when not given.
+        /// - `match=field=value`: repeatable, only calls whose request has `value` in `field`,
+        ///   a trailing part of the field path such as `labels.io.kubernetes.pod.name` or
+        ///   `metadata.name`.
+        /// - `probability`: chance of applying it to a matching call, `1` when not given.
+        /// - `latency`: delay before the call, in `ms`, `s`, `m` or `h`.
+        /// - `code`, `message`: fail the call with this `tonic::Code`, by name or number.
+        /// - `truncate`: keep this many items of every list of the response.
+        /// - `drop_after`: end a server stream with `Unavailable` after this many items.
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct CriFault {
+            pub method: Option<String>,
+            pub version: Option<String>,
+            pub matches: Vec<(String, String)>,
+            pub probability: f64,
+            pub latency: Option<::std::time::Duration>,
+            pub code: Option<tonic::Code>,
+            pub message: Option<String>,
+            pub truncate: Option<usize>,
+            pub drop_after: Option<usize>,
+        }
+
+        impl Default for CriFault {
+            fn default() -> Self {
+                CriFault {
+                    method: None,
+                    version: None,
+                    matches: Vec::new(),
+                    probability: 1.0,
+                    latency: None,
+                    code: None,
+                    message: None,
+                    truncate: None,
+                    drop_after: None,
+                }
+            }
+        }
+
+        impl CriFault {
+            fn applies_to(&self, version: &str, method: &str, fields: &mut Option<Vec<(String, String)>>, request: &dyn CriChaosMessage) -> bool {
+                if self.version.as_deref().map_or(false, |it| it != version)
+                    || self.method.as_deref().map_or(false, |it| it != method && it != "*")
+                {
+                    return false;
+                }
+                if self.matches.is_empty() {
+                    return true;
+                }
+                let fields = fields.get_or_insert_with(|| {
+                    let mut out = Vec::new();
+                    request.fields("", &mut out);
+                    out
+                });
+                self.matches.iter().all(|(field, value)| {
+                    fields.iter().any(|(path, it)| {
+                        it == value && (path == field || path.ends_with(&format!(".{}", field)))
+                    })
+                })
+            }
+
+            fn parse_duration(value: &str) -> Option<::std::time::Duration> {
+                let (number, unit) = value.split_at(value.find(|it: char| !it.is_ascii_digit())?);
+                let number = number.parse::<u64>().ok()?;
+                let millis = match unit {
+                    "ms" => number,
+                    "s" => number.checked_mul(1_000)?,
+                    "m" => number.checked_mul(60_000)?,
+                    "h" => number.checked_mul(3_600_000)?,
+                    _ => return None,
+                };
+                Some(::std::time::Duration::from_millis(millis))
+            }
+
+            fn parse_code(value: &str) -> Option<tonic::Code> {
+                if let Ok(number) = value.parse::<i32>() {
+                    return (0..=16).contains(&number).then(|| tonic::Code::from_i32(number));
+                }
+                let value = value.replace('_', "").to_lowercase();
+                (0..=16)
+                    .map(tonic::Code::from_i32)
+                    .find(|it| format!("{:?}", it).to_lowercase() == value)
+            }
+
+            /// Decodes the `%XX` escapes and `+` of a query string value.
+            fn decode(value: &str) -> String {
+                let bytes = value.as_bytes();
+                let hex = |it: u8| (it as char).to_digit(16);
+                let mut out = Vec::with_capacity(bytes.len());
+                let mut index = 0;
+                while index < bytes.len() {
+                    match bytes[index] {
+                        b'+' => out.push(b' '),
+                        b'%' if index + 2 < bytes.len() => match (hex(bytes[index + 1]), hex(bytes[index + 2])) {
+                            (Some(high), Some(low)) => {
+                                out.push((high * 16 + low) as u8);
+                                index += 2;
+                            }
+                            _ => out.push(b'%'),
+                        },
+                        it => out.push(it),
+                    }
+                    index += 1;
+                }
+                String::from_utf8_lossy(&out).into_owned()
+            }
+
+            /// Escapes what `decode` reads differently, and the line breaks of `GET /faults`.
+            fn encode(value: &str) -> String {
+                let mut out = String::with_capacity(value.len());
+                for it in value.chars() {
+                    match it {
+                        ' ' => out.push('+'),
+                        '%' | '&' | '=' | '+' => out.push_str(&format!("%{:02X}", it as u32)),
+                        it if it.is_ascii_control() => out.push_str(&format!("%{:02X}", it as u32)),
+                        it => out.push(it),
+                    }
+                }
+                out
+            }
+        }
+
+        impl ::std::str::FromStr for CriFault {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let mut fault = CriFault::default();
+                for pair in s.split('&').filter(|it| !it.is_empty()) {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    let value = CriFault::decode(value);
+                    let invalid = || format!("invalid `{}`: `{}`", key, value);
+                    match key {
+                        "method" => fault.method = Some(value),
+                        "version" => fault.version = Some(value),
+                        "match" => {
+                            let (field, expected) = value.split_once('=').ok_or_else(invalid)?;
+                            fault.matches.push((field.to_string(), expected.to_string()));
+                        }
+                        "probability" => {
+                            fault.probability = value.parse().ok().filter(|it| (0.0..=1.0).contains(it)).ok_or_else(invalid)?;
+                        }
+                        "latency" => fault.latency = Some(CriFault::parse_duration(&value).ok_or_else(invalid)?),
+                        "code" => fault.code = Some(CriFault::parse_code(&value).ok_or_else(invalid)?),
+                        "message" => fault.message = Some(value),
+                        "truncate" => fault.truncate = Some(value.parse().map_err(|_| invalid())?),
+                        "drop_after" => fault.drop_after = Some(value.parse().map_err(|_| invalid())?),
+                        _ => return Err(format!("unknown fault key `{}`", key)),
+                    }
+                }
+                Ok(fault)
+            }
+        }
+
+        impl ::std::fmt::Display for CriFault {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                write!(f, "method={}", CriFault::encode(self.method.as_deref().unwrap_or("*")))?;
+                if let Some(version) = &self.version {
+                    write!(f, "&version={}", CriFault::encode(version))?;
+                }
+                for (field, value) in &self.matches {
+                    write!(f, "&match={}={}", CriFault::encode(field), CriFault::encode(value))?;
+                }
+                write!(f, "&probability={}", self.probability)?;
+                if let Some(latency) = self.latency {
+                    write!(f, "&latency={}ms", latency.as_millis())?;
+                }
+                if let Some(code) = self.code {
+                    write!(f, "&code={:?}", code)?;
+                }
+                if let Some(message) = &self.message {
+                    write!(f, "&message={}", CriFault::encode(message))?;
+                }
+                if let Some(keep) = self.truncate {
+                    write!(f, "&truncate={}", keep)?;
+                }
+                if let Some(count) = self.drop_after {
+                    write!(f, "&drop_after={}", count)?;
+                }
+                Ok(())
+            }
+        }
+
+        /// The faults of a `CriChaos`, changed at runtime through `add` and `clear` or the HTTP
+        /// control API of `serve_unix` and `serve_tcp`.
+        #[derive(Clone, Debug)]
+        pub struct CriFaults {
+            state: ::std::sync::Arc<::std::sync::Mutex<CriFaultsState>>,
+        }
+
+        #[derive(Debug)]
+        struct CriFaultsState {
+            faults: Vec<CriFault>,
+            /// xorshift64 state of the probabilities.
+            rng: u64,
+        }
+
+        impl Default for CriFaults {
+            fn default() -> Self {
+                let seed = ::std::time::SystemTime::now()
+                    .duration_since(::std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_nanos() as u64;
+                CriFaults {
+                    state: ::std::sync::Arc::new(::std::sync::Mutex::new(CriFaultsState {
+                        faults: Vec::new(),
+                        rng: seed | 1,
+                    })),
+                }
+            }
+        }
+
+        impl CriFaults {
+            /// Adds `fault`, the first fault applying to a call is injected.
+            pub fn add(&self, fault: CriFault) {
+                self.lock().faults.push(fault);
+            }
+
+            pub fn clear(&self) {
+                self.lock().faults.clear();
+            }
+
+            pub fn list(&self) -> Vec<CriFault> {
+                self.lock().faults.clone()
+            }
+
+            fn lock(&self) -> ::std::sync::MutexGuard<'_, CriFaultsState> {
+                self.state.lock().unwrap_or_else(|it| it.into_inner())
+            }
+
+            /// The fault to inject into a call of `method`, if one applies and wins its roll.
+            fn pick(&self, version: &str, method: &str, request: &dyn CriChaosMessage) -> Option<CriFault> {
+                let mut state = self.lock();
+                let CriFaultsState { faults, rng } = &mut *state;
+                let mut fields = None;
+                faults
+                    .iter()
+                    .find(|fault| {
+                        if !fault.applies_to(version, method, &mut fields, request) {
+                            return false;
+                        }
+                        if fault.probability >= 1.0 {
+                            return true;
+                        }
+                        *rng ^= *rng << 13;
+                        *rng ^= *rng >> 7;
+                        *rng ^= *rng << 17;
+                        ((*rng >> 11) as f64 / (1u64 << 53) as f64) < fault.probability
+                    })
+                    .cloned()
+            }
+
+            /// Serves the control API over HTTP on `addr`, e.g. `127.0.0.1:9091`.
+            pub async fn serve_tcp(self, addr: ::std::net::SocketAddr) -> ::std::io::Result<()> {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    tokio::spawn(self.clone().respond(stream));
+                }
+            }
+
+            /// Serves the control API over HTTP on the unix socket at `path`, replacing a stale
+            /// one: `GET /faults` lists the faults one per line, `POST /faults?<fault>` adds one
+            /// and `DELETE /faults` removes them all.
+            pub async fn serve_unix(self, path: impl AsRef<::std::path::Path>) -> ::std::io::Result<()> {
+                let listener = cri_http::bind_unix(path.as_ref())?;
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    tokio::spawn(self.clone().respond(stream));
+                }
+            }
+
+            /// Answers a single control API request.
+            async fn respond<S>(self, mut stream: S)
+            where
+                S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+            {
+                let (method, path, query) = match cri_http::read_request(&mut stream).await {
+                    Some(it) => it,
+                    None => return,
+                };
+                let (status, body) = match (method.as_str(), path.as_str()) {
+                    ("GET", "/faults") => {
+                        let body = self.list().iter().map(|it| format!("{}\n", it)).collect::<String>();
+                        ("200 OK", body)
+                    }
+                    ("POST", "/faults") => match query.parse::<CriFault>() {
+                        Ok(fault) => {
+                            let body = format!("{}\n", fault);
+                            self.add(fault);
+                            ("200 OK", body)
+                        }
+                        Err(e) => ("400 Bad Request", format!("{}\n", e)),
+                    },
+                    ("DELETE", "/faults") => {
+                        self.clear();
+                        ("200 OK", String::new())
+                    }
+                    (_, "/faults") => ("405 Method Not Allowed", String::new()),
+                    _ => ("404 Not Found", String::new()),
+                };
+                cri_http::write_response(&mut stream, status, "text/plain", &body).await;
+            }
+        }
+
+        /// Server stream of a `CriChaos`, ended with `Unavailable` after `drop_after` items
+        /// when a fault says so.
+        pub struct CriChaosStream<S> {
+            inner: ::std::pin::Pin<Box<S>>,
+            remaining: Option<usize>,
+            dropped: bool,
+        }
+
+        impl<S> CriChaosStream<S> {
+            pub fn new(inner: S, drop_after: Option<usize>) -> Self {
+                CriChaosStream {
+                    inner: Box::pin(inner),
+                    remaining: drop_after,
+                    dropped: false,
+                }
+            }
+        }
+
+        impl<S, T> tonic::codegen::futures_core::Stream for CriChaosStream<S>
+        where
+            S: tonic::codegen::futures_core::Stream<Item = Result<T, tonic::Status>>,
+        {
+            type Item = S::Item;
+
+            fn poll_next(
+                mut self: ::std::pin::Pin<&mut Self>,
+                cx: &mut ::std::task::Context<'_>,
+            ) -> ::std::task::Poll<Option<Self::Item>> {
+                if self.dropped {
+                    return ::std::task::Poll::Ready(None);
+                }
+                if self.remaining == Some(0) {
+                    self.dropped = true;
+                    let status = tonic::Status::unavailable("stream dropped by CriChaos");
+                    return ::std::task::Poll::Ready(Some(Err(status)));
+                }
+                let item = self.inner.as_mut().poll_next(cx);
+                if let ::std::task::Poll::Ready(Some(_)) = &item {
+                    if let Some(remaining) = self.remaining.as_mut() {
+                        *remaining -= 1;
+                    }
+                }
+                item
+            }
+        }
+
+        /// What `CriChaos` reads and changes in CRI messages.
+        pub trait CriChaosMessage {
+            /// Appends the scalar fields as `(path, value)`, nested ones prefixed by `path`.
+            fn fields(&self, path: &str, out: &mut Vec<(String, String)>);
+
+            /// Keeps at most `keep` items of every list.
+            fn truncate(&mut self, keep: usize);
+        }
+    }
+}
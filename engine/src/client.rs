@@ -0,0 +1,163 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::visit_mut::VisitMut;
+use syn::{TraitItem, Type};
+
+use crate::method::{self, MethodInfo};
+use crate::{service_trait, ServiceType, SuperRemover};
+
+/// `CriClient` methods forwarding to the bundled tonic client of `service_type`, with the trace
+/// context of the current span when `opentelemetry` is set.
+fn client_methods(file: &syn::File, service_type: &ServiceType, opentelemetry: bool) -> Vec<TokenStream> {
+    let Some(service) = service_trait(file, service_type) else {
+        return Vec::new();
+    };
+    let field = quote::format_ident!("{}", service_type.get_name());
+    let streams = service
+        .items
+        .iter()
+        .filter_map(|it| match it {
+            TraitItem::Type(t) => Some(t),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let stream_types = streams.iter().map(|it| it.ident.to_string()).collect::<Vec<_>>();
+
+    service
+        .items
+        .iter()
+        .filter_map(|it| match it {
+            TraitItem::Method(m) => Some(m),
+            _ => None,
+        })
+        .filter_map(|m| {
+            let mut m = m.clone();
+            SuperRemover.visit_trait_item_method_mut(&mut m);
+            let method = MethodInfo::new(&m, &stream_types);
+            let request_ty = method.request_ty.as_ref()?;
+            let response_ty = method.response_ty()?;
+            let response_ty = if method.is_stream {
+                // `Self::GetContainerEventsStream` is received as a `Streaming` of its messages
+                let Type::Path(path) = &response_ty else {
+                    return None;
+                };
+                let stream = &path.path.segments.last()?.ident;
+                let mut item = method::stream_item(streams.iter().find(|it| it.ident == *stream)?)?;
+                SuperRemover.visit_type_mut(&mut item);
+                let message = method::first_generic_argument(&item)?;
+                quote! { tonic::codec::Streaming<#message> }
+            } else {
+                quote! { #response_ty }
+            };
+
+            let name = &method.name;
+            let docs = m.attrs.iter().filter(|it| it.path.is_ident("doc"));
+            let inject = if opentelemetry {
+                quote! {
+                    let mut request = tonic::Request::new(request);
+                    super::CriTraceContext::inject(request.metadata_mut());
+                }
+            } else {
+                quote! {}
+            };
+            Some(quote! {
+                #(#docs)*
+                pub async fn #name(&self, request: #request_ty) -> Result<#response_ty, tonic::Status> {
+                    #inject
+                    self.#field.clone().#name(request).await.map(tonic::Response::into_inner)
+                }
+            })
+        })
+        .collect()
+}
+
+/// Emits `CriClient` inside a version mod, a client of both services of the version built
+/// on the bundled `runtime_service_client` and `image_service_client`.
+pub(crate) fn expand_client(file: &syn::File, opentelemetry: bool) -> TokenStream {
+    let runtime = client_methods(file, &ServiceType::Runtime, opentelemetry);
+    let image = client_methods(file, &ServiceType::Image, opentelemetry);
+
+    quote! {
+        /// Client of the runtime and image services of this CRI version, sharing the message
+        /// types of the generated server side.
+        ///
+        /// Methods take the request message and return the response message, the underlying
+        /// tonic clients are available through `runtime()` and `image()` for metadata and
+        /// compression settings. Cloning is cheap, clones share the connection. With
+        /// `opentelemetry`, methods send the trace context of the current span.
+        #[derive(Clone, Debug)]
+        pub struct CriClient {
+            runtime: runtime_service_client::RuntimeServiceClient<tonic::transport::Channel>,
+            image: image_service_client::ImageServiceClient<tonic::transport::Channel>,
+        }
+
+        impl CriClient {
+            pub fn new(channel: tonic::transport::Channel) -> Self {
+                CriClient {
+                    runtime: runtime_service_client::RuntimeServiceClient::new(channel.clone()),
+                    image: image_service_client::ImageServiceClient::new(channel),
+                }
+            }
+
+            /// Connects to a runtime listening on TCP, such as `http://127.0.0.1:10010`.
+            pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+            where
+                D: ::std::convert::TryInto<tonic::transport::Endpoint>,
+                D::Error: Into<tonic::codegen::StdError>,
+            {
+                let channel = tonic::transport::Endpoint::new(dst)?.connect().await?;
+                Ok(Self::new(channel))
+            }
+
+            /// Connects to a runtime listening on the unix socket at `path`, such as
+            /// `/run/containerd/containerd.sock`.
+            pub async fn connect_unix(path: impl AsRef<::std::path::Path>) -> Result<Self, tonic::transport::Error> {
+                let connector = super::CriUnixConnector(::std::sync::Arc::new(path.as_ref().to_path_buf()));
+                // the endpoint needs a URI, the connector dials `path` whatever it is
+                let channel = tonic::transport::Endpoint::from_static("http://[::]:50051")
+                    .connect_with_connector(connector)
+                    .await?;
+                Ok(Self::new(channel))
+            }
+
+            /// The runtime service client, for calls that need request metadata.
+            pub fn runtime(&self) -> runtime_service_client::RuntimeServiceClient<tonic::transport::Channel> {
+                self.runtime.clone()
+            }
+
+            /// The image service client, for calls that need request metadata.
+            pub fn image(&self) -> image_service_client::ImageServiceClient<tonic::transport::Channel> {
+                self.image.clone()
+            }
+
+            #(#runtime)*
+            #(#image)*
+        }
+    }
+}
+
+/// Emits the connector `CriClient::connect_unix` of both versions dials with.
+pub(crate) fn expand_connector() -> TokenStream {
+    quote! {
+        /// Dials the unix socket of a `CriClient` for every new connection.
+        #[derive(Clone, Debug)]
+        struct CriUnixConnector(::std::sync::Arc<::std::path::PathBuf>);
+
+        impl tonic::codegen::Service<tonic::codegen::http::Uri> for CriUnixConnector {
+            type Response = tokio::net::UnixStream;
+            type Error = ::std::io::Error;
+            type Future = ::std::pin::Pin<
+                Box<dyn ::std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+            >;
+
+            fn poll_ready(&mut self, _cx: &mut ::std::task::Context<'_>) -> ::std::task::Poll<Result<(), Self::Error>> {
+                ::std::task::Poll::Ready(Ok(()))
+            }
+
+            fn call(&mut self, _uri: tonic::codegen::http::Uri) -> Self::Future {
+                let path = self.0.clone();
+                Box::pin(async move { tokio::net::UnixStream::connect(&*path).await })
+            }
+        }
+    }
+}
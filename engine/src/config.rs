@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+
+use crate::{expand_attribute, AutoImplArgs, ServiceType};
+
+/// A version of the CRI, named as the mod its impls are generated in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    V1,
+    V1alpha2,
+}
+
+impl Version {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Version::V1 => "v1",
+            Version::V1alpha2 => "v1alpha2",
+        }
+    }
+}
+
+/// Sources of `v1`, `v1alpha2`, `health` and `reflection`.
+const SOURCES: [&str; 4] = [
+    "runtime.v1.rs",
+    "runtime.v1alpha2.rs",
+    "grpc.health.v1.rs",
+    "grpc.reflection.v1alpha.rs",
+];
+
+/// Descriptor sets served by `reflection`, both CRI versions plus the services registered
+/// next to them.
+const DESCRIPTOR_SETS: [&str; 4] = [
+    "runtime.v1.bin",
+    "runtime.v1alpha2.bin",
+    "grpc.health.v1.bin",
+    "grpc.reflection.v1alpha.bin",
+];
+
+/// Sources the impls are generated from: the prost and tonic output of `runtime.v1` and
+/// `runtime.v1alpha2`, of the services `health` and `reflection` register, and the descriptor
+/// sets `reflection` serves.
+#[derive(Clone, Debug)]
+pub struct Protos {
+    pub v1: String,
+    pub v1alpha2: String,
+    /// `grpc.health.v1.rs`.
+    pub health: String,
+    /// `grpc.reflection.v1alpha.rs`.
+    pub reflection: String,
+    /// Encoded `FileDescriptorSet`s by file name, e.g. `runtime.v1.bin`.
+    pub descriptor_sets: Vec<(String, Vec<u8>)>,
+}
+
+impl Protos {
+    /// The files of `engine/proto` bundled with the engine, what `#[auto_impl]` expands against.
+    pub fn bundled() -> Self {
+        let descriptor_sets: [&[u8]; 4] = [
+            include_bytes!("../proto/runtime.v1.bin"),
+            include_bytes!("../proto/runtime.v1alpha2.bin"),
+            include_bytes!("../proto/grpc.health.v1.bin"),
+            include_bytes!("../proto/grpc.reflection.v1alpha.bin"),
+        ];
+        Protos {
+            v1: include_str!("../proto/runtime.v1.rs").to_string(),
+            v1alpha2: include_str!("../proto/runtime.v1alpha2.rs").to_string(),
+            health: include_str!("../proto/grpc.health.v1.rs").to_string(),
+            reflection: include_str!("../proto/grpc.reflection.v1alpha.rs").to_string(),
+            descriptor_sets: DESCRIPTOR_SETS
+                .iter()
+                .zip(descriptor_sets)
+                .map(|(name, bytes)| (name.to_string(), bytes.to_vec()))
+                .collect(),
+        }
+    }
+
+    /// Reads the sources and descriptor sets of `bundled` from `dir`.
+    pub fn read(dir: impl AsRef<Path>) -> syn::Result<Self> {
+        let read = |name: &str| {
+            let path = dir.as_ref().join(name);
+            std::fs::read(&path)
+                .map_err(|e| syn::Error::new(Span::call_site(), format!("read {} failed: {}", path.display(), e)))
+        };
+        let read_to_string = |name: &str| {
+            String::from_utf8(read(name)?)
+                .map_err(|_| syn::Error::new(Span::call_site(), format!("{} is not UTF-8", name)))
+        };
+        let [v1, v1alpha2, health, reflection] = SOURCES;
+        Ok(Protos {
+            v1: read_to_string(v1)?,
+            v1alpha2: read_to_string(v1alpha2)?,
+            health: read_to_string(health)?,
+            reflection: read_to_string(reflection)?,
+            descriptor_sets: DESCRIPTOR_SETS
+                .iter()
+                .map(|name| Ok((name.to_string(), read(name)?)))
+                .collect::<syn::Result<_>>()?,
+        })
+    }
+
+    /// The names of the files `read` reads from its dir.
+    pub fn files() -> impl Iterator<Item = &'static str> {
+        SOURCES.into_iter().chain(DESCRIPTOR_SETS)
+    }
+
+    /// The source of `version`.
+    pub fn get_mut(&mut self, version: Version) -> &mut String {
+        match version {
+            Version::V1 => &mut self.v1,
+            Version::V1alpha2 => &mut self.v1alpha2,
+        }
+    }
+}
+
+/// One expansion of `#[auto_impl(...)]`: the services and options of the attribute, the struct
+/// it annotates and the protos the impls are generated from.
+#[derive(Clone)]
+pub struct Config {
+    /// The services implemented, e.g. `[ServiceType::Image]` for `#[auto_impl(image)]`.
+    pub services: Vec<ServiceType>,
+    /// The flags and options after the services, in the syntax of the attribute, e.g.
+    /// `quote!(mock, limits(pull_image(concurrency = 2)))`.
+    pub options: TokenStream,
+    /// The annotated struct, emitted with the impls.
+    pub target: syn::ItemStruct,
+    pub protos: Protos,
+    /// Keeps only the mod and trait aliases of this version when given. Items shared by both
+    /// versions may still refer to the other one, so this is meant for reading.
+    pub version: Option<Version>,
+}
+
+impl Config {
+    /// No services nor options on `target` yet, both versions.
+    pub fn new(target: syn::ItemStruct, protos: Protos) -> Self {
+        Config {
+            services: Vec::new(),
+            options: TokenStream::new(),
+            target,
+            protos,
+            version: None,
+        }
+    }
+
+    /// The expansion of `#[auto_impl(attr)]` on `item`, what the attribute runs.
+    pub fn from_attribute(attr: TokenStream, item: TokenStream, protos: Protos) -> syn::Result<Self> {
+        let mut config = Config::new(syn::parse2(item)?, protos);
+
+        let mut tokens = attr.into_iter().peekable();
+        while let Some(TokenTree::Ident(ident)) = tokens.peek() {
+            let Some(service) = ServiceType::from_ident(ident) else {
+                break;
+            };
+            config.services.push(service);
+            tokens.next();
+            match tokens.next() {
+                Some(TokenTree::Punct(p)) if p.as_char() == ',' => {}
+                Some(it) => return Err(syn::Error::new(it.span(), "expected `,`")),
+                None => {}
+            }
+        }
+        config.options = tokens.collect();
+        Ok(config)
+    }
+
+    /// The items the attribute replaces the struct with.
+    pub fn expand(&self) -> syn::Result<TokenStream> {
+        let services = self.services.iter().map(|it| format_ident!("{}", it.get_name()));
+        let options = &self.options;
+        let attr = if options.is_empty() {
+            quote! { #(#services),* }
+        } else {
+            quote! { #(#services,)* #options }
+        };
+        let args = syn::parse2::<AutoImplArgs>(attr)?;
+        let tokens = expand_attribute(args, self.target.clone(), &self.protos)?;
+
+        let Some(version) = self.version else {
+            return Ok(tokens);
+        };
+        let is_kept = |ident: &syn::Ident| ident == version.as_str() || (ident != "v1" && ident != "v1alpha2");
+        let mut file = syn::parse2::<syn::File>(tokens)?;
+        file.items.retain(|item| match item {
+            syn::Item::Mod(m) => is_kept(&m.ident),
+            syn::Item::Use(u) => match &u.tree {
+                syn::UseTree::Path(p) => is_kept(&p.ident),
+                _ => true,
+            },
+            _ => true,
+        });
+        Ok(quote! { #file })
+    }
+
+    /// `expand` formatted as Rust source.
+    pub fn to_source(&self) -> syn::Result<String> {
+        Ok(prettyplease::unparse(&syn::parse2(self.expand()?)?))
+    }
+}
@@ -0,0 +1,138 @@
+use quote::quote;
+
+/// Emits `CriContext` and its helper types next to the annotated struct.
+///
+/// The generated trait impls build one context per call from the incoming `tonic::Request`
+/// and store it in the request extensions, so handlers keep their usual signature.
+pub(crate) fn expand_context() -> proc_macro2::TokenStream {
+    quote! {
+        /// CRI API version a call came in on.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum CriVersion {
+            V1,
+            V1alpha2,
+        }
+
+        impl CriVersion {
+            pub const fn as_str(&self) -> &'static str {
+                match self {
+                    CriVersion::V1 => "v1",
+                    CriVersion::V1alpha2 => "v1alpha2",
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for CriVersion {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        /// Remote end of a CRI connection.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum CriPeer {
+            /// Peer connected over TCP.
+            Tcp(::std::net::SocketAddr),
+            /// Peer connected over a unix socket, identified by its credentials.
+            Unix {
+                uid: u32,
+                gid: u32,
+                pid: Option<i32>,
+            },
+        }
+
+        /// Per-request context built by the generated impls.
+        #[derive(Clone, Debug)]
+        pub struct CriContext {
+            /// CRI version of the service that received the call.
+            pub version: CriVersion,
+            /// `x-request-id` sent by the client, or a process-unique id.
+            pub request_id: String,
+            /// Metadata of the incoming request.
+            pub metadata: tonic::metadata::MetadataMap,
+            /// Connecting peer, if the transport exposes it.
+            pub peer: Option<CriPeer>,
+            /// Deadline derived from the `grpc-timeout` header.
+            pub deadline: Option<::std::time::Instant>,
+        }
+
+        impl CriContext {
+            pub fn from_request<T>(request: &tonic::Request<T>, version: CriVersion) -> Self {
+                static NEXT_REQUEST_ID: ::std::sync::atomic::AtomicU64 = ::std::sync::atomic::AtomicU64::new(1);
+
+                let metadata = request.metadata().clone();
+                let request_id = metadata
+                    .get("x-request-id")
+                    .and_then(|it| it.to_str().ok())
+                    .map(str::to_owned)
+                    .unwrap_or_else(|| {
+                        format!("{:016x}", NEXT_REQUEST_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed))
+                    });
+                let deadline = metadata
+                    .get("grpc-timeout")
+                    .and_then(|it| it.to_str().ok())
+                    .and_then(Self::parse_timeout)
+                    .map(|it| ::std::time::Instant::now() + it);
+
+                CriContext {
+                    version,
+                    request_id,
+                    metadata,
+                    peer: Self::peer(request),
+                    deadline,
+                }
+            }
+
+            /// Context attached to `request` by the generated impl.
+            pub fn get<T>(request: &tonic::Request<T>) -> Option<&CriContext> {
+                request.extensions().get::<CriContext>()
+            }
+
+            /// Time left until the deadline, `None` if the client did not set one.
+            pub fn remaining(&self) -> Option<::std::time::Duration> {
+                self.deadline
+                    .map(|it| it.saturating_duration_since(::std::time::Instant::now()))
+            }
+
+            pub fn is_expired(&self) -> bool {
+                self.remaining().map_or(false, |it| it.is_zero())
+            }
+
+            fn peer<T>(request: &tonic::Request<T>) -> Option<CriPeer> {
+                #[cfg(unix)]
+                let unix = request
+                    .extensions()
+                    .get::<tonic::transport::server::UdsConnectInfo>()
+                    .and_then(|it| it.peer_cred)
+                    .map(|it| CriPeer::Unix {
+                        uid: it.uid(),
+                        gid: it.gid(),
+                        pid: it.pid(),
+                    });
+                #[cfg(not(unix))]
+                let unix = None;
+
+                unix.or_else(|| request.remote_addr().map(CriPeer::Tcp))
+            }
+
+            /// Parses a `grpc-timeout` value, e.g. `100m` or `5S`.
+            fn parse_timeout(value: &str) -> Option<::std::time::Duration> {
+                if value.is_empty() || value.len() > 9 || !value.is_ascii() {
+                    return None;
+                }
+
+                let (amount, unit) = value.split_at(value.len() - 1);
+                let amount = amount.parse::<u64>().ok()?;
+                match unit {
+                    "H" => Some(::std::time::Duration::from_secs(amount * 60 * 60)),
+                    "M" => Some(::std::time::Duration::from_secs(amount * 60)),
+                    "S" => Some(::std::time::Duration::from_secs(amount)),
+                    "m" => Some(::std::time::Duration::from_millis(amount)),
+                    "u" => Some(::std::time::Duration::from_micros(amount)),
+                    "n" => Some(::std::time::Duration::from_nanos(amount)),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
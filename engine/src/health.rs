@@ -0,0 +1,121 @@
+use quote::quote;
+
+use crate::Protos;
+
+/// Emits the `grpc.health.v1` messages and `CriHealth`, the health service registered by
+/// `CriServices` when the `health` flag is set.
+///
+/// Every generated CRI service starts out as `SERVING`, the status of each one can be changed
+/// at runtime through a clone of `CriServices::health`.
+pub(crate) fn expand_health(protos: &Protos) -> syn::Result<proc_macro2::TokenStream> {
+    let file = syn::parse_file(&protos.health)?;
+
+    Ok(quote! {
+        #[doc = "gRPC health checking protocol generated by auto_impl"]
+        pub mod grpc_health_v1 {
+            #file
+        }
+
+        /// `grpc.health.v1.Health` implementation reporting a serving status per service.
+        #[derive(Clone, Debug)]
+        pub struct CriHealth {
+            statuses: ::std::sync::Arc<
+                tokio::sync::watch::Sender<
+                    ::std::collections::HashMap<String, grpc_health_v1::health_check_response::ServingStatus>,
+                >,
+            >,
+        }
+
+        impl CriHealth {
+            /// Creates a reporter where `services` and the server as a whole (`""`) are serving.
+            pub fn new(services: &[&str]) -> Self {
+                let statuses = services
+                    .iter()
+                    .chain(::std::iter::once(&""))
+                    .map(|it| (it.to_string(), grpc_health_v1::health_check_response::ServingStatus::Serving))
+                    .collect();
+                CriHealth {
+                    statuses: ::std::sync::Arc::new(tokio::sync::watch::channel(statuses).0),
+                }
+            }
+
+            pub fn set_serving_status(
+                &self,
+                service: &str,
+                status: grpc_health_v1::health_check_response::ServingStatus,
+            ) {
+                self.statuses.send_modify(|it| {
+                    it.insert(service.to_string(), status);
+                });
+            }
+
+            /// Sets the status of every known service, including the server itself.
+            pub fn set_all(&self, status: grpc_health_v1::health_check_response::ServingStatus) {
+                self.statuses.send_modify(|it| it.values_mut().for_each(|it| *it = status));
+            }
+
+            pub fn serving_status(
+                &self,
+                service: &str,
+            ) -> Option<grpc_health_v1::health_check_response::ServingStatus> {
+                self.statuses.borrow().get(service).copied()
+            }
+        }
+
+        #[tonic::async_trait]
+        impl grpc_health_v1::health_server::Health for CriHealth {
+            async fn check(
+                &self,
+                request: tonic::Request<grpc_health_v1::HealthCheckRequest>,
+            ) -> Result<tonic::Response<grpc_health_v1::HealthCheckResponse>, tonic::Status> {
+                let service = request.into_inner().service;
+                match self.serving_status(&service) {
+                    Some(status) => Ok(tonic::Response::new(grpc_health_v1::HealthCheckResponse {
+                        status: status as i32,
+                    })),
+                    None => Err(tonic::Status::not_found(format!("unknown service {}", service))),
+                }
+            }
+
+            type WatchStream = ::std::pin::Pin<
+                Box<
+                    dyn tonic::codegen::futures_core::Stream<
+                            Item = Result<grpc_health_v1::HealthCheckResponse, tonic::Status>,
+                        > + Send,
+                >,
+            >;
+
+            async fn watch(
+                &self,
+                request: tonic::Request<grpc_health_v1::HealthCheckRequest>,
+            ) -> Result<tonic::Response<Self::WatchStream>, tonic::Status> {
+                let service = request.into_inner().service;
+                let mut statuses = self.statuses.subscribe();
+                let (tx, rx) = tokio::sync::mpsc::channel(4);
+
+                tokio::spawn(async move {
+                    let mut last = None;
+                    loop {
+                        let status = statuses
+                            .borrow_and_update()
+                            .get(&service)
+                            .copied()
+                            .unwrap_or(grpc_health_v1::health_check_response::ServingStatus::ServiceUnknown);
+                        if last != Some(status) {
+                            let response = grpc_health_v1::HealthCheckResponse { status: status as i32 };
+                            if tx.send(Ok(response)).await.is_err() {
+                                break;
+                            }
+                            last = Some(status);
+                        }
+                        if statuses.changed().await.is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                Ok(tonic::Response::new(Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))))
+            }
+        }
+    })
+}
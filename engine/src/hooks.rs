@@ -0,0 +1,141 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::visit_mut::{visit_type_mut, VisitMut};
+use syn::Type;
+
+use crate::method::{MethodBody, MethodInfo};
+use crate::ServiceType;
+
+/// Rewrites `Self::Assoc` into the stream type the struct's own method returns, such as
+/// `super::Assoc`.
+///
+/// The hooks see the result of that method, before `server` wraps the stream to end it on
+/// drain, so the associated type of the trait impl would not match.
+struct SelfReplacer<'a> {
+    streams: &'a [(Ident, TokenStream)],
+}
+
+impl VisitMut for SelfReplacer<'_> {
+    fn visit_type_mut(&mut self, i: &mut Type) {
+        visit_type_mut(self, i);
+
+        if let Type::Path(p) = i {
+            if p.qself.is_none() && p.path.segments.len() == 2 && p.path.segments[0].ident == "Self" {
+                let assoc = &p.path.segments[1].ident;
+                if let Some((_, stream)) = self.streams.iter().find(|(name, _)| name == assoc) {
+                    *i = syn::parse_quote! { #stream };
+                }
+            }
+        }
+    }
+}
+
+/// Emits `<Trait>Hooks` inside a version mod, with a no-op `before_<method>` and
+/// `after_<method>` for every method of the service. `streams` are the stream types the
+/// struct's methods return, by associated type.
+pub(crate) fn expand_hooks_trait(
+    service_type: &ServiceType,
+    methods: &[MethodInfo],
+    streams: &[(Ident, TokenStream)],
+) -> TokenStream {
+    let hooks_trait = quote::format_ident!("{}Hooks", service_type.get_trait());
+
+    let hooks = methods.iter().filter_map(|method| {
+        let request_ty = method.request_ty.as_ref()?;
+        let mut output_ty = method.output_ty.clone()?;
+        SelfReplacer { streams }.visit_type_mut(&mut output_ty);
+
+        let before = quote::format_ident!("before_{}", method.name);
+        let after = quote::format_ident!("after_{}", method.name);
+        let before_doc = format!("Runs before `{}`, an error is returned to the caller instead.", method.name);
+        let after_doc = format!("Runs with the result of `{}` before it is returned.", method.name);
+        Some(quote! {
+            #[doc = #before_doc]
+            fn #before(&self, _request: &mut tonic::Request<#request_ty>) -> Result<(), tonic::Status> {
+                Ok(())
+            }
+
+            #[doc = #after_doc]
+            fn #after(&self, _response: &#output_ty) {}
+        })
+    });
+
+    let doc = format!(
+        "Typed hooks around every `{}` method, implemented by the `layer` type of `#[auto_impl]`.",
+        service_type.get_trait()
+    );
+    quote! {
+        #[doc = #doc]
+        pub trait #hooks_trait: Send + Sync + 'static {
+            #(#hooks)*
+        }
+    }
+}
+
+/// Calls the hooks of `method` around the forwarded call.
+///
+/// The hooks wrap the call itself, so the checks wrapped around it afterwards run before any
+/// hook sees the request.
+pub(crate) fn hooks_body(body: &mut MethodBody, service_type: &ServiceType, method: &MethodInfo) {
+    let (Some(request), Some(_)) = (method.request(), &method.request_ty) else {
+        return;
+    };
+    let hooks_trait = quote::format_ident!("{}Hooks", service_type.get_trait());
+    let before = quote::format_ident!("before_{}", method.name);
+    let after = quote::format_ident!("after_{}", method.name);
+
+    let call = &body.call;
+    body.call = quote! {
+        match #hooks_trait::#before(self.cri_hooks(), &mut #request) {
+            Ok(()) => {
+                let response = #call;
+                #hooks_trait::#after(self.cri_hooks(), &response);
+                response
+            }
+            Err(e) => Err(e),
+        }
+    };
+    body.mutable_request = true;
+}
+
+/// Emits `cri_hooks()` on the struct, returning the `layer` instance the generated impls use.
+///
+/// A field of the layer type is used when the struct has one, otherwise a single
+/// `Default` instance is created on first use.
+pub(crate) fn expand_hooks_accessor(layer: &Type, struct_ast: &syn::ItemStruct) -> TokenStream {
+    let struct_name = &struct_ast.ident;
+    let body = match field_of_type(struct_ast, layer) {
+        Some(field) => quote! { &self.#field },
+        None => quote! {
+            static HOOKS: ::std::sync::OnceLock<#layer> = ::std::sync::OnceLock::new();
+            HOOKS.get_or_init(Default::default)
+        },
+    };
+
+    quote! {
+        impl #struct_name {
+            /// Hooks the generated impls run around every call.
+            fn cri_hooks(&self) -> &#layer {
+                #body
+            }
+        }
+    }
+}
+
+/// Member of the first field of `struct_ast` whose type is written as `ty`.
+pub(crate) fn field_of_type(struct_ast: &syn::ItemStruct, ty: &Type) -> Option<TokenStream> {
+    let ty_str = quote! { #ty }.to_string();
+    struct_ast.fields.iter().enumerate().find_map(|(index, field)| {
+        let field_ty = &field.ty;
+        if quote! { #field_ty }.to_string() != ty_str {
+            return None;
+        }
+        Some(match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = syn::Index::from(index);
+                quote! { #index }
+            }
+        })
+    })
+}
@@ -0,0 +1,72 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+/// Emits `cri_http`, the HTTP/1.1 plumbing of the `CRI_METRICS` endpoint and the `CriFaults`
+/// control API: one request per connection, answered and closed.
+pub(crate) fn expand_http() -> TokenStream {
+    quote! {
+        mod cri_http {
+            /// Listens on the unix socket at `path`, replacing a stale one. Anything else at
+            /// `path` is left alone.
+            pub(super) fn bind_unix(path: &::std::path::Path) -> ::std::io::Result<tokio::net::UnixListener> {
+                use ::std::os::unix::fs::FileTypeExt;
+
+                match ::std::fs::symlink_metadata(path) {
+                    Ok(meta) if meta.file_type().is_socket() => ::std::fs::remove_file(path)?,
+                    Ok(_) => {
+                        return Err(::std::io::Error::new(
+                            ::std::io::ErrorKind::AlreadyExists,
+                            format!("{} exists and is not a socket", path.display()),
+                        ));
+                    }
+                    Err(e) if e.kind() == ::std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e),
+                }
+                tokio::net::UnixListener::bind(path)
+            }
+
+            /// Reads the head of a request, `None` if the peer left before sending it. Returns the
+            /// method, the path and the query string.
+            pub(super) async fn read_request<S>(stream: &mut S) -> Option<(String, String, String)>
+            where
+                S: tokio::io::AsyncRead + Unpin,
+            {
+                use tokio::io::AsyncReadExt;
+
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.windows(4).any(|it| it == b"\r\n\r\n") && head.len() < 16 * 1024 {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => return None,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+
+                let head = String::from_utf8_lossy(&head);
+                let mut line = head.lines().next().unwrap_or_default().split(' ');
+                let method = line.next().unwrap_or_default();
+                let target = line.next().unwrap_or_default();
+                let (path, query) = target.split_once('?').unwrap_or((target, ""));
+                Some((method.to_string(), path.to_string(), query.to_string()))
+            }
+
+            /// Answers with `status`, such as `200 OK`, and closes the connection.
+            pub(super) async fn write_response<S>(stream: &mut S, status: &str, content_type: &str, body: &str)
+            where
+                S: tokio::io::AsyncWrite + Unpin,
+            {
+                use tokio::io::AsyncWriteExt;
+
+                let response = format!(
+                    "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    content_type,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use proc_macro2::Ident;
+use quote::quote;
+
+use crate::message;
+use crate::method::{MethodBody, MethodInfo};
+
+/// Runs the forwarded call inside a `tracing` span.
+///
+/// The span carries the method, the service and its version, the ids the request targets
+/// and, once the call is done, the resulting `tonic::Code`. With `opentelemetry` the span is
+/// parented to the trace context the caller sent in the metadata.
+pub(crate) fn tracing_body(
+    body: &mut MethodBody,
+    file: &syn::File,
+    container: &Ident,
+    trait_name: &Ident,
+    method: &MethodInfo,
+    opentelemetry: bool,
+) {
+    let (Some(request), Some(request_ty)) = (method.request(), &method.request_ty) else {
+        return;
+    };
+    let name = method.name.to_string();
+    let version = container.to_string();
+    let service = trait_name.to_string();
+    let ids = message::target_ids(file, request_ty, request).into_iter().map(|(id, expr)| {
+        let id = quote::format_ident!("{}", id);
+        quote! { #id = #expr, }
+    });
+
+    body.before.push(quote! {
+        let span = tracing::info_span!(
+            "cri",
+            method = #name,
+            service = #service,
+            version = #version,
+            #(#ids)*
+            code = tracing::field::Empty,
+        );
+    });
+    if opentelemetry {
+        body.before.push(quote! {
+            tracing_opentelemetry::OpenTelemetrySpanExt::set_parent(
+                &span,
+                super::CriTraceContext::extract(#request.metadata()),
+            );
+        });
+    }
+    let call = &body.call;
+    body.call = quote! {
+        tracing::Instrument::instrument(async { #call }, span.clone()).await
+    };
+    body.after.push(quote! {
+        let code = match &response {
+            Ok(_) => tonic::Code::Ok,
+            Err(e) => e.code(),
+        };
+        span.record("code", &tracing::field::debug(code));
+    });
+}
@@ -0,0 +1,335 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::message::messages;
+
+/// How the `#[prost(...)]` attribute of a field says its value is encoded.
+enum Encoding {
+    /// An `i32` of the enumeration at this path, a `Vec<i32>` when repeated.
+    Enumeration(syn::Path, bool),
+    Bytes,
+    Other,
+}
+
+fn encoding(field: &syn::Field) -> Encoding {
+    let metas = field
+        .attrs
+        .iter()
+        .filter(|it| it.path.is_ident("prost"))
+        .filter_map(|it| match it.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    let repeated = metas.iter().any(
+        |it| matches!(it, syn::NestedMeta::Meta(syn::Meta::Path(p)) if p.is_ident("repeated")),
+    );
+
+    for meta in &metas {
+        match meta {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(it)) if it.path.is_ident("enumeration") => {
+                if let syn::Lit::Str(s) = &it.lit {
+                    if let Ok(path) = s.parse::<syn::Path>() {
+                        return Encoding::Enumeration(path, repeated);
+                    }
+                }
+            }
+            syn::NestedMeta::Meta(syn::Meta::NameValue(it)) if it.path.is_ident("bytes") => {
+                return Encoding::Bytes
+            }
+            syn::NestedMeta::Meta(syn::Meta::Path(it)) if it.is_ident("bytes") => {
+                return Encoding::Bytes
+            }
+            _ => {}
+        }
+    }
+    Encoding::Other
+}
+
+/// Emits `CriJson` impls for every message of a version mod.
+///
+/// Fields are named in lowerCamelCase as the protobuf JSON mapping does, enumerations are
+/// rendered by the name of their value and bytes as lossy UTF-8.
+pub(crate) fn expand_json_impls(file: &syn::File) -> TokenStream {
+    let impls = messages(file).map(|message| {
+        let fields = message.fields.iter().filter_map(|field| {
+            let name = field.ident.as_ref()?;
+            let json_name = change_case::camel_case(name.to_string().trim_start_matches("r#"));
+            let value = match encoding(field) {
+                Encoding::Enumeration(path, false) => quote! {
+                    super::CriValue::enumeration(#path::from_i32(self.#name).map(|it| it.as_str_name()), self.#name)
+                },
+                Encoding::Enumeration(path, true) => quote! {
+                    super::CriValue::Array(
+                        self.#name
+                            .iter()
+                            .map(|it| super::CriValue::enumeration(#path::from_i32(*it).map(|it| it.as_str_name()), *it))
+                            .collect(),
+                    )
+                },
+                Encoding::Bytes => quote! {
+                    super::CriValue::String(String::from_utf8_lossy(&self.#name).into_owned())
+                },
+                Encoding::Other => quote! {
+                    super::CriJson::to_value(&self.#name)
+                },
+            };
+            Some(quote! {
+                (#json_name.to_string(), #value),
+            })
+        });
+
+        let ident = &message.ident;
+        quote! {
+            impl super::CriJson for #ident {
+                fn to_value(&self) -> super::CriValue {
+                    super::CriValue::Object(vec![#(#fields)*])
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#impls)*
+    }
+}
+
+/// Emits `CriValue` and `CriJson`, implemented by every CRI message of both versions and the
+/// types of their fields.
+pub(crate) fn expand_json() -> TokenStream {
+    let numbers = ["i32", "i64", "u32", "u64", "f32", "f64"].iter().map(|it| {
+        let ty = quote::format_ident!("{}", it);
+        quote! {
+            impl CriJson for #ty {
+                fn to_value(&self) -> CriValue {
+                    CriValue::Number(self.to_string())
+                }
+            }
+        }
+    });
+
+    quote! {
+        /// A CRI message as a JSON document, rendered with `to_json` or `to_yaml`.
+        #[derive(Clone, Debug, PartialEq)]
+        pub enum CriValue {
+            Null,
+            Bool(bool),
+            /// A number as it is written.
+            Number(String),
+            String(String),
+            Array(Vec<CriValue>),
+            /// Fields in declaration order.
+            Object(Vec<(String, CriValue)>),
+        }
+
+        impl CriValue {
+            /// The name of an enumeration value, its number when it is unknown.
+            pub fn enumeration(name: Option<&str>, value: i32) -> Self {
+                match name {
+                    Some(name) => CriValue::String(name.to_string()),
+                    None => CriValue::Number(value.to_string()),
+                }
+            }
+
+            /// The value of the field `key` of an object.
+            pub fn get(&self, key: &str) -> Option<&CriValue> {
+                match self {
+                    CriValue::Object(fields) => fields.iter().find(|(it, _)| it == key).map(|(_, it)| it),
+                    _ => None,
+                }
+            }
+
+            /// Pretty-printed JSON indented by two spaces, without a trailing newline.
+            pub fn to_json(&self) -> String {
+                let mut out = String::new();
+                self.write_json(&mut out, 0);
+                out
+            }
+
+            /// A YAML document, ending with a newline.
+            pub fn to_yaml(&self) -> String {
+                let mut out = String::new();
+                self.write_yaml(&mut out, 0);
+                out
+            }
+
+            fn write_json(&self, out: &mut String, indent: usize) {
+                let pad = |out: &mut String, indent: usize| out.extend(::std::iter::repeat(' ').take(indent));
+                match self {
+                    CriValue::Array(items) if !items.is_empty() => {
+                        out.push_str("[\n");
+                        for (index, it) in items.iter().enumerate() {
+                            pad(out, indent + 2);
+                            it.write_json(out, indent + 2);
+                            out.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+                        }
+                        pad(out, indent);
+                        out.push(']');
+                    }
+                    CriValue::Object(fields) if !fields.is_empty() => {
+                        out.push_str("{\n");
+                        for (index, (key, it)) in fields.iter().enumerate() {
+                            pad(out, indent + 2);
+                            out.push_str(&Self::quote(key));
+                            out.push_str(": ");
+                            it.write_json(out, indent + 2);
+                            out.push_str(if index + 1 < fields.len() { ",\n" } else { "\n" });
+                        }
+                        pad(out, indent);
+                        out.push('}');
+                    }
+                    CriValue::String(it) => out.push_str(&Self::quote(it)),
+                    it => out.push_str(&it.scalar()),
+                }
+            }
+
+            /// Writes an object or array as lines indented by `indent`, a scalar as the rest of
+            /// the current line.
+            fn write_yaml(&self, out: &mut String, indent: usize) {
+                let pad = |out: &mut String, indent: usize| out.extend(::std::iter::repeat(' ').take(indent));
+                match self {
+                    CriValue::Array(items) if !items.is_empty() => {
+                        for it in items {
+                            pad(out, indent);
+                            out.push('-');
+                            it.write_yaml_nested(out, indent, true);
+                        }
+                    }
+                    CriValue::Object(fields) if !fields.is_empty() => {
+                        for (key, it) in fields {
+                            pad(out, indent);
+                            out.push_str(&Self::yaml_string(key));
+                            out.push(':');
+                            it.write_yaml_nested(out, indent, false);
+                        }
+                    }
+                    it => {
+                        out.push_str(&it.yaml_scalar());
+                        out.push('\n');
+                    }
+                }
+            }
+
+            /// Writes the value of a `key:` or `-` on the current line, objects of list items
+            /// start on it too.
+            fn write_yaml_nested(&self, out: &mut String, indent: usize, item: bool) {
+                match self {
+                    CriValue::Array(items) if !items.is_empty() => {
+                        out.push('\n');
+                        self.write_yaml(out, indent + 2);
+                    }
+                    CriValue::Object(fields) if !fields.is_empty() && item => {
+                        let mut nested = String::new();
+                        self.write_yaml(&mut nested, indent + 2);
+                        out.push(' ');
+                        out.push_str(nested.trim_start());
+                    }
+                    CriValue::Object(fields) if !fields.is_empty() => {
+                        out.push('\n');
+                        self.write_yaml(out, indent + 2);
+                    }
+                    it => {
+                        out.push(' ');
+                        out.push_str(&it.yaml_scalar());
+                        out.push('\n');
+                    }
+                }
+            }
+
+            fn scalar(&self) -> String {
+                match self {
+                    CriValue::Null => "null".to_string(),
+                    CriValue::Bool(it) => it.to_string(),
+                    CriValue::Number(it) => it.clone(),
+                    CriValue::String(it) => Self::quote(it),
+                    CriValue::Array(_) => "[]".to_string(),
+                    CriValue::Object(_) => "{}".to_string(),
+                }
+            }
+
+            fn yaml_scalar(&self) -> String {
+                match self {
+                    CriValue::String(it) => Self::yaml_string(it),
+                    it => it.scalar(),
+                }
+            }
+
+            /// `value` as a plain YAML scalar when it reads back as the same string, quoted
+            /// otherwise.
+            fn yaml_string(value: &str) -> String {
+                let plain = !value.is_empty()
+                    && value.trim() == value
+                    && !value.starts_with(|it: char| "-?:,[]{}#&*!|>'\"%@`".contains(it))
+                    && !value.contains(": ")
+                    && !value.contains(" #")
+                    && !value.contains(|it: char| it.is_control())
+                    && !["true", "false", "null", "~", "yes", "no", "on", "off"].contains(&value.to_lowercase().as_str())
+                    && value.parse::<f64>().is_err();
+                if plain {
+                    value.to_string()
+                } else {
+                    Self::quote(value)
+                }
+            }
+
+            fn quote(value: &str) -> String {
+                let mut out = String::with_capacity(value.len() + 2);
+                out.push('"');
+                for it in value.chars() {
+                    match it {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        it if (it as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", it as u32)),
+                        it => out.push(it),
+                    }
+                }
+                out.push('"');
+                out
+            }
+        }
+
+        /// Converts a CRI message into a `CriValue`, following the protobuf JSON mapping.
+        pub trait CriJson {
+            fn to_value(&self) -> CriValue;
+        }
+
+        impl CriJson for String {
+            fn to_value(&self) -> CriValue {
+                CriValue::String(self.clone())
+            }
+        }
+
+        impl CriJson for bool {
+            fn to_value(&self) -> CriValue {
+                CriValue::Bool(*self)
+            }
+        }
+
+        #(#numbers)*
+
+        impl<T: CriJson> CriJson for Option<T> {
+            fn to_value(&self) -> CriValue {
+                self.as_ref().map_or(CriValue::Null, CriJson::to_value)
+            }
+        }
+
+        impl<T: CriJson> CriJson for Vec<T> {
+            fn to_value(&self) -> CriValue {
+                CriValue::Array(self.iter().map(CriJson::to_value).collect())
+            }
+        }
+
+        /// Entries are sorted by key, so the output is stable.
+        impl<T: CriJson> CriJson for ::std::collections::HashMap<String, T> {
+            fn to_value(&self) -> CriValue {
+                let mut entries = self.iter().collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                CriValue::Object(entries.into_iter().map(|(key, it)| (key.clone(), it.to_value())).collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,975 @@
+//! The expansion engine of `#[auto_impl(...)]`, usable outside of a proc macro, e.g. from a
+//! build script with `Build` or to print what the attribute generates. `Config` describes an
+//! expansion, the attribute is a thin wrapper over `Config::from_attribute`.
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Ident, TraitItem, Token, Type, TypePath, AngleBracketedGenericArguments, GenericArgument, ConstParam, ItemMod};
+use syn::visit_mut::{visit_angle_bracketed_generic_arguments_mut, visit_const_param_mut, visit_item_mod_mut, visit_type_path_mut, VisitMut};
+
+mod access;
+mod audit;
+mod build;
+mod chaos;
+mod client;
+mod config;
+mod context;
+mod health;
+mod hooks;
+mod http;
+mod instrument;
+mod json;
+mod limits;
+mod message;
+mod method;
+mod metrics;
+mod mirror;
+mod mock;
+mod propagation;
+mod proxy;
+mod record;
+mod redact;
+mod reflection;
+mod server;
+mod validate;
+
+pub use build::Build;
+pub use config::{Config, Protos, Version};
+
+const RUNTIME_MOD: &str = "runtime_service_server";
+const RUNTIME_TRAIT: &str = "RuntimeService";
+const IMAGE_MOD: &str = "image_service_server";
+const IMAGE_TRAIT: &str = "ImageService";
+
+#[inline]
+fn remove_fisrt(segments: &mut syn::punctuated::Punctuated<syn::PathSegment, syn::Token![::]>) {
+    let mut segment: syn::punctuated::Punctuated<_, Token![::]> = syn::punctuated::Punctuated::new();
+    segments.iter().skip(1).for_each(|it| segment.push(it.clone()));
+    segments.clear();
+    segments.extend(segment);
+}
+
+/// Drops the leading `super::` of type paths, so items copied out of a version mod of the
+/// protos resolve from the mod the impls are generated in.
+pub struct SuperRemover;
+
+impl VisitMut for SuperRemover {
+    fn visit_type_path_mut(&mut self, i: &mut TypePath) {
+        visit_type_path_mut(self, i);
+
+        if i.path.segments.len() > 1 && i.path.segments.first().unwrap().ident == "super" {
+            remove_fisrt(&mut i.path.segments);
+        }
+    }
+
+    fn visit_const_param_mut(&mut self, i: &mut ConstParam) {
+        visit_const_param_mut(self, i);
+
+        if let Type::Path(p) = &mut i.ty {
+            self.visit_type_path_mut(&mut p.clone());
+        }
+    }
+
+
+    fn visit_angle_bracketed_generic_arguments_mut(&mut self, i: &mut AngleBracketedGenericArguments) {
+        visit_angle_bracketed_generic_arguments_mut(self, i);
+        i.args.iter_mut().for_each(|it| {
+            match it {
+                GenericArgument::Type(Type::Path(p)) => {
+                    self.visit_type_path_mut(p);
+                }
+                GenericArgument::Constraint(_) => {}
+                _ => {}
+            }
+        })
+    }
+}
+
+/// Hides the server mods of the services that were not requested.
+struct RedundantRemover<'a>(&'a [ServiceType]);
+impl VisitMut for RedundantRemover<'_> {
+    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
+        visit_item_mod_mut(self, i);
+
+        // remove redundant mod
+        let is_server_mod = i.ident == RUNTIME_MOD || i.ident == IMAGE_MOD;
+        if is_server_mod && !self.0.iter().any(|it| i.ident == it.get_mod()) {
+            i.vis = syn::Visibility::Inherited;
+        }
+    }
+}
+
+/// A CRI service the struct implements, `runtime` or `image` in `#[auto_impl(...)]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceType {
+    Runtime,
+    Image,
+}
+
+impl ServiceType {
+    /// The mod of the tonic server of the service in the protos, e.g. `image_service_server`.
+    pub const fn get_mod(&self) -> &str {
+        match self {
+            ServiceType::Runtime => RUNTIME_MOD,
+            ServiceType::Image => IMAGE_MOD,
+        }
+    }
+
+    /// The trait of the service, e.g. `ImageService`.
+    pub const fn get_trait(&self) -> &str {
+        match self {
+            ServiceType::Runtime => RUNTIME_TRAIT,
+            ServiceType::Image => IMAGE_TRAIT,
+        }
+    }
+
+    /// The name of the service in `#[auto_impl(...)]`.
+    pub const fn get_name(&self) -> &str {
+        match self {
+            ServiceType::Runtime => "runtime",
+            ServiceType::Image => "image",
+        }
+    }
+
+    pub fn from_ident(ident: &Ident) -> Option<Self> {
+        if ident == "runtime" {
+            Some(ServiceType::Runtime)
+        } else if ident == "image" {
+            Some(ServiceType::Image)
+        } else {
+            None
+        }
+    }
+}
+
+/// Arguments of `#[auto_impl(...)]`: one or more services followed by optional flags.
+struct AutoImplArgs {
+    services: Vec<ServiceType>,
+    context: bool,
+    authorize: bool,
+    audit: bool,
+    /// Overrides of the built-in read-only/mutating classification.
+    read_only: Vec<Ident>,
+    mutating: Vec<Ident>,
+    server: bool,
+    /// Permissions of the socket of `serve_unix`, `server::SOCKET_MODE` when not given.
+    socket_mode: Option<u32>,
+    health: bool,
+    reflection: bool,
+    layer: Option<Type>,
+    tracing: bool,
+    /// Parents the `tracing` spans to the caller's trace context, implies `tracing`.
+    opentelemetry: bool,
+    metrics: bool,
+    log: bool,
+    /// Fields hidden by the `log` layer, `redact::DEFAULT_REDACTED` when not given.
+    redact: Option<Vec<redact::RedactedField>>,
+    limits: Vec<limits::MethodLimit>,
+    /// Extra rules of `validate`, `None` when requests are not validated.
+    validate: Option<Vec<validate::FieldRule>>,
+    mock: bool,
+    client: bool,
+    proxy: bool,
+    /// The version a `proxy` forwards the calls of both versions to.
+    bridge: Option<Ident>,
+    mirror: bool,
+    record: bool,
+    chaos: bool,
+    json: bool,
+}
+
+impl syn::parse::Parse for AutoImplArgs {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut args = AutoImplArgs {
+            services: Vec::new(),
+            context: false,
+            authorize: false,
+            audit: false,
+            read_only: Vec::new(),
+            mutating: Vec::new(),
+            server: false,
+            socket_mode: None,
+            health: false,
+            reflection: false,
+            layer: None,
+            tracing: false,
+            opentelemetry: false,
+            metrics: false,
+            log: false,
+            redact: None,
+            limits: Vec::new(),
+            validate: None,
+            mock: false,
+            client: false,
+            proxy: false,
+            bridge: None,
+            mirror: false,
+            record: false,
+            chaos: false,
+            json: false,
+        };
+
+        // the clients and messages are all that is generated without a service
+        let mut needs_service = false;
+        while !input.is_empty() {
+            let ident = input.parse::<Ident>()?;
+            if ident != "client" && ident != "json" && ServiceType::from_ident(&ident).is_none() {
+                needs_service = true;
+            }
+            if input.peek(Token![=]) {
+                input.parse::<Token![=]>()?;
+                if ident == "socket_mode" {
+                    let lit = input.parse::<syn::LitInt>()?;
+                    let mode = lit.base10_parse::<u32>()?;
+                    if mode > 0o777 {
+                        return Err(syn::Error::new(lit.span(), "expected permission bits such as `0o660`"));
+                    }
+                    args.socket_mode = Some(mode);
+                } else if ident == "layer" {
+                    args.layer = Some(input.parse::<Type>()?);
+                } else if ident == "read_only" || ident == "mutating" {
+                    let content;
+                    syn::bracketed!(content in input);
+                    let methods = content.parse_terminated::<Ident, Token![,]>(syn::parse::Parse::parse)?;
+                    if ident == "read_only" {
+                        args.read_only.extend(methods);
+                    } else {
+                        args.mutating.extend(methods);
+                    }
+                } else if ident == "redact" {
+                    let content;
+                    syn::bracketed!(content in input);
+                    let fields = content.parse_terminated::<redact::RedactedField, Token![,]>(syn::parse::Parse::parse)?;
+                    args.redact = Some(fields.into_iter().collect());
+                } else if ident == "bridge" {
+                    let version = input.parse::<Ident>()?;
+                    if version != "v1" && version != "v1alpha2" {
+                        return Err(syn::Error::new(version.span(), "expected `v1` or `v1alpha2`"));
+                    }
+                    args.proxy = true;
+                    args.bridge = Some(version);
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!("unknown auto_impl option `{}`", ident),
+                    ));
+                }
+            } else if ident == "limits" && input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in input);
+                let limits = content.parse_terminated::<limits::MethodLimit, Token![,]>(syn::parse::Parse::parse)?;
+                args.limits.extend(limits);
+            } else if ident == "validate" {
+                let mut rules = Vec::new();
+                if input.peek(syn::token::Paren) {
+                    let content;
+                    syn::parenthesized!(content in input);
+                    rules = validate::parse_rules(&content)?;
+                }
+                args.validate = Some(rules);
+            } else if let Some(service_type) = ServiceType::from_ident(&ident) {
+                if !args.services.contains(&service_type) {
+                    args.services.push(service_type);
+                }
+            } else if ident == "context" {
+                args.context = true;
+            } else if ident == "server" {
+                args.server = true;
+            } else if ident == "health" {
+                args.health = true;
+            } else if ident == "reflection" {
+                args.reflection = true;
+            } else if ident == "tracing" {
+                args.tracing = true;
+            } else if ident == "opentelemetry" {
+                args.tracing = true;
+                args.opentelemetry = true;
+            } else if ident == "metrics" {
+                args.metrics = true;
+            } else if ident == "authorize" {
+                args.authorize = true;
+            } else if ident == "audit" {
+                args.audit = true;
+            } else if ident == "log" {
+                args.log = true;
+            } else if ident == "mock" {
+                args.mock = true;
+            } else if ident == "client" {
+                args.client = true;
+            } else if ident == "proxy" {
+                args.proxy = true;
+            } else if ident == "mirror" {
+                args.mirror = true;
+            } else if ident == "record" {
+                args.record = true;
+            } else if ident == "chaos" {
+                args.chaos = true;
+            } else if ident == "json" {
+                args.json = true;
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("unknown auto_impl option `{}`", ident),
+                ));
+            }
+
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+
+        if args.services.is_empty() && (needs_service || !(args.client || args.json)) {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "proto type is required(runtime or image)",
+            ));
+        }
+        if args.socket_mode.is_some() && !args.server {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`socket_mode` sets the permissions of the generated server's socket, add `server`",
+            ));
+        }
+        if (args.health || args.reflection) && !args.server {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`health` and `reflection` are registered by the generated server, add `server`",
+            ));
+        }
+        if args.redact.is_some() && !args.log {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`redact` configures the logging layer, add `log`",
+            ));
+        }
+
+        Ok(args)
+    }
+}
+
+impl AutoImplArgs {
+    /// Fields hidden by the `log` layer.
+    fn redacted(&self) -> Vec<redact::RedactedField> {
+        self.redact.clone().unwrap_or_else(redact::default_fields)
+    }
+
+    /// Whether `CriClient` is generated, which `proxy` forwards through.
+    fn clients(&self) -> bool {
+        self.client || self.proxy
+    }
+
+    /// Whether `method` changes runtime state, `read_only`/`mutating` take precedence over the
+    /// built-in table.
+    fn is_mutating(&self, method: &Ident) -> bool {
+        if self.mutating.contains(method) {
+            true
+        } else if self.read_only.contains(method) {
+            false
+        } else {
+            access::is_mutating(&method.to_string())
+        }
+    }
+}
+
+fn expand(
+    mut file: syn::File,
+    container: Ident,
+    args: &AutoImplArgs,
+    struct_ast: &syn::ItemStruct,
+    bridge: Option<&proxy::Bridge>,
+    metrics: &mut Vec<metrics::MetricsEntry>,
+) -> proc_macro2::TokenStream {
+    RedundantRemover(&args.services).visit_file_mut(&mut file);
+
+    let rules = args.validate.as_ref().map(|it| validate::Rules::new(&file, it));
+    let (impls, use_blocks): (Vec<_>, Vec<_>) = args
+        .services
+        .iter()
+        .map(|service_type| {
+            expand_service(&file, &container, service_type, args, rules.as_ref(), struct_ast, bridge, metrics)
+        })
+        .unzip();
+
+    let validate_impls = match &rules {
+        Some(rules) => validate::expand_validate_impls(&file, rules),
+        None => quote! {},
+    };
+
+    let client = if args.clients() {
+        client::expand_client(&file, args.opentelemetry)
+    } else {
+        quote! {}
+    };
+
+    let redact_impls = if args.log {
+        redact::expand_redact_impls(&file, &args.redacted())
+    } else {
+        quote! {}
+    };
+
+    let diff_impls = if args.mirror {
+        mirror::expand_diff_impls(&file)
+    } else {
+        quote! {}
+    };
+
+    let chaos_impls = if args.chaos {
+        chaos::expand_chaos_impls(&file)
+    } else {
+        quote! {}
+    };
+
+    let json_impls = if args.json {
+        json::expand_json_impls(&file)
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #[allow(deprecated)]
+        pub mod #container {
+            #file
+
+            #(#impls)*
+            #client
+            #redact_impls
+            #diff_impls
+            #chaos_impls
+            #json_impls
+            #validate_impls
+        }
+        #(#use_blocks)*
+    }
+}
+
+/// Generates the forwarding impl of one service trait and the `use` of its alias.
+#[allow(clippy::too_many_arguments)]
+fn expand_service(
+    file: &syn::File,
+    container: &Ident,
+    service_type: &ServiceType,
+    args: &AutoImplArgs,
+    rules: Option<&validate::Rules>,
+    struct_ast: &syn::ItemStruct,
+    bridge: Option<&proxy::Bridge>,
+    metrics: &mut Vec<metrics::MetricsEntry>,
+) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+    let version = quote::format_ident!("{}", change_case::pascal_case(&container.to_string()));
+    let struct_name = struct_ast.ident.clone();
+    let trait_name = quote::format_ident!("{}", service_type.get_trait());
+    let trait_meta = file.items.iter().find_map::<syn::ItemMod, _>(|item| {
+        if let syn::Item::Mod(m) = item {
+            if service_type.get_mod() == m.ident.to_string().as_str() {
+                Some(m.clone())
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    })
+        .unwrap_or_else(|| panic!("{} is not found", service_type.get_mod()))
+        .content
+        .unwrap_or_else(|| panic!("{} is empty mod", service_type.get_mod()));
+    let trait_meta = trait_meta
+        .1
+        .iter()
+        .find_map(|it| {
+            if let syn::Item::Trait(t) = it {
+                if service_type.get_trait() == t.ident.to_string().as_str() {
+                    Some(t)
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        })
+        .unwrap_or_else(|| panic!("find {} faild", service_type.get_trait()));
+
+    let streams = trait_meta.items.iter().filter_map(|it| {
+        if let TraitItem::Type(t) = it {
+            Some(t)
+        } else {
+            None
+        }
+    }).collect::<Vec<_>>();
+    let stream_types = streams.iter().map(|it| it.ident.to_string()).collect::<Vec<_>>();
+    // the stream types the struct's methods return, before `server` wraps them
+    let inner_streams = streams.iter().map(|t| {
+        let name = &t.ident;
+        let stream = if args.proxy {
+            proxy::stream_type(t)
+        } else {
+            quote! { super::#name }
+        };
+        (name.clone(), stream)
+    }).collect::<Vec<_>>();
+    let mut methods = Vec::new();
+    let mut mock_items = Vec::new();
+    let mut mock_handles = Vec::new();
+    let mut proxy_items = Vec::new();
+    let mut mirror_items = Vec::new();
+    let mut replay_arms = Vec::new();
+    let mut chaos_items = Vec::new();
+    let trait_alias = quote::format_ident!("{}_{}", service_type.get_trait(), container);
+    let mut trait_items = trait_meta.items.clone();
+    let trait_items = trait_items.iter_mut().filter_map(|it| {
+        match it {
+            TraitItem::Method(m) => {
+                SuperRemover.visit_trait_item_method_mut(m);
+
+                let method = method::MethodInfo::new(m, &stream_types);
+                let mut body = method::MethodBody::new(&method);
+
+                // forward to the `<Service>Proxy` impl, whose defaults call the upstream
+                if args.proxy {
+                    body.call = proxy::proxy_call(service_type, &struct_name, &method);
+                    let proxied = proxy::proxy_method(m, &method, container, service_type, &streams, bridge, args.opentelemetry);
+                    proxy_items.extend(proxied);
+                }
+
+                if args.mock {
+                    if let Some((item, handle)) = mock::mock_method(m, &method, container, service_type, &struct_name) {
+                        mock_items.push(item);
+                        mock_handles.push(handle);
+                    }
+                }
+
+                // innermost, so `validate` and `authorize` wrapped around it run first
+                if args.layer.is_some() {
+                    hooks::hooks_body(&mut body, service_type, &method);
+                }
+
+                if let Some(rules) = rules {
+                    validate::validate_body(&mut body, rules, &method);
+                }
+
+                let mutating = args.is_mutating(&method.name);
+
+                if args.mirror {
+                    mirror_items.extend(mirror::mirror_method(m, &method, container, &trait_name, &trait_alias, mutating));
+                }
+
+                if args.chaos {
+                    chaos_items.extend(chaos::chaos_method(m, &method, container, &trait_alias));
+                }
+
+                if args.authorize {
+                    access::authorize_body(&mut body, &method, mutating);
+                }
+
+                if args.audit && mutating {
+                    audit::audit_body(&mut body, file, container, &trait_name, &method);
+                }
+
+                // attach the `CriContext` to the request before handing it over
+                if let (Some(request), true) = (method.request(), args.context) {
+                    body.before.push(quote! {
+                        let ctx = super::CriContext::from_request(&#request, super::CriVersion::#version);
+                        #request.extensions_mut().insert(ctx);
+                    });
+                }
+
+                if args.tracing {
+                    instrument::tracing_body(&mut body, file, container, &trait_name, &method, args.opentelemetry);
+                }
+
+                if args.log {
+                    redact::log_body(&mut body, container, &trait_name, &method);
+                }
+
+                if args.record {
+                    record::record_body(&mut body, container, &trait_name, &method);
+                    replay_arms.extend(record::replay_arm(&method, &trait_alias));
+                }
+
+                if args.metrics {
+                    metrics::metrics_body(&mut body, metrics, container, &trait_name, &method);
+                }
+
+                limits::limits_body(&mut body, &args.limits, &method);
+
+                // server streams are wrapped so they end once the server drains
+                if let (Some(request), true) = (method.request(), method.is_stream && args.server) {
+                    let call = &body.call;
+                    body.before.push(quote! {
+                        let shutdown = #request.extensions().get::<super::CriShutdown>().cloned();
+                    });
+                    body.call = quote! {
+                        #call.map(|it| it.map(|it| super::CriDrainStream::new(it, shutdown)))
+                    };
+                }
+
+                m.default = Some(body.into_block(&method));
+                methods.push(method);
+
+                Some(quote! {
+                    #m
+                })
+            }
+            TraitItem::Type(t) => {
+                if args.mock {
+                    mock_items.push(mock::mock_stream_type(t));
+                }
+
+                let name = quote::format_ident!("{}", t.ident);
+                let (_, stream) = inner_streams.iter().find(|(it, _)| *it == t.ident)?;
+                if args.server {
+                    Some(quote! {
+                        type #name = super::CriDrainStream<#stream>;
+                    })
+                } else {
+                    Some(quote! {
+                        type #name = #stream;
+                    })
+                }
+            }
+            _ => { None /* not used */ }
+        }
+    }).collect::<Vec<_>>();
+
+    let hooks_trait = if args.layer.is_some() {
+        hooks::expand_hooks_trait(service_type, &methods, &inner_streams)
+    } else {
+        quote! {}
+    };
+
+    let proxy_trait = if args.proxy {
+        proxy::expand_proxy_trait(service_type, &proxy_items)
+    } else {
+        quote! {}
+    };
+
+    let service_mod = quote::format_ident!("{}", service_type.get_mod());
+
+    let mock = if args.mock {
+        mock::expand_mock_service(&trait_alias, &struct_name, &mock_items, &mock_handles)
+    } else {
+        quote! {}
+    };
+
+    let mirror = if args.mirror {
+        mirror::expand_mirror_service(&trait_alias, &streams, &mirror_items)
+    } else {
+        quote! {}
+    };
+
+    let replay = if args.record {
+        record::expand_replay_service(service_type, &trait_alias, &replay_arms)
+    } else {
+        quote! {}
+    };
+
+    let chaos = if args.chaos {
+        chaos::expand_chaos_service(&trait_alias, &streams, &chaos_items)
+    } else {
+        quote! {}
+    };
+
+    (
+        quote! {
+            #[tonic::async_trait]
+            impl super::#trait_alias for super::#struct_name {
+                #(#trait_items)*
+            }
+
+            #hooks_trait
+            #proxy_trait
+            #mock
+            #mirror
+            #replay
+            #chaos
+        },
+        quote! {
+            use #container::#service_mod::#trait_name as #trait_alias;
+        },
+    )
+}
+
+/// Checks the options that refer to the proto files, so mistakes fail at macro time.
+fn check_args(args: &AutoImplArgs, files: &[&syn::File]) -> syn::Result<()> {
+    if args.log {
+        redact::check_fields(files, &args.redacted())?;
+    }
+    if let Some(rules) = &args.validate {
+        validate::check_rules(files, rules)?;
+    }
+
+    let methods = files
+        .iter()
+        .flat_map(|file| args.services.iter().flat_map(|it| trait_methods(file, it)))
+        .collect::<Vec<_>>();
+    let streams = files
+        .iter()
+        .flat_map(|file| args.services.iter().flat_map(|it| trait_stream_methods(file, it)))
+        .collect::<Vec<_>>();
+    limits::check_methods(&args.limits, &methods, &streams)?;
+    for method in args.read_only.iter().chain(&args.mutating) {
+        if !methods.iter().any(|it| method == it) {
+            return Err(syn::Error::new(
+                method.span(),
+                format!("`{}` is not a method of the generated services", method),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The trait of `service_type` in `file`.
+fn service_trait<'a>(file: &'a syn::File, service_type: &ServiceType) -> Option<&'a syn::ItemTrait> {
+    file.items
+        .iter()
+        .filter_map(|it| match it {
+            syn::Item::Mod(m) if m.ident == service_type.get_mod() => m.content.as_ref(),
+            _ => None,
+        })
+        .flat_map(|(_, items)| items)
+        .find_map(|it| match it {
+            syn::Item::Trait(t) if t.ident == service_type.get_trait() => Some(t),
+            _ => None,
+        })
+}
+
+/// Method names of the trait of `service_type` in `file`.
+fn trait_methods(file: &syn::File, service_type: &ServiceType) -> Vec<String> {
+    service_trait(file, service_type)
+        .into_iter()
+        .flat_map(|t| &t.items)
+        .filter_map(|it| match it {
+            TraitItem::Method(m) => Some(m.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Server-streaming methods of the trait of `service_type` in `file`.
+fn trait_stream_methods(file: &syn::File, service_type: &ServiceType) -> Vec<String> {
+    let Some(service) = service_trait(file, service_type) else {
+        return Vec::new();
+    };
+    let stream_types = service
+        .items
+        .iter()
+        .filter_map(|it| match it {
+            TraitItem::Type(t) => Some(t.ident.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    service
+        .items
+        .iter()
+        .filter_map(|it| match it {
+            TraitItem::Method(m) if method::MethodInfo::new(m, &stream_types).is_stream => Some(m.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Expands `#[auto_impl(args)]` on `struct_ast` against `protos`, both versions.
+fn expand_attribute(args: AutoImplArgs, struct_ast: syn::ItemStruct, protos: &Protos) -> syn::Result<TokenStream> {
+    let file_v1 = syn::parse_file(&protos.v1)?;
+    let file_v1alpha2 = syn::parse_file(&protos.v1alpha2)?;
+    check_args(&args, &[&file_v1, &file_v1alpha2])?;
+
+    let bridge_file = args.bridge.as_ref().map(|it| if it == "v1" { file_v1.clone() } else { file_v1alpha2.clone() });
+    let bridge = args.bridge.clone().zip(bridge_file.as_ref()).map(|(container, file)| proxy::Bridge { container, file });
+
+    let mut metric_entries = Vec::new();
+    let container_v1 = quote::format_ident!("v1");
+    let v1 = expand(file_v1, container_v1.clone(), &args, &struct_ast, bridge.as_ref(), &mut metric_entries);
+    let container_v1alpha2 = quote::format_ident!("v1alpha2");
+    let v1alpha2 = expand(file_v1alpha2, container_v1alpha2.clone(), &args, &struct_ast, bridge.as_ref(), &mut metric_entries);
+
+
+    let context = if args.context {
+        context::expand_context()
+    } else {
+        quote! {}
+    };
+
+    let credentials = if args.authorize || args.audit {
+        access::expand_credentials()
+    } else {
+        quote! {}
+    };
+
+    let authorize = if args.authorize {
+        access::expand_authorize(&struct_ast)
+    } else {
+        quote! {}
+    };
+
+    let audit = if args.audit {
+        let audit_ty = syn::parse_quote! { CriAuditLog };
+        match hooks::field_of_type(&struct_ast, &audit_ty) {
+            Some(field) => audit::expand_audit(&struct_ast, &field),
+            None => {
+                return Err(syn::Error::new(Span::call_site(), "`audit` needs a field of type `CriAuditLog` to write to"));
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let proxy = if args.proxy {
+        let upstream_ty = syn::parse_quote! { CriUpstream };
+        match hooks::field_of_type(&struct_ast, &upstream_ty) {
+            Some(field) => proxy::expand_proxy(&struct_ast, &field),
+            None => {
+                return Err(syn::Error::new(Span::call_site(), "`proxy` needs a field of type `CriUpstream` to forward to"));
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let record = if args.record {
+        let recorder_ty = syn::parse_quote! { CriRecorder };
+        match hooks::field_of_type(&struct_ast, &recorder_ty) {
+            Some(field) => record::expand_record(&struct_ast, &field, &[container_v1.clone(), container_v1alpha2.clone()], &args.services),
+            None => {
+                return Err(syn::Error::new(Span::call_site(), "`record` needs a field of type `CriRecorder` to write to"));
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let bridge = if args.bridge.is_some() {
+        proxy::expand_bridge()
+    } else {
+        quote! {}
+    };
+
+    let health = if args.health {
+        health::expand_health(protos)?
+    } else {
+        quote! {}
+    };
+
+    let hooks = match &args.layer {
+        Some(layer) => hooks::expand_hooks_accessor(layer, &struct_ast),
+        None => quote! {},
+    };
+
+    let metrics = if args.metrics {
+        metrics::expand_metrics(&metric_entries)
+    } else {
+        quote! {}
+    };
+
+    let http = if args.metrics || args.chaos {
+        http::expand_http()
+    } else {
+        quote! {}
+    };
+
+    let limits = limits::expand_limits(&args.limits);
+
+    let client = if args.clients() {
+        client::expand_connector()
+    } else {
+        quote! {}
+    };
+
+    let mock = if args.mock {
+        mock::expand_mock(&struct_ast.ident)
+    } else {
+        quote! {}
+    };
+
+    let mirror = if args.mirror {
+        mirror::expand_mirror()
+    } else {
+        quote! {}
+    };
+
+    let chaos = if args.chaos {
+        chaos::expand_chaos()
+    } else {
+        quote! {}
+    };
+
+    let field_path = if args.mirror || args.chaos {
+        message::expand_field_path()
+    } else {
+        quote! {}
+    };
+
+    let json = if args.json {
+        json::expand_json()
+    } else {
+        quote! {}
+    };
+
+    let validate = if args.validate.is_some() {
+        validate::expand_validate_trait()
+    } else {
+        quote! {}
+    };
+
+    let redact = if args.log {
+        redact::expand_redact_trait(&args.redacted())
+    } else {
+        quote! {}
+    };
+
+    let trace_context = if args.opentelemetry {
+        propagation::expand_trace_context()
+    } else {
+        quote! {}
+    };
+
+    let reflection = if args.reflection {
+        reflection::expand_reflection(protos)?
+    } else {
+        quote! {}
+    };
+
+    let server = if args.server {
+        server::expand_server(&[container_v1, container_v1alpha2], &args, &struct_ast.ident)
+    } else {
+        quote! {}
+    };
+
+    let doc = format!(
+        "Generated by `#[derive(auto_impl({}))]`",
+        args.services.iter().map(ServiceType::get_name).collect::<Vec<_>>().join(", ")
+    );
+
+    Ok(quote! {
+        #[doc = #doc]
+        #struct_ast
+
+        #context
+        #credentials
+        #authorize
+        #audit
+        #server
+        #health
+        #reflection
+        #hooks
+        #metrics
+        #http
+        #trace_context
+        #redact
+        #validate
+        #limits
+        #mock
+        #mirror
+        #chaos
+        #field_path
+        #json
+        #client
+        #proxy
+        #bridge
+        #record
+
+        #[doc = "Version v1 impls generated by auto_impl"]
+        #v1
+        #[doc = "Version v1alpha2 Impls generated by auto_impl"]
+        #v1alpha2
+    })
+}
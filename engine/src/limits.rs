@@ -0,0 +1,185 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::{LitInt, LitStr, Token};
+
+use crate::method::{MethodBody, MethodInfo};
+
+/// An entry of `limits(...)`: `method(timeout = "10s", concurrency = 2)`.
+pub(crate) struct MethodLimit {
+    method: Ident,
+    /// Maximum duration in milliseconds.
+    timeout: Option<(u64, LitStr)>,
+    concurrency: Option<usize>,
+}
+
+impl syn::parse::Parse for MethodLimit {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let method = input.parse::<Ident>()?;
+        let mut limit = MethodLimit {
+            method,
+            timeout: None,
+            concurrency: None,
+        };
+
+        let content;
+        syn::parenthesized!(content in input);
+        while !content.is_empty() {
+            let key = content.parse::<Ident>()?;
+            content.parse::<Token![=]>()?;
+            if key == "timeout" {
+                let lit = content.parse::<LitStr>()?;
+                let millis = parse_duration(&lit.value())
+                    .ok_or_else(|| syn::Error::new(lit.span(), "expected a duration such as \"500ms\", \"30s\", \"10m\" or \"1h\""))?;
+                limit.timeout = Some((millis, lit));
+            } else if key == "concurrency" {
+                let lit = content.parse::<LitInt>()?;
+                let max = lit.base10_parse::<usize>()?;
+                if max == 0 {
+                    return Err(syn::Error::new(lit.span(), "concurrency must be at least 1"));
+                }
+                limit.concurrency = Some(max);
+            } else {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!("unknown limit `{}`, expected `timeout` or `concurrency`", key),
+                ));
+            }
+
+            if content.is_empty() {
+                break;
+            }
+            content.parse::<Token![,]>()?;
+        }
+
+        Ok(limit)
+    }
+}
+
+/// Milliseconds of `"500ms"`, `"30s"`, `"10m"` or `"1h"`.
+fn parse_duration(value: &str) -> Option<u64> {
+    let split = value.find(|it: char| !it.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(split);
+    let amount = amount.parse::<u64>().ok()?;
+    let unit = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return None,
+    };
+    amount.checked_mul(unit).filter(|it| *it > 0)
+}
+
+/// Checks that every limited method belongs to one of the generated traits and is unary. The
+/// limits of a server-streaming method would end when its stream is returned, not when it ends.
+pub(crate) fn check_methods(limits: &[MethodLimit], methods: &[String], streams: &[String]) -> syn::Result<()> {
+    for limit in limits {
+        if !methods.iter().any(|it| limit.method == it) {
+            return Err(syn::Error::new(
+                limit.method.span(),
+                format!("`{}` is not a method of the generated services", limit.method),
+            ));
+        }
+        if streams.iter().any(|it| limit.method == it) {
+            return Err(syn::Error::new(
+                limit.method.span(),
+                format!("`{}` is a server-streaming method, `limits` only applies to unary calls", limit.method),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn permits_ident(method: &Ident) -> Ident {
+    quote::format_ident!("CRI_PERMITS_{}", method.to_string().to_uppercase())
+}
+
+/// Enforces the limits of `method`, the call fails with `ResourceExhausted` when all its
+/// permits are taken and with `DeadlineExceeded` when it runs longer than its timeout.
+pub(crate) fn limits_body(body: &mut MethodBody, limits: &[MethodLimit], method: &MethodInfo) {
+    let Some(limit) = limits.iter().find(|it| it.method == method.name) else {
+        return;
+    };
+    let name = method.name.to_string();
+
+    if let Some((millis, lit)) = &limit.timeout {
+        let call = &body.call;
+        let message = format!("{} exceeded its timeout of {}", name, lit.value());
+        body.call = quote! {
+            match tokio::time::timeout(::std::time::Duration::from_millis(#millis), async { #call }).await {
+                Ok(it) => it,
+                Err(_) => Err(tonic::Status::deadline_exceeded(#message)),
+            }
+        };
+    }
+
+    if let Some(max) = limit.concurrency {
+        let call = &body.call;
+        let permits = permits_ident(&method.name);
+        let message = format!("{} is limited to {} concurrent calls", name, max);
+        body.call = quote! {
+            match super::#permits.try_acquire() {
+                Some(_permit) => #call,
+                None => Err(tonic::Status::resource_exhausted(#message)),
+            }
+        };
+    }
+}
+
+/// Emits `CriPermits` and one static per method with a concurrency limit, shared by both
+/// versions of the method.
+pub(crate) fn expand_limits(limits: &[MethodLimit]) -> TokenStream {
+    let statics = limits.iter().filter_map(|limit| {
+        let max = limit.concurrency?;
+        let permits = permits_ident(&limit.method);
+        Some(quote! {
+            static #permits: CriPermits = CriPermits::new(#max);
+        })
+    }).collect::<Vec<_>>();
+    if statics.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        /// Concurrency limit of a generated method, calls over it are rejected rather than queued.
+        #[derive(Debug)]
+        pub struct CriPermits {
+            active: ::std::sync::atomic::AtomicUsize,
+            max: usize,
+        }
+
+        /// Held for the duration of a call, the permit is returned on drop.
+        pub struct CriPermit(&'static CriPermits);
+
+        impl CriPermits {
+            pub const fn new(max: usize) -> Self {
+                CriPermits {
+                    active: ::std::sync::atomic::AtomicUsize::new(0),
+                    max,
+                }
+            }
+
+            pub fn try_acquire(&'static self) -> Option<CriPermit> {
+                use ::std::sync::atomic::Ordering;
+
+                self.active
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |it| (it < self.max).then_some(it + 1))
+                    .ok()
+                    .map(|_| CriPermit(self))
+            }
+
+            /// Calls currently holding a permit.
+            pub fn active(&self) -> usize {
+                self.active.load(::std::sync::atomic::Ordering::Acquire)
+            }
+        }
+
+        impl Drop for CriPermit {
+            fn drop(&mut self) {
+                self.0.active.fetch_sub(1, ::std::sync::atomic::Ordering::AcqRel);
+            }
+        }
+
+        #(#statics)*
+    }
+}
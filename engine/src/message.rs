@@ -0,0 +1,86 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::Type;
+
+/// Finds the prost message struct named like the last segment of `ty` in `file`.
+pub(crate) fn find_message<'a>(file: &'a syn::File, ty: &Type) -> Option<&'a syn::ItemStruct> {
+    let name = match ty {
+        Type::Path(p) => &p.path.segments.last()?.ident,
+        _ => return None,
+    };
+    file.items.iter().find_map(|it| {
+        match it {
+            syn::Item::Struct(s) if &s.ident == name => Some(s),
+            _ => None,
+        }
+    })
+}
+
+/// Identifiers of the objects a request targets, as `(name, expression)` pairs.
+///
+/// The expressions evaluate to a `&str` read from `request`, a `tonic::Request` of the
+/// message. Only `pod_sandbox_id`, `container_id` and the `image` of an `ImageSpec` are
+/// considered.
+pub(crate) fn target_ids(file: &syn::File, ty: &Type, request: &Ident) -> Vec<(&'static str, TokenStream)> {
+    let message = match find_message(file, ty) {
+        Some(it) => it,
+        None => return Vec::new(),
+    };
+
+    message.fields.iter().filter_map(|field| {
+        let ident = field.ident.as_ref()?;
+        let field_ty = &field.ty;
+        let field_ty = quote! { #field_ty }.to_string();
+        if ident == "pod_sandbox_id" {
+            Some(("pod_sandbox_id", quote! { #request.get_ref().pod_sandbox_id.as_str() }))
+        } else if ident == "container_id" {
+            Some(("container_id", quote! { #request.get_ref().container_id.as_str() }))
+        } else if ident == "image" && field_ty.contains("ImageSpec") {
+            Some(("image", quote! { #request.get_ref().image.as_ref().map_or("", |it| it.image.as_str()) }))
+        } else {
+            None
+        }
+    }).collect()
+}
+
+/// Top-level structs of `file`, i.e. its prost messages.
+pub(crate) fn messages(file: &syn::File) -> impl Iterator<Item = &syn::ItemStruct> {
+    file.items.iter().filter_map(|it| match it {
+        syn::Item::Struct(s) => Some(s),
+        _ => None,
+    })
+}
+
+/// Last path segment of `ty` and its generic type arguments, e.g. `("Vec", [T])`.
+pub(crate) fn segment(ty: &Type) -> Option<(String, Vec<&Type>)> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let last = p.path.segments.last()?;
+    let args = match &last.arguments {
+        syn::PathArguments::AngleBracketed(a) => a
+            .args
+            .iter()
+            .filter_map(|it| match it {
+                syn::GenericArgument::Type(t) => Some(t),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    Some((last.ident.to_string(), args))
+}
+
+/// Emits `cri_field_path`, joining a message field to the path of its message, e.g.
+/// `config.metadata.name`: the paths `chaos` matches and `mirror` reports.
+pub(crate) fn expand_field_path() -> TokenStream {
+    quote! {
+        fn cri_field_path(path: &str, field: &str) -> String {
+            if path.is_empty() {
+                field.to_string()
+            } else {
+                format!("{}.{}", path, field)
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{Block, Type};
+
+/// What the generated features need to know about one trait method.
+pub(crate) struct MethodInfo {
+    pub(crate) name: Ident,
+    /// Arguments forwarded to the user's method, the first one is the request.
+    pub(crate) args: Vec<Ident>,
+    /// `X` of the `tonic::Request<X>` argument.
+    pub(crate) request_ty: Option<Type>,
+    /// Declared return type, `Result<tonic::Response<Y>, tonic::Status>`.
+    pub(crate) output_ty: Option<Type>,
+    pub(crate) is_async: bool,
+    /// The response is a server stream, i.e. the output refers to a `Self::...Stream`.
+    pub(crate) is_stream: bool,
+}
+
+impl MethodInfo {
+    pub(crate) fn new(m: &syn::TraitItemMethod, stream_types: &[String]) -> Self {
+        let args = m.sig.inputs.iter().filter_map(|it| {
+            match it {
+                syn::FnArg::Typed(t) => {
+                    match *t.pat {
+                        syn::Pat::Ident(ref i) => {
+                            Some(i.ident.clone())
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None
+            }
+        }).collect::<Vec<_>>();
+
+        let request_ty = m.sig.inputs.iter().find_map(|it| {
+            if let syn::FnArg::Typed(t) = it {
+                first_generic_argument(&t.ty)
+            } else {
+                None
+            }
+        });
+
+        let output_ty = match &m.sig.output {
+            syn::ReturnType::Type(_, ty) => Some((**ty).clone()),
+            syn::ReturnType::Default => None,
+        };
+
+        let output = m.sig.output.to_token_stream().to_string();
+        let is_stream = stream_types.iter().any(|it| output.contains(it.as_str()));
+
+        MethodInfo {
+            name: m.sig.ident.clone(),
+            args,
+            request_ty,
+            output_ty,
+            is_async: m.sig.asyncness.is_some(),
+            is_stream,
+        }
+    }
+
+    pub(crate) fn request(&self) -> Option<&Ident> {
+        self.args.first()
+    }
+
+    /// `Y` of the `Result<tonic::Response<Y>, tonic::Status>` output.
+    pub(crate) fn response_ty(&self) -> Option<Type> {
+        first_generic_argument(self.output_ty.as_ref()?).and_then(|it| first_generic_argument(&it))
+    }
+}
+
+/// `Item` type of a server stream type declared by a service trait, such as
+/// `Result<super::ContainerEventResponse, tonic::Status>`.
+pub(crate) fn stream_item(t: &syn::TraitItemType) -> Option<Type> {
+    t.bounds.iter().find_map(|bound| {
+        let syn::TypeParamBound::Trait(bound) = bound else {
+            return None;
+        };
+        let syn::PathArguments::AngleBracketed(args) = &bound.path.segments.last()?.arguments else {
+            return None;
+        };
+        args.args.iter().find_map(|it| match it {
+            syn::GenericArgument::Binding(b) if b.ident == "Item" => Some(b.ty.clone()),
+            _ => None,
+        })
+    })
+}
+
+/// `T` of a single segment generic type such as `tonic::Request<T>`.
+pub(crate) fn first_generic_argument(ty: &Type) -> Option<Type> {
+    if let Type::Path(p) = ty {
+        if let syn::PathArguments::AngleBracketed(a) = &p.path.segments.last()?.arguments {
+            return a.args.iter().find_map(|it| {
+                if let syn::GenericArgument::Type(t) = it {
+                    Some(t.clone())
+                } else {
+                    None
+                }
+            });
+        }
+    }
+    None
+}
+
+/// Body of a generated trait method, assembled by the enabled features.
+///
+/// Features push statements that run before the forwarded call (the request is rebound
+/// as mutable first) and after it (the result is bound to `response`), or wrap the call
+/// expression itself.
+pub(crate) struct MethodBody {
+    pub(crate) before: Vec<TokenStream>,
+    pub(crate) call: TokenStream,
+    pub(crate) after: Vec<TokenStream>,
+    /// Whether the wrapped call borrows the request mutably, which needs the rebinding too.
+    pub(crate) mutable_request: bool,
+}
+
+impl MethodBody {
+    pub(crate) fn new(method: &MethodInfo) -> Self {
+        let name = &method.name;
+        let args = &method.args;
+        let call = if method.is_async {
+            quote! { self.#name(#(#args),*).await }
+        } else {
+            quote! { self.#name(#(#args),*) }
+        };
+
+        MethodBody {
+            before: Vec::new(),
+            call,
+            after: Vec::new(),
+            mutable_request: false,
+        }
+    }
+
+    pub(crate) fn into_block(self, method: &MethodInfo) -> Block {
+        let MethodBody { before, call, after, mutable_request } = self;
+
+        let output_ty = method.output_ty.as_ref().map(|it| quote! { : #it });
+        let tokens = match method.request() {
+            Some(request) if !before.is_empty() || !after.is_empty() || mutable_request => {
+                quote! {
+                    {
+                        #[allow(unused_mut)]
+                        let mut #request = #request;
+                        #(#before)*
+                        let response #output_ty = #call;
+                        #(#after)*
+                        response
+                    }
+                }
+            }
+            _ => quote! { {#call} },
+        };
+
+        syn::parse2::<Block>(tokens).unwrap()
+    }
+}
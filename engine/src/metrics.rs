@@ -0,0 +1,236 @@
+use proc_macro2::Ident;
+use quote::quote;
+
+use crate::method::{MethodBody, MethodInfo};
+
+/// Upper bounds of the latency histogram, in seconds. Image pulls can take minutes.
+const BUCKETS: [f64; 14] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Labels of one generated method, its position is the index into `CRI_METRICS.methods`.
+pub(crate) struct MetricsEntry {
+    service: String,
+    method: String,
+    version: String,
+}
+
+/// Registers `method` in `entries` and records every call in its counters.
+pub(crate) fn metrics_body(
+    body: &mut MethodBody,
+    entries: &mut Vec<MetricsEntry>,
+    container: &Ident,
+    trait_name: &Ident,
+    method: &MethodInfo,
+) {
+    if method.request().is_none() {
+        return;
+    }
+    let index = entries.len();
+    entries.push(MetricsEntry {
+        service: trait_name.to_string(),
+        method: method.name.to_string(),
+        version: container.to_string(),
+    });
+
+    body.before.push(quote! {
+        let metrics = super::CRI_METRICS.methods[#index].start();
+    });
+    body.after.push(quote! {
+        metrics.finish(match &response {
+            Ok(_) => tonic::Code::Ok,
+            Err(e) => e.code(),
+        });
+    });
+}
+
+/// Emits `CRI_METRICS`, the counters of every generated method, and its Prometheus exporter.
+pub(crate) fn expand_metrics(methods: &[MetricsEntry]) -> proc_macro2::TokenStream {
+    let count = methods.len();
+    let entries = methods.iter().map(|it| {
+        let service = &it.service;
+        let method = &it.method;
+        let version = &it.version;
+        quote! { CriMethodMetrics::new(#service, #method, #version) }
+    });
+    let bucket_count = BUCKETS.len();
+    let buckets = BUCKETS.iter();
+
+    quote! {
+        /// Counters of one generated method.
+        #[derive(Debug)]
+        pub struct CriMethodMetrics {
+            pub service: &'static str,
+            pub method: &'static str,
+            pub version: &'static str,
+            requests: ::std::sync::atomic::AtomicU64,
+            in_flight: ::std::sync::atomic::AtomicI64,
+            /// Finished calls by `tonic::Code`.
+            codes: [::std::sync::atomic::AtomicU64; 17],
+            buckets: [::std::sync::atomic::AtomicU64; #bucket_count],
+            latency_micros: ::std::sync::atomic::AtomicU64,
+        }
+
+        /// In-flight call, counted as finished when dropped without `finish`.
+        pub struct CriMetricsGuard {
+            metrics: &'static CriMethodMetrics,
+            started: ::std::time::Instant,
+            finished: bool,
+        }
+
+        #[allow(clippy::declare_interior_mutable_const)]
+        const CRI_METRICS_ZERO: ::std::sync::atomic::AtomicU64 = ::std::sync::atomic::AtomicU64::new(0);
+
+        impl CriMethodMetrics {
+            /// Upper bounds of the latency histogram, in seconds.
+            pub const BUCKETS: [f64; #bucket_count] = [#(#buckets),*];
+
+            const fn new(service: &'static str, method: &'static str, version: &'static str) -> Self {
+                CriMethodMetrics {
+                    service,
+                    method,
+                    version,
+                    requests: CRI_METRICS_ZERO,
+                    in_flight: ::std::sync::atomic::AtomicI64::new(0),
+                    codes: [CRI_METRICS_ZERO; 17],
+                    buckets: [CRI_METRICS_ZERO; #bucket_count],
+                    latency_micros: CRI_METRICS_ZERO,
+                }
+            }
+
+            pub fn start(&'static self) -> CriMetricsGuard {
+                self.requests.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed);
+                self.in_flight.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed);
+                CriMetricsGuard {
+                    metrics: self,
+                    started: ::std::time::Instant::now(),
+                    finished: false,
+                }
+            }
+
+            fn record(&self, code: tonic::Code, elapsed: ::std::time::Duration) {
+                use ::std::sync::atomic::Ordering;
+
+                self.in_flight.fetch_sub(1, Ordering::Relaxed);
+                if let Some(it) = self.codes.get(code as usize) {
+                    it.fetch_add(1, Ordering::Relaxed);
+                }
+                let seconds = elapsed.as_secs_f64();
+                if let Some(it) = Self::BUCKETS.iter().position(|it| seconds <= *it) {
+                    self.buckets[it].fetch_add(1, Ordering::Relaxed);
+                }
+                self.latency_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+            }
+        }
+
+        impl CriMetricsGuard {
+            pub fn finish(mut self, code: tonic::Code) {
+                self.finished = true;
+                self.metrics.record(code, self.started.elapsed());
+            }
+        }
+
+        impl Drop for CriMetricsGuard {
+            fn drop(&mut self) {
+                if !self.finished {
+                    self.metrics.record(tonic::Code::Cancelled, self.started.elapsed());
+                }
+            }
+        }
+
+        /// Counters of every generated method, in service and version order.
+        #[derive(Debug)]
+        pub struct CriMetrics {
+            pub methods: [CriMethodMetrics; #count],
+        }
+
+        /// Metrics recorded by the generated impls.
+        pub static CRI_METRICS: CriMetrics = CriMetrics {
+            methods: [#(#entries),*],
+        };
+
+        impl CriMetrics {
+            /// Renders every counter in the Prometheus text exposition format.
+            pub fn render(&self) -> String {
+                use ::std::fmt::Write;
+                use ::std::sync::atomic::Ordering;
+
+                let mut out = String::new();
+                let _ = writeln!(out, "# HELP cri_requests_total CRI calls received.");
+                let _ = writeln!(out, "# TYPE cri_requests_total counter");
+                for it in &self.methods {
+                    let _ = writeln!(out, "cri_requests_total{{{}}} {}", it.labels(), it.requests.load(Ordering::Relaxed));
+                }
+
+                let _ = writeln!(out, "# HELP cri_errors_total CRI calls that failed, by gRPC code.");
+                let _ = writeln!(out, "# TYPE cri_errors_total counter");
+                for it in &self.methods {
+                    for (code, count) in it.codes.iter().enumerate().skip(1) {
+                        let count = count.load(Ordering::Relaxed);
+                        if count > 0 {
+                            let code = format!("{:?}", tonic::Code::from_i32(code as i32));
+                            let _ = writeln!(out, "cri_errors_total{{{},code=\"{}\"}} {}", it.labels(), code, count);
+                        }
+                    }
+                }
+
+                let _ = writeln!(out, "# HELP cri_requests_in_flight CRI calls currently being handled.");
+                let _ = writeln!(out, "# TYPE cri_requests_in_flight gauge");
+                for it in &self.methods {
+                    let _ = writeln!(out, "cri_requests_in_flight{{{}}} {}", it.labels(), it.in_flight.load(Ordering::Relaxed));
+                }
+
+                let _ = writeln!(out, "# HELP cri_request_duration_seconds Latency of finished CRI calls.");
+                let _ = writeln!(out, "# TYPE cri_request_duration_seconds histogram");
+                for it in &self.methods {
+                    let mut cumulative = 0;
+                    for (bound, count) in CriMethodMetrics::BUCKETS.iter().zip(&it.buckets) {
+                        cumulative += count.load(Ordering::Relaxed);
+                        let _ = writeln!(out, "cri_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", it.labels(), bound, cumulative);
+                    }
+                    let total = it.codes.iter().map(|it| it.load(Ordering::Relaxed)).sum::<u64>();
+                    let sum = it.latency_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+                    let _ = writeln!(out, "cri_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", it.labels(), total);
+                    let _ = writeln!(out, "cri_request_duration_seconds_sum{{{}}} {}", it.labels(), sum);
+                    let _ = writeln!(out, "cri_request_duration_seconds_count{{{}}} {}", it.labels(), total);
+                }
+                out
+            }
+
+            /// Serves `render()` over HTTP on `addr`, e.g. `127.0.0.1:9090`.
+            pub async fn serve_tcp(&'static self, addr: ::std::net::SocketAddr) -> ::std::io::Result<()> {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    tokio::spawn(self.respond(stream));
+                }
+            }
+
+            /// Serves `render()` over HTTP on the unix socket at `path`, replacing a stale one.
+            pub async fn serve_unix(&'static self, path: impl AsRef<::std::path::Path>) -> ::std::io::Result<()> {
+                let listener = cri_http::bind_unix(path.as_ref())?;
+                loop {
+                    let (stream, _) = listener.accept().await?;
+                    tokio::spawn(self.respond(stream));
+                }
+            }
+
+            /// Answers a single HTTP request with the rendered metrics.
+            async fn respond<S>(&'static self, mut stream: S)
+            where
+                S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+            {
+                if cri_http::read_request(&mut stream).await.is_some() {
+                    cri_http::write_response(&mut stream, "200 OK", "text/plain; version=0.0.4", &self.render()).await;
+                }
+            }
+        }
+
+        impl CriMethodMetrics {
+            fn labels(&self) -> String {
+                format!(
+                    "service=\"{}\",method=\"{}\",version=\"{}\"",
+                    self.service, self.method, self.version
+                )
+            }
+        }
+    }
+}
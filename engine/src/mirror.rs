@@ -0,0 +1,226 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::Type;
+
+use crate::message::{messages, segment};
+use crate::method::MethodInfo;
+
+/// `CriMirror`'s implementation of one trait method: mutating calls and server streams only go
+/// to the primary, read-only calls are also sent to the shadow once the primary answered.
+pub(crate) fn mirror_method(
+    m: &syn::TraitItemMethod,
+    method: &MethodInfo,
+    container: &Ident,
+    trait_name: &Ident,
+    trait_alias: &Ident,
+    mutating: bool,
+) -> Option<TokenStream> {
+    let request = method.request()?;
+    let name = &method.name;
+    let sig = &m.sig;
+
+    if mutating || method.is_stream {
+        return Some(quote! {
+            #sig {
+                super::#trait_alias::#name(&*self.primary, #request).await
+            }
+        });
+    }
+
+    let method_name = name.to_string();
+    let version = container.to_string();
+    let service = trait_name.to_string();
+    Some(quote! {
+        #sig {
+            let mut copy = tonic::Request::new(#request.get_ref().clone());
+            *copy.metadata_mut() = #request.metadata().clone();
+
+            let response = super::#trait_alias::#name(&*self.primary, #request).await;
+            let primary = match &response {
+                Ok(it) => Ok(it.get_ref().clone()),
+                Err(e) => Err(e.code()),
+            };
+            let shadow = self.shadow.clone();
+            tokio::spawn(async move {
+                let answer = super::#trait_alias::#name(&*shadow, copy).await;
+                let diffs = match (&primary, &answer) {
+                    (Ok(primary), Ok(answer)) => {
+                        let mut diffs = Vec::new();
+                        super::CriDiff::diff(primary, answer.get_ref(), "", &mut diffs);
+                        diffs
+                    }
+                    (Err(primary), Err(answer)) if *primary == answer.code() => Vec::new(),
+                    (Err(primary), Err(answer)) => {
+                        vec![format!("primary failed with {:?}, shadow with {:?}", primary, answer.code())]
+                    }
+                    (Ok(_), Err(answer)) => {
+                        vec![format!("shadow failed with {:?}: {}", answer.code(), answer.message())]
+                    }
+                    (Err(primary), Ok(_)) => vec![format!("primary failed with {:?}, shadow succeeded", primary)],
+                };
+                if !diffs.is_empty() {
+                    tracing::warn!(
+                        target: "cri",
+                        method = #method_name,
+                        service = #service,
+                        version = #version,
+                        diff = %diffs.join("; "),
+                        "mirror mismatch",
+                    );
+                }
+            });
+            response
+        }
+    })
+}
+
+/// Emits `CriMirror`'s impl of one service trait inside a version mod.
+pub(crate) fn expand_mirror_service(
+    trait_alias: &Ident,
+    streams: &[&syn::TraitItemType],
+    items: &[TokenStream],
+) -> TokenStream {
+    let streams = streams.iter().map(|it| {
+        let name = &it.ident;
+        quote! {
+            type #name = <P as super::#trait_alias>::#name;
+        }
+    });
+    quote! {
+        #[tonic::async_trait]
+        impl<P, S> super::#trait_alias for super::CriMirror<P, S>
+        where
+            P: super::#trait_alias,
+            S: super::#trait_alias,
+        {
+            #(#streams)*
+            #(#items)*
+        }
+    }
+}
+
+/// Emits `CriDiff` impls for every message of a version mod.
+///
+/// Nested messages are compared field by field, lists item by item when their lengths match,
+/// and maps of messages entry by entry. Other fields are compared as a whole.
+pub(crate) fn expand_diff_impls(file: &syn::File) -> TokenStream {
+    let names = messages(file).map(|it| it.ident.to_string()).collect::<Vec<_>>();
+    let is_message = |ty: &Type| matches!(segment(ty), Some((name, args)) if args.is_empty() && names.contains(&name));
+
+    let impls = messages(file).map(|message| {
+        let statements = message.fields.iter().filter_map(|field| {
+            let name = field.ident.as_ref()?;
+            let (outer, args) = segment(&field.ty)?;
+            let field_name = name.to_string();
+
+            Some(match (outer.as_str(), args.as_slice()) {
+                ("Option", [it]) if is_message(it) => quote! {
+                    match (&self.#name, &other.#name) {
+                        (Some(a), Some(b)) => super::CriDiff::diff(a, b, &super::cri_field_path(path, #field_name), diffs),
+                        (a, b) if a != b => diffs.push(format!("{}: {:?} != {:?}", super::cri_field_path(path, #field_name), a, b)),
+                        _ => {}
+                    }
+                },
+                ("Vec", [it]) if is_message(it) => quote! {
+                    if self.#name.len() != other.#name.len() {
+                        diffs.push(format!(
+                            "{}: {} != {} items",
+                            super::cri_field_path(path, #field_name),
+                            self.#name.len(),
+                            other.#name.len(),
+                        ));
+                    } else {
+                        for (i, (a, b)) in self.#name.iter().zip(&other.#name).enumerate() {
+                            let path = format!("{}[{}]", super::cri_field_path(path, #field_name), i);
+                            super::CriDiff::diff(a, b, &path, diffs);
+                        }
+                    }
+                },
+                ("HashMap", [_, it]) if is_message(it) => quote! {
+                    for (key, a) in &self.#name {
+                        let path = format!("{}[{:?}]", super::cri_field_path(path, #field_name), key);
+                        match other.#name.get(key) {
+                            Some(b) => super::CriDiff::diff(a, b, &path, diffs),
+                            None => diffs.push(format!("{}: only in primary", path)),
+                        }
+                    }
+                    for key in other.#name.keys().filter(|it| !self.#name.contains_key(*it)) {
+                        diffs.push(format!("{}[{:?}]: only in shadow", super::cri_field_path(path, #field_name), key));
+                    }
+                },
+                _ if is_message(&field.ty) => quote! {
+                    super::CriDiff::diff(&self.#name, &other.#name, &super::cri_field_path(path, #field_name), diffs);
+                },
+                _ => quote! {
+                    if self.#name != other.#name {
+                        diffs.push(format!("{}: {:?} != {:?}", super::cri_field_path(path, #field_name), self.#name, other.#name));
+                    }
+                },
+            })
+        });
+
+        let ident = &message.ident;
+        quote! {
+            impl super::CriDiff for #ident {
+                #[allow(unused_variables)]
+                fn diff(&self, other: &Self, path: &str, diffs: &mut Vec<String>) {
+                    #(#statements)*
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#impls)*
+    }
+}
+
+/// Emits `CriMirror` and `CriDiff`, implemented by every CRI message of both versions.
+pub(crate) fn expand_mirror() -> TokenStream {
+    quote! {
+        /// Serves the CRI calls with `primary`, and sends a copy of every read-only call to
+        /// `shadow` once `primary` answered.
+        ///
+        /// The answers are compared field by field, and differences are logged as a `tracing`
+        /// warning of the `cri` target, the shadow's answer is never returned. Server streams
+        /// are not mirrored.
+        pub struct CriMirror<P, S> {
+            primary: ::std::sync::Arc<P>,
+            shadow: ::std::sync::Arc<S>,
+        }
+
+        impl<P, S> CriMirror<P, S> {
+            pub fn new(primary: P, shadow: S) -> Self {
+                Self::from_arc(::std::sync::Arc::new(primary), ::std::sync::Arc::new(shadow))
+            }
+
+            pub fn from_arc(primary: ::std::sync::Arc<P>, shadow: ::std::sync::Arc<S>) -> Self {
+                CriMirror { primary, shadow }
+            }
+
+            pub fn primary(&self) -> &::std::sync::Arc<P> {
+                &self.primary
+            }
+
+            pub fn shadow(&self) -> &::std::sync::Arc<S> {
+                &self.shadow
+            }
+        }
+
+        impl<P, S> Clone for CriMirror<P, S> {
+            fn clone(&self) -> Self {
+                CriMirror {
+                    primary: self.primary.clone(),
+                    shadow: self.shadow.clone(),
+                }
+            }
+        }
+
+        /// Field-level differences between two values of a CRI message.
+        pub trait CriDiff {
+            /// Appends a `field: primary != shadow` entry to `diffs` for every field that
+            /// differs, prefixed by `path`, the path of `self` in the response.
+            fn diff(&self, other: &Self, path: &str, diffs: &mut Vec<String>);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::visit_mut::{visit_type_mut, VisitMut};
+use syn::Type;
+
+use crate::method::{self, MethodInfo};
+use crate::{ServiceType, SuperRemover};
+
+/// `Mock<Struct>`, the name of the generated mock.
+pub(crate) fn mock_ident(struct_name: &Ident) -> Ident {
+    quote::format_ident!("Mock{}", struct_name)
+}
+
+/// Rewrites `Self::Assoc` into `<#self_ty as #trait_path>::Assoc`.
+struct QualifiedSelf<'a> {
+    self_ty: &'a TokenStream,
+    trait_path: &'a TokenStream,
+}
+
+impl VisitMut for QualifiedSelf<'_> {
+    fn visit_type_mut(&mut self, i: &mut Type) {
+        visit_type_mut(self, i);
+
+        if let Type::Path(p) = i {
+            if p.qself.is_none() && p.path.segments.len() == 2 && p.path.segments[0].ident == "Self" {
+                let (self_ty, trait_path) = (self.self_ty, self.trait_path);
+                let assoc = &p.path.segments[1].ident;
+                *i = syn::parse_quote! { <#self_ty as #trait_path>::#assoc };
+            }
+        }
+    }
+}
+
+/// Associated stream type of the mock: a boxed stream of the items the trait declares.
+pub(crate) fn mock_stream_type(t: &syn::TraitItemType) -> TokenStream {
+    let mut item = method::stream_item(t).unwrap_or_else(|| panic!("{} has no Item type", t.ident));
+    SuperRemover.visit_type_mut(&mut item);
+
+    let name = &t.ident;
+    quote! {
+        type #name = ::std::pin::Pin<
+            Box<dyn tonic::codegen::futures_core::Stream<Item = #item> + Send + 'static>,
+        >;
+    }
+}
+
+/// The mock's implementation of one trait method, which records the call and answers with
+/// the script of the method, and the typed `<version>_<method>()` scripting handle.
+pub(crate) fn mock_method(
+    m: &syn::TraitItemMethod,
+    method: &MethodInfo,
+    container: &Ident,
+    service_type: &ServiceType,
+    struct_name: &Ident,
+) -> Option<(TokenStream, TokenStream)> {
+    let request = method.request()?;
+    let request_ty = method.request_ty.as_ref()?;
+    let mut response_ty: Type = method.response_ty()?;
+    let mock = mock_ident(struct_name);
+    let name = method.name.to_string();
+    let version = container.to_string();
+
+    let default = if method.is_stream {
+        quote! { || -> #response_ty { Box::pin(tokio_stream::empty()) } }
+    } else {
+        quote! { <#response_ty as Default>::default }
+    };
+
+    let sig = &m.sig;
+    let item = quote! {
+        #sig {
+            super::#mock::respond(self, #version, #name, #request, #default)
+        }
+    };
+
+    // the handle lives outside the trait impl, so `Self::...Stream` is spelled out
+    let service_mod = quote::format_ident!("{}", service_type.get_mod());
+    let service_trait = quote::format_ident!("{}", service_type.get_trait());
+    QualifiedSelf {
+        self_ty: &quote! { super::#mock },
+        trait_path: &quote! { #service_mod::#service_trait },
+    }
+    .visit_type_mut(&mut response_ty);
+    let handle = quote::format_ident!("{}_{}", container, method.name);
+    let doc = format!("Script and inspect the `{}` calls of version `{}`.", name, version);
+    let handle = quote! {
+        #[doc = #doc]
+        pub fn #handle(&self) -> super::CriMockMethod<'_, #request_ty, #response_ty> {
+            super::CriMockMethod::new(&self.state, #version, #name)
+        }
+    };
+
+    Some((item, handle))
+}
+
+/// Emits the mock's impl of one service trait inside a version mod, and its handles.
+pub(crate) fn expand_mock_service(
+    trait_alias: &Ident,
+    struct_name: &Ident,
+    items: &[TokenStream],
+    handles: &[TokenStream],
+) -> TokenStream {
+    let mock = mock_ident(struct_name);
+    quote! {
+        #[tonic::async_trait]
+        impl super::#trait_alias for super::#mock {
+            #(#items)*
+        }
+
+        impl super::#mock {
+            #(#handles)*
+        }
+    }
+}
+
+/// Emits `Mock<Struct>` and the types its scripts and assertions are made of.
+pub(crate) fn expand_mock(struct_name: &Ident) -> TokenStream {
+    let mock = mock_ident(struct_name);
+    let doc = format!(
+        "Test double of `{}` implementing the same service traits, generated by `#[auto_impl(mock)]`.\n\n\
+         Every call is recorded, and answered from the script of its method: queued responses \
+         first, then a closure set with `returns_with`, then the `Default` response (an empty \
+         stream for server streams).",
+        struct_name
+    );
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Default)]
+        pub struct #mock {
+            state: ::std::sync::Arc<::std::sync::Mutex<CriMockState>>,
+        }
+
+        #[derive(Default)]
+        struct CriMockState {
+            calls: Vec<CriMockCall>,
+            /// `CriMockScript<Req, Resp>` per `(version, method)`.
+            scripts: ::std::collections::HashMap<(&'static str, &'static str), Box<dyn ::std::any::Any + Send>>,
+        }
+
+        /// A call received by the mock.
+        #[derive(Clone)]
+        pub struct CriMockCall {
+            pub version: &'static str,
+            pub method: &'static str,
+            request: ::std::sync::Arc<dyn ::std::any::Any + Send + Sync>,
+            debug: String,
+        }
+
+        impl CriMockCall {
+            /// The request message, `None` if it is not a `T`.
+            pub fn request<T: 'static>(&self) -> Option<&T> {
+                self.request.downcast_ref()
+            }
+        }
+
+        impl ::std::fmt::Debug for CriMockCall {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                write!(f, "{}/{}({})", self.version, self.method, self.debug)
+            }
+        }
+
+        struct CriMockScript<Req, Resp> {
+            queue: ::std::collections::VecDeque<Result<Resp, tonic::Status>>,
+            with: Option<Box<dyn FnMut(&Req) -> Result<Resp, tonic::Status> + Send>>,
+        }
+
+        /// Typed script of one method of one version, see `Mock...::<version>_<method>()`.
+        pub struct CriMockMethod<'a, Req, Resp> {
+            state: &'a ::std::sync::Mutex<CriMockState>,
+            version: &'static str,
+            method: &'static str,
+            _types: ::std::marker::PhantomData<fn(Req) -> Resp>,
+        }
+
+        impl<'a, Req: Send + 'static, Resp: Send + 'static> CriMockMethod<'a, Req, Resp> {
+            fn new(state: &'a ::std::sync::Mutex<CriMockState>, version: &'static str, method: &'static str) -> Self {
+                CriMockMethod {
+                    state,
+                    version,
+                    method,
+                    _types: ::std::marker::PhantomData,
+                }
+            }
+
+            fn script<T>(&self, f: impl FnOnce(&mut CriMockScript<Req, Resp>) -> T) -> T {
+                let mut state = self.state.lock().unwrap_or_else(|it| it.into_inner());
+                let script = state
+                    .scripts
+                    .entry((self.version, self.method))
+                    .or_insert_with(|| {
+                        Box::new(CriMockScript::<Req, Resp> {
+                            queue: ::std::collections::VecDeque::new(),
+                            with: None,
+                        })
+                    })
+                    .downcast_mut::<CriMockScript<Req, Resp>>()
+                    .expect("mock script of another type");
+                f(script)
+            }
+
+            /// Queues `response` for the next call.
+            pub fn returns(self, response: Result<Resp, tonic::Status>) -> Self {
+                self.script(|it| it.queue.push_back(response));
+                self
+            }
+
+            /// Answers calls with `f` once the queue is empty. `f` must not call the mock.
+            pub fn returns_with<F>(self, f: F) -> Self
+            where
+                F: FnMut(&Req) -> Result<Resp, tonic::Status> + Send + 'static,
+            {
+                self.script(|it| it.with = Some(Box::new(f)));
+                self
+            }
+
+            /// Drops the queued responses and the closure, calls get the default response.
+            pub fn returns_default(self) -> Self {
+                self.script(|it| {
+                    it.queue.clear();
+                    it.with = None;
+                });
+                self
+            }
+
+            /// Requests received so far, oldest first.
+            pub fn calls(&self) -> Vec<Req>
+            where
+                Req: Clone,
+            {
+                let state = self.state.lock().unwrap_or_else(|it| it.into_inner());
+                state
+                    .calls
+                    .iter()
+                    .filter(|it| it.version == self.version && it.method == self.method)
+                    .filter_map(|it| it.request::<Req>().cloned())
+                    .collect()
+            }
+        }
+
+        /// Assertion on the number of calls of a method, over all versions.
+        #[derive(Debug)]
+        pub struct CriMockExpectation {
+            method: String,
+            calls: Vec<CriMockCall>,
+        }
+
+        impl CriMockExpectation {
+            #[track_caller]
+            pub fn times(&self, count: usize) {
+                assert_eq!(
+                    self.calls.len(),
+                    count,
+                    "expected `{}` to be called {} times, got {:?}",
+                    self.method,
+                    count,
+                    self.calls
+                );
+            }
+
+            #[track_caller]
+            pub fn never(&self) {
+                self.times(0);
+            }
+
+            #[track_caller]
+            pub fn at_least(&self, count: usize) {
+                assert!(
+                    self.calls.len() >= count,
+                    "expected `{}` to be called at least {} times, got {:?}",
+                    self.method,
+                    count,
+                    self.calls
+                );
+            }
+        }
+
+        impl #mock {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Every call received so far, oldest first.
+            pub fn calls(&self) -> Vec<CriMockCall> {
+                self.state.lock().unwrap_or_else(|it| it.into_inner()).calls.clone()
+            }
+
+            /// Calls of `method`, such as `"stop_container"`, to assert on.
+            pub fn expect_called(&self, method: &str) -> CriMockExpectation {
+                CriMockExpectation {
+                    method: method.to_string(),
+                    calls: self.calls().into_iter().filter(|it| it.method == method).collect(),
+                }
+            }
+
+            /// Forgets the recorded calls and every script.
+            pub fn reset(&self) {
+                let mut state = self.state.lock().unwrap_or_else(|it| it.into_inner());
+                state.calls.clear();
+                state.scripts.clear();
+            }
+
+            #[allow(clippy::result_large_err)]
+            fn respond<Req, Resp>(
+                &self,
+                version: &'static str,
+                method: &'static str,
+                request: tonic::Request<Req>,
+                default: fn() -> Resp,
+            ) -> Result<tonic::Response<Resp>, tonic::Status>
+            where
+                Req: ::std::fmt::Debug + Send + Sync + 'static,
+                Resp: Send + 'static,
+            {
+                let request = request.into_inner();
+                let mut state = self.state.lock().unwrap_or_else(|it| it.into_inner());
+                let scripted = state
+                    .scripts
+                    .get_mut(&(version, method))
+                    .and_then(|it| it.downcast_mut::<CriMockScript<Req, Resp>>())
+                    .and_then(|it| match it.queue.pop_front() {
+                        Some(response) => Some(response),
+                        None => it.with.as_mut().map(|with| with(&request)),
+                    });
+                state.calls.push(CriMockCall {
+                    version,
+                    method,
+                    debug: format!("{:?}", request),
+                    request: ::std::sync::Arc::new(request),
+                });
+
+                scripted.unwrap_or_else(|| Ok(default())).map(tonic::Response::new)
+            }
+        }
+    }
+}
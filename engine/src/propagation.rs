@@ -0,0 +1,71 @@
+use quote::quote;
+
+/// Emits `CriTraceContext`, which moves W3C trace context (`traceparent`, `tracestate`)
+/// between gRPC metadata and the `tracing` spans of the generated impls.
+///
+/// Incoming requests are handled in a span parented to the caller's context, outgoing
+/// calls carry the context of the current span when made through `CriTraceContext::interceptor`.
+pub(crate) fn expand_trace_context() -> proc_macro2::TokenStream {
+    quote! {
+        /// W3C trace context propagation over `tonic` metadata.
+        #[derive(Clone, Copy, Debug, Default)]
+        pub struct CriTraceContext;
+
+        impl CriTraceContext {
+            /// Context sent by the caller, empty when the metadata carries no valid `traceparent`.
+            pub fn extract(metadata: &tonic::metadata::MetadataMap) -> opentelemetry::Context {
+                use opentelemetry::propagation::TextMapPropagator;
+
+                opentelemetry::sdk::propagation::TraceContextPropagator::new()
+                    .extract(&CriMetadataExtractor(metadata))
+            }
+
+            /// Writes the context of the current `tracing` span into `metadata`.
+            pub fn inject(metadata: &mut tonic::metadata::MetadataMap) {
+                use opentelemetry::propagation::TextMapPropagator;
+                use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+                let context = tracing::Span::current().context();
+                opentelemetry::sdk::propagation::TraceContextPropagator::new()
+                    .inject_context(&context, &mut CriMetadataInjector(metadata));
+            }
+
+            /// Client interceptor injecting the current context into every outgoing call, e.g.
+            /// `RuntimeServiceClient::with_interceptor(channel, CriTraceContext::interceptor)`.
+            #[allow(clippy::result_large_err)]
+            pub fn interceptor(mut request: tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> {
+                Self::inject(request.metadata_mut());
+                Ok(request)
+            }
+        }
+
+        struct CriMetadataExtractor<'a>(&'a tonic::metadata::MetadataMap);
+
+        impl opentelemetry::propagation::Extractor for CriMetadataExtractor<'_> {
+            fn get(&self, key: &str) -> Option<&str> {
+                self.0.get(key).and_then(|it| it.to_str().ok())
+            }
+
+            fn keys(&self) -> Vec<&str> {
+                self.0
+                    .keys()
+                    .map(|it| match it {
+                        tonic::metadata::KeyRef::Ascii(it) => it.as_str(),
+                        tonic::metadata::KeyRef::Binary(it) => it.as_str(),
+                    })
+                    .collect()
+            }
+        }
+
+        struct CriMetadataInjector<'a>(&'a mut tonic::metadata::MetadataMap);
+
+        impl opentelemetry::propagation::Injector for CriMetadataInjector<'_> {
+            fn set(&mut self, key: &str, value: String) {
+                let key = tonic::metadata::MetadataKey::from_bytes(key.as_bytes());
+                if let (Ok(key), Ok(value)) = (key, value.parse()) {
+                    self.0.insert(key, value);
+                }
+            }
+        }
+    }
+}
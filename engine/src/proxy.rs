@@ -0,0 +1,240 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::visit_mut::VisitMut;
+
+use crate::method::{self, MethodInfo};
+use crate::{trait_methods, ServiceType, SuperRemover};
+
+/// The version a `bridge` proxy forwards both versions to, and its bundled proto file.
+pub(crate) struct Bridge<'a> {
+    pub(crate) container: Ident,
+    pub(crate) file: &'a syn::File,
+}
+
+fn proxy_trait(service_type: &ServiceType) -> Ident {
+    quote::format_ident!("{}Proxy", service_type.get_trait())
+}
+
+/// Message of a server stream type declared by a service trait.
+fn stream_message(t: &syn::TraitItemType) -> TokenStream {
+    let mut item = method::stream_item(t).unwrap_or_else(|| panic!("{} has no Item type", t.ident));
+    SuperRemover.visit_type_mut(&mut item);
+    let message = method::first_generic_argument(&item).unwrap_or_else(|| panic!("{} has no message type", t.ident));
+    quote! { #message }
+}
+
+/// Stream type of the generated impls in proxy mode, inside a version mod.
+pub(crate) fn stream_type(t: &syn::TraitItemType) -> TokenStream {
+    let message = stream_message(t);
+    quote! { super::CriProxyStream<#message> }
+}
+
+/// Forwards the call to the struct's `<Service>Proxy` impl rather than to an inherent method.
+pub(crate) fn proxy_call(service_type: &ServiceType, struct_name: &Ident, method: &MethodInfo) -> TokenStream {
+    let proxy_trait = proxy_trait(service_type);
+    let name = &method.name;
+    let args = &method.args;
+    quote! { <super::#struct_name as #proxy_trait>::#name(self, #(#args),*).await }
+}
+
+/// A method of `<Service>Proxy` whose default forwards to the same method of the upstream, or
+/// of the upstream's `bridge` version, converting the messages. With `opentelemetry` the
+/// forwarded request carries the context of the `cri` span in place of the caller's.
+pub(crate) fn proxy_method(
+    m: &syn::TraitItemMethod,
+    method: &MethodInfo,
+    container: &Ident,
+    service_type: &ServiceType,
+    streams: &[&syn::TraitItemType],
+    bridge: Option<&Bridge>,
+    opentelemetry: bool,
+) -> Option<TokenStream> {
+    let request = method.request()?;
+    let request_ty = method.request_ty.as_ref()?;
+    let name = &method.name;
+    let client = quote::format_ident!("{}", service_type.get_name());
+    let docs = m.attrs.iter().filter(|it| it.path.is_ident("doc"));
+    let bridged = bridge.filter(|it| it.container != *container);
+    let upstream = bridged.map_or(container, |it| &it.container);
+
+    let message = if method.is_stream {
+        let syn::Type::Path(path) = method.response_ty()? else {
+            return None;
+        };
+        let stream = path.path.segments.last()?.ident.clone();
+        Some(stream_message(streams.iter().find(|it| it.ident == stream)?))
+    } else {
+        None
+    };
+    let output = match &message {
+        Some(message) => quote! { Result<tonic::Response<super::CriProxyStream<#message>>, tonic::Status> },
+        None => {
+            let output = method.output_ty.as_ref()?;
+            quote! { #output }
+        }
+    };
+
+    let traced = |request: TokenStream| {
+        if opentelemetry {
+            quote! {
+                let mut #request = #request;
+                super::CriTraceContext::inject(#request.metadata_mut());
+            }
+        } else {
+            quote! {}
+        }
+    };
+    let forwarded = traced(quote! { forwarded });
+    let request_traced = traced(quote! { #request });
+
+    let forward = match (bridged, &message) {
+        (Some(bridge), _) if !trait_methods(bridge.file, service_type).contains(&name.to_string()) => {
+            let error = format!(
+                "runtime.{}.{}/{} has no counterpart in runtime.{}",
+                container,
+                service_type.get_trait(),
+                change_case::pascal_case(&name.to_string()),
+                upstream
+            );
+            quote! {
+                let _ = #request;
+                Err(tonic::Status::unimplemented(#error))
+            }
+        }
+        // the messages of both versions share their field numbers, they convert through the wire
+        (Some(_), Some(message)) => quote! {
+            let mut client = self.cri_upstream().#upstream.#client();
+            let converted = super::cri_bridge(#request.get_ref())?;
+            let forwarded = #request.map(|_| converted);
+            #forwarded
+            let response = client.#name(forwarded).await?;
+            Ok(response.map(|it| {
+                let it = tokio_stream::StreamExt::map(it, |it| it.and_then(|it| super::cri_bridge(&it)));
+                Box::pin(it) as super::CriProxyStream<#message>
+            }))
+        },
+        (Some(_), None) => quote! {
+            let mut client = self.cri_upstream().#upstream.#client();
+            let converted = super::cri_bridge(#request.get_ref())?;
+            let forwarded = #request.map(|_| converted);
+            #forwarded
+            let response = client.#name(forwarded).await?;
+            let converted = super::cri_bridge(response.get_ref())?;
+            Ok(response.map(|_| converted))
+        },
+        (None, Some(message)) => quote! {
+            #request_traced
+            let mut client = self.cri_upstream().#upstream.#client();
+            client
+                .#name(#request)
+                .await
+                .map(|it| it.map(|it| Box::pin(it) as super::CriProxyStream<#message>))
+        },
+        (None, None) => quote! {
+            #request_traced
+            let mut client = self.cri_upstream().#upstream.#client();
+            client.#name(#request).await
+        },
+    };
+
+    Some(quote! {
+        #(#docs)*
+        async fn #name(&self, #request: tonic::Request<#request_ty>) -> #output {
+            #forward
+        }
+    })
+}
+
+/// Emits `<Service>Proxy` inside a version mod, implemented by the struct to override
+/// individual methods.
+pub(crate) fn expand_proxy_trait(service_type: &ServiceType, items: &[TokenStream]) -> TokenStream {
+    let proxy_trait = proxy_trait(service_type);
+    let doc = format!(
+        "Methods of `{}` a `proxy` struct serves, each forwards to the upstream unless overridden.",
+        service_type.get_trait()
+    );
+    quote! {
+        #[doc = #doc]
+        #[tonic::async_trait]
+        pub trait #proxy_trait: super::CriProxied + Send + Sync + 'static {
+            #(#items)*
+        }
+    }
+}
+
+/// Emits `CriUpstream`, `CriProxyStream` and `CriProxied`, implemented by the struct with its
+/// `CriUpstream` field.
+pub(crate) fn expand_proxy(struct_ast: &syn::ItemStruct, field: &TokenStream) -> TokenStream {
+    let struct_name = &struct_ast.ident;
+
+    quote! {
+        /// Upstream CRI endpoint of a `proxy` struct, one connection serving the clients of
+        /// both versions.
+        #[derive(Clone, Debug)]
+        pub struct CriUpstream {
+            pub v1: v1::CriClient,
+            pub v1alpha2: v1alpha2::CriClient,
+        }
+
+        impl CriUpstream {
+            pub fn new(channel: tonic::transport::Channel) -> Self {
+                CriUpstream {
+                    v1: v1::CriClient::new(channel.clone()),
+                    v1alpha2: v1alpha2::CriClient::new(channel),
+                }
+            }
+
+            /// Connects to an upstream listening on TCP, such as `http://127.0.0.1:10010`.
+            pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+            where
+                D: ::std::convert::TryInto<tonic::transport::Endpoint>,
+                D::Error: Into<tonic::codegen::StdError>,
+            {
+                let channel = tonic::transport::Endpoint::new(dst)?.connect().await?;
+                Ok(Self::new(channel))
+            }
+
+            /// Connects to an upstream listening on the unix socket at `path`.
+            pub async fn connect_unix(path: impl AsRef<::std::path::Path>) -> Result<Self, tonic::transport::Error> {
+                let connector = CriUnixConnector(::std::sync::Arc::new(path.as_ref().to_path_buf()));
+                let channel = tonic::transport::Endpoint::from_static("http://[::]:50051")
+                    .connect_with_connector(connector)
+                    .await?;
+                Ok(Self::new(channel))
+            }
+        }
+
+        /// Server stream of a proxied method, such as the upstream's container events.
+        pub type CriProxyStream<T> = ::std::pin::Pin<
+            Box<dyn tonic::codegen::futures_core::Stream<Item = Result<T, tonic::Status>> + Send + 'static>,
+        >;
+
+        /// Gives the `<Service>Proxy` traits the upstream to forward to.
+        pub trait CriProxied {
+            fn cri_upstream(&self) -> &CriUpstream;
+        }
+
+        impl CriProxied for #struct_name {
+            fn cri_upstream(&self) -> &CriUpstream {
+                &self.#field
+            }
+        }
+    }
+}
+
+/// Emits `cri_bridge`, converting a message of one CRI version into the same message of the
+/// other for the `bridge` proxies.
+pub(crate) fn expand_bridge() -> TokenStream {
+    quote! {
+        /// Converts `message` into the message of the other CRI version with the same name, by
+        /// their encoding, fields the other version lacks are dropped.
+        fn cri_bridge<A, B>(message: &A) -> Result<B, tonic::Status>
+        where
+            A: ::prost::Message,
+            B: ::prost::Message + Default,
+        {
+            B::decode(::prost::Message::encode_to_vec(message).as_slice())
+                .map_err(|e| tonic::Status::internal(format!("converting the CRI message failed: {}", e)))
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+
+use crate::method::{MethodBody, MethodInfo};
+use crate::ServiceType;
+
+/// Writes the call, its timing and its outcome to the struct's `CriRecorder` once it is done.
+pub(crate) fn record_body(body: &mut MethodBody, container: &Ident, trait_name: &Ident, method: &MethodInfo) {
+    let (Some(request), Some(_)) = (method.request(), &method.request_ty) else {
+        return;
+    };
+    let name = method.name.to_string();
+    let version = container.to_string();
+    let service = trait_name.to_string();
+
+    // server streams are recorded when opened, their items are not
+    let response_bytes = if method.is_stream {
+        quote! { None }
+    } else {
+        quote! { response.as_ref().ok().map(|it| ::prost::Message::encode_to_vec(it.get_ref())) }
+    };
+
+    body.before.push(quote! {
+        let record_request = ::prost::Message::encode_to_vec(#request.get_ref());
+        let record_started_at = ::std::time::SystemTime::now();
+        let record_start = ::std::time::Instant::now();
+    });
+    body.after.push(quote! {
+        let (code, message) = match &response {
+            Ok(_) => (tonic::Code::Ok, String::new()),
+            Err(e) => (e.code(), e.message().to_string()),
+        };
+        self.cri_recorder().record(&super::CriRecord {
+            version: #version.to_string(),
+            service: #service.to_string(),
+            method: #name.to_string(),
+            started_at: record_started_at
+                .duration_since(::std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64,
+            duration: record_start.elapsed().as_nanos() as u64,
+            request: record_request,
+            response: #response_bytes,
+            code: code as i32,
+            message,
+        });
+    });
+}
+
+/// Match arm of `cri_replay_<service>` re-issuing a recorded call of `method` against the
+/// target and comparing the outcome with the recorded one.
+pub(crate) fn replay_arm(method: &MethodInfo, trait_alias: &Ident) -> Option<TokenStream> {
+    let request_ty = method.request_ty.as_ref()?;
+    let name = &method.name;
+    let method_name = name.to_string();
+
+    let compare = if method.is_stream {
+        quote! { super::CriRecording::compare_code(record, actual.map(|_| ())) }
+    } else {
+        let response_ty = method.response_ty()?;
+        quote! { super::CriRecording::compare::<#response_ty>(record, actual.map(tonic::Response::into_inner)) }
+    };
+
+    Some(quote! {
+        #method_name => {
+            let request = match <#request_ty as ::prost::Message>::decode(record.request.as_slice()) {
+                Ok(it) => it,
+                Err(e) => return Some(Err(format!("the recorded request does not decode: {}", e))),
+            };
+            let actual = super::#trait_alias::#name(target, tonic::Request::new(request)).await;
+            Some(#compare)
+        }
+    })
+}
+
+/// Emits `cri_replay_<service>` inside a version mod, dispatching the recorded calls of one
+/// service to its trait methods.
+pub(crate) fn expand_replay_service(service_type: &ServiceType, trait_alias: &Ident, arms: &[TokenStream]) -> TokenStream {
+    let replay = quote::format_ident!("cri_replay_{}", change_case::snake_case(service_type.get_trait()));
+    quote! {
+        /// Re-issues `record` against `target`, `None` when it is not a call of the service.
+        pub(super) async fn #replay<T: super::#trait_alias>(
+            target: &T,
+            record: &super::CriRecord,
+        ) -> Option<Result<(), String>> {
+            match record.method.as_str() {
+                #(#arms)*
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Emits `CriRecord`, `CriRecorder`, `CriRecording`, `CriDivergence` and the `cri_recorder()`
+/// accessor returning the `CriRecorder` field of the struct.
+pub(crate) fn expand_record(
+    struct_ast: &syn::ItemStruct,
+    field: &TokenStream,
+    containers: &[Ident],
+    services: &[ServiceType],
+) -> TokenStream {
+    let struct_name = &struct_ast.ident;
+    let pairs = containers
+        .iter()
+        .flat_map(|container| services.iter().map(move |service| (container, service)))
+        .collect::<Vec<_>>();
+    let bounds = pairs.iter().map(|(container, service)| {
+        let alias = quote::format_ident!("{}_{}", service.get_trait(), container);
+        quote! { #alias }
+    });
+    let arms = pairs.iter().map(|(container, service)| {
+        let version = container.to_string();
+        let trait_name = service.get_trait();
+        let replay = quote::format_ident!("cri_replay_{}", change_case::snake_case(trait_name));
+        quote! {
+            (#version, #trait_name) => #container::#replay(target, record).await,
+        }
+    });
+
+    quote! {
+        /// A recorded CRI call, written length-delimited by `CriRecorder`.
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct CriRecord {
+            #[prost(string, tag = "1")]
+            pub version: String,
+            #[prost(string, tag = "2")]
+            pub service: String,
+            #[prost(string, tag = "3")]
+            pub method: String,
+            /// Start of the call, in nanoseconds since the Unix epoch.
+            #[prost(uint64, tag = "4")]
+            pub started_at: u64,
+            /// Duration of the call, in nanoseconds.
+            #[prost(uint64, tag = "5")]
+            pub duration: u64,
+            /// Encoded request message.
+            #[prost(bytes = "vec", tag = "6")]
+            pub request: Vec<u8>,
+            /// Encoded response message, `None` for failed calls and server streams.
+            #[prost(bytes = "vec", optional, tag = "7")]
+            pub response: Option<Vec<u8>>,
+            /// `tonic::Code` of the outcome.
+            #[prost(int32, tag = "8")]
+            pub code: i32,
+            #[prost(string, tag = "9")]
+            pub message: String,
+        }
+
+        /// Session recording of every CRI call, a file of length-delimited `CriRecord`s.
+        ///
+        /// Requests are recorded whole to be replayed, credentials included: the `auth` of image
+        /// pulls and the `envs` of containers. The file is only readable by its owner.
+        #[derive(Clone, Debug)]
+        pub struct CriRecorder {
+            file: ::std::sync::Arc<::std::sync::Mutex<::std::fs::File>>,
+        }
+
+        impl CriRecorder {
+            /// Starts a new recording at `path` with mode `0600`, replacing an existing one.
+            pub fn create(path: impl AsRef<::std::path::Path>) -> ::std::io::Result<Self> {
+                use ::std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+                const RECORDING_MODE: u32 = 0o600;
+
+                let path = path.as_ref();
+                if let Some(parent) = path.parent() {
+                    ::std::fs::create_dir_all(parent)?;
+                }
+                let file = ::std::fs::OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .mode(RECORDING_MODE)
+                    .open(path)?;
+                // the mode only applies to new files
+                file.set_permissions(::std::fs::Permissions::from_mode(RECORDING_MODE))?;
+                Ok(CriRecorder {
+                    file: ::std::sync::Arc::new(::std::sync::Mutex::new(file)),
+                })
+            }
+
+            /// Writes `record`, a failure is logged as a `tracing` warning of the `cri` target
+            /// rather than returned to the caller whose call already happened.
+            pub fn record(&self, record: &CriRecord) {
+                use ::std::io::Write as _;
+
+                let bytes = ::prost::Message::encode_length_delimited_to_vec(record);
+                let mut file = self.file.lock().unwrap_or_else(|it| it.into_inner());
+                if let Err(e) = file.write_all(&bytes) {
+                    tracing::warn!(
+                        target: "cri",
+                        method = %record.method,
+                        service = %record.service,
+                        version = %record.version,
+                        error = %e,
+                        "record not written",
+                    );
+                }
+            }
+        }
+
+        /// A recorded call whose outcome was different when replayed.
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub struct CriDivergence {
+            /// Position of the call in the recording.
+            pub index: usize,
+            pub version: String,
+            pub service: String,
+            pub method: String,
+            pub reason: String,
+        }
+
+        impl ::std::fmt::Display for CriDivergence {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                write!(
+                    f,
+                    "#{} runtime.{}.{}/{}: {}",
+                    self.index, self.version, self.service, self.method, self.reason
+                )
+            }
+        }
+
+        /// The calls of a `CriRecorder` file, replayed against an implementation with `replay`.
+        #[derive(Clone, Debug, Default)]
+        pub struct CriRecording {
+            pub records: Vec<CriRecord>,
+        }
+
+        impl CriRecording {
+            pub fn open(path: impl AsRef<::std::path::Path>) -> ::std::io::Result<Self> {
+                let bytes = ::std::fs::read(path)?;
+                let mut buf = bytes.as_slice();
+                let mut records = Vec::new();
+                while !buf.is_empty() {
+                    let record = <CriRecord as ::prost::Message>::decode_length_delimited(&mut buf)
+                        .map_err(|e| ::std::io::Error::new(::std::io::ErrorKind::InvalidData, e))?;
+                    records.push(record);
+                }
+                Ok(CriRecording { records })
+            }
+
+            /// Re-issues the recorded calls against `target` one after the other, without their
+            /// metadata and timing, and returns the calls whose code or response differs.
+            pub async fn replay<T>(&self, target: &T) -> Vec<CriDivergence>
+            where
+                T: #(#bounds)+*,
+            {
+                let mut divergences = Vec::new();
+                for (index, record) in self.records.iter().enumerate() {
+                    let replayed = match (record.version.as_str(), record.service.as_str()) {
+                        #(#arms)*
+                        _ => None,
+                    };
+                    let reason = match replayed {
+                        Some(Ok(())) => continue,
+                        Some(Err(reason)) => reason,
+                        None => "not a method of the replayed services".to_string(),
+                    };
+                    divergences.push(CriDivergence {
+                        index,
+                        version: record.version.clone(),
+                        service: record.service.clone(),
+                        method: record.method.clone(),
+                        reason,
+                    });
+                }
+                divergences
+            }
+
+            fn compare_code(record: &CriRecord, actual: Result<(), tonic::Status>) -> Result<(), String> {
+                let recorded = tonic::Code::from_i32(record.code);
+                match actual {
+                    Ok(()) if recorded == tonic::Code::Ok => Ok(()),
+                    Err(e) if e.code() == recorded => Ok(()),
+                    Ok(()) => Err(format!("recorded {:?}, replayed Ok", recorded)),
+                    Err(e) => Err(format!("recorded {:?}, replayed {:?}: {}", recorded, e.code(), e.message())),
+                }
+            }
+
+            fn compare<M>(record: &CriRecord, actual: Result<M, tonic::Status>) -> Result<(), String>
+            where
+                M: ::prost::Message + Default + PartialEq,
+            {
+                let actual = match actual {
+                    Ok(it) => it,
+                    Err(e) => return Self::compare_code(record, Err(e)),
+                };
+                Self::compare_code(record, Ok(()))?;
+                let recorded = record.response.as_deref().unwrap_or_default();
+                let recorded = M::decode(recorded).map_err(|e| format!("the recorded response does not decode: {}", e))?;
+                if recorded == actual {
+                    Ok(())
+                } else {
+                    Err(format!("recorded {:?}, replayed {:?}", recorded, actual))
+                }
+            }
+        }
+
+        impl #struct_name {
+            /// Recorder the generated impls write every call to.
+            fn cri_recorder(&self) -> &CriRecorder {
+                &self.#field
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::{Token, Type};
+
+use crate::message::{messages, segment};
+use crate::method::{MethodBody, MethodInfo};
+
+/// Fields redacted by the `log` layer when `redact = [...]` is not given: registry
+/// credentials of `AuthConfig` and the values of container environment variables, whose
+/// keys are kept.
+const DEFAULT_REDACTED: [&str; 6] = [
+    "AuthConfig.username",
+    "AuthConfig.password",
+    "AuthConfig.auth",
+    "AuthConfig.identity_token",
+    "AuthConfig.registry_token",
+    "KeyValue.value",
+];
+
+/// An entry of `redact = [...]`, either a field name of any message or `Message.field`.
+#[derive(Clone)]
+pub(crate) struct RedactedField {
+    message: Option<Ident>,
+    field: Ident,
+}
+
+impl syn::parse::Parse for RedactedField {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let first = input.parse::<Ident>()?;
+        if input.peek(Token![.]) {
+            input.parse::<Token![.]>()?;
+            Ok(RedactedField {
+                message: Some(first),
+                field: input.parse()?,
+            })
+        } else {
+            Ok(RedactedField {
+                message: None,
+                field: first,
+            })
+        }
+    }
+}
+
+impl std::fmt::Display for RedactedField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.message {
+            Some(message) => write!(f, "{}.{}", message, self.field),
+            None => write!(f, "{}", self.field),
+        }
+    }
+}
+
+impl RedactedField {
+    fn matches(&self, message: &Ident, field: &Ident) -> bool {
+        self.field == *field
+            && match &self.message {
+                Some(it) => it == message,
+                None => true,
+            }
+    }
+}
+
+/// Checks that every redacted name is a field of at least one message of `files`.
+pub(crate) fn check_fields(files: &[&syn::File], fields: &[RedactedField]) -> syn::Result<()> {
+    for redacted in fields {
+        let found = files.iter().flat_map(|file| messages(file)).any(|message| {
+            message
+                .fields
+                .iter()
+                .filter_map(|it| it.ident.as_ref())
+                .any(|it| redacted.matches(&message.ident, it))
+        });
+        if !found {
+            let span = redacted.message.as_ref().unwrap_or(&redacted.field).span();
+            return Err(syn::Error::new(
+                span,
+                format!("`{}` is not a field of any CRI message", redacted),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The default redacted fields, used when `redact = [...]` is not given.
+pub(crate) fn default_fields() -> Vec<RedactedField> {
+    DEFAULT_REDACTED.iter().map(|it| syn::parse_str(it).unwrap()).collect()
+}
+
+fn is_string(ty: &Type) -> bool {
+    matches!(segment(ty), Some((name, _)) if name == "String")
+}
+
+/// Emits `CriRedact` impls for every message of a version mod.
+///
+/// Redacted string values are replaced by `[REDACTED]`, other redacted values are cleared,
+/// and nested messages are redacted recursively.
+pub(crate) fn expand_redact_impls(file: &syn::File, fields: &[RedactedField]) -> TokenStream {
+    let names = messages(file).map(|it| it.ident.to_string()).collect::<Vec<_>>();
+    let is_message = |ty: &Type| matches!(segment(ty), Some((name, args)) if args.is_empty() && names.contains(&name));
+
+    let impls = messages(file).map(|message| {
+        let statements = message.fields.iter().filter_map(|field| {
+            let name = field.ident.as_ref()?;
+            let (outer, args) = segment(&field.ty)?;
+
+            if fields.iter().any(|it| it.matches(&message.ident, name)) {
+                return Some(match (outer.as_str(), args.as_slice()) {
+                    ("String", _) => quote! {
+                        if !self.#name.is_empty() {
+                            self.#name = "[REDACTED]".to_string();
+                        }
+                    },
+                    ("Vec", [it]) if is_string(it) => quote! {
+                        self.#name.iter_mut().for_each(|it| *it = "[REDACTED]".to_string());
+                    },
+                    ("HashMap", [_, it]) if is_string(it) => quote! {
+                        self.#name.values_mut().for_each(|it| *it = "[REDACTED]".to_string());
+                    },
+                    _ => quote! {
+                        self.#name = Default::default();
+                    },
+                });
+            }
+
+            match (outer.as_str(), args.as_slice()) {
+                ("Option", [it]) if is_message(it) => Some(quote! {
+                    if let Some(it) = &mut self.#name {
+                        super::CriRedact::redact(it);
+                    }
+                }),
+                ("Vec", [it]) if is_message(it) => Some(quote! {
+                    self.#name.iter_mut().for_each(super::CriRedact::redact);
+                }),
+                ("HashMap", [_, it]) if is_message(it) => Some(quote! {
+                    self.#name.values_mut().for_each(super::CriRedact::redact);
+                }),
+                _ if is_message(&field.ty) => Some(quote! {
+                    super::CriRedact::redact(&mut self.#name);
+                }),
+                _ => None,
+            }
+        });
+
+        let ident = &message.ident;
+        quote! {
+            impl super::CriRedact for #ident {
+                fn redact(&mut self) {
+                    #(#statements)*
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#impls)*
+    }
+}
+
+/// Emits `CriRedact`, implemented by every CRI message of both versions.
+pub(crate) fn expand_redact_trait(fields: &[RedactedField]) -> TokenStream {
+    let doc = format!(
+        "Hides sensitive fields (`{}`) of a CRI message before it is logged.",
+        fields.iter().map(ToString::to_string).collect::<Vec<_>>().join("`, `")
+    );
+    quote! {
+        #[doc = #doc]
+        pub trait CriRedact {
+            fn redact(&mut self);
+        }
+    }
+}
+
+/// Logs the redacted request and response of `method` as `tracing` debug events of the
+/// `cri` target.
+pub(crate) fn log_body(body: &mut MethodBody, container: &Ident, trait_name: &Ident, method: &MethodInfo) {
+    let (Some(request), Some(_)) = (method.request(), &method.request_ty) else {
+        return;
+    };
+    let name = method.name.to_string();
+    let version = container.to_string();
+    let service = trait_name.to_string();
+
+    body.before.push(quote! {
+        let log = tracing::enabled!(target: "cri", tracing::Level::DEBUG);
+        if log {
+            let mut message = #request.get_ref().clone();
+            super::CriRedact::redact(&mut message);
+            tracing::debug!(
+                target: "cri",
+                method = #name,
+                service = #service,
+                version = #version,
+                request = ?message,
+                "request",
+            );
+        }
+    });
+
+    // server streams are logged when opened, their items are not
+    let ok = if method.is_stream {
+        quote! {
+            Ok(_) => tracing::debug!(
+                target: "cri",
+                method = #name,
+                service = #service,
+                version = #version,
+                "response stream",
+            ),
+        }
+    } else {
+        quote! {
+            Ok(it) => {
+                let mut message = it.get_ref().clone();
+                super::CriRedact::redact(&mut message);
+                tracing::debug!(
+                    target: "cri",
+                    method = #name,
+                    service = #service,
+                    version = #version,
+                    response = ?message,
+                    "response",
+                );
+            }
+        }
+    };
+    body.after.push(quote! {
+        if log {
+            match &response {
+                #ok
+                Err(e) => tracing::debug!(
+                    target: "cri",
+                    method = #name,
+                    service = #service,
+                    version = #version,
+                    code = ?e.code(),
+                    error = e.message(),
+                    "response",
+                ),
+            }
+        }
+    });
+}
@@ -0,0 +1,238 @@
+use proc_macro2::{Literal, Span};
+use quote::quote;
+
+use crate::Protos;
+
+/// Whether `bytes` are made of length-delimited `file` entries, field 1 of `FileDescriptorSet`,
+/// so the decoding `CriReflection::new` does at runtime cannot fail on a truncated file.
+fn is_descriptor_set(mut bytes: &[u8]) -> bool {
+    while let Some((&tag, rest)) = bytes.split_first() {
+        if tag != 0x0a {
+            return false;
+        }
+        let mut len = 0usize;
+        let mut shift = 0;
+        let mut rest = rest;
+        loop {
+            let Some((&byte, tail)) = rest.split_first() else {
+                return false;
+            };
+            rest = tail;
+            len |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift > 63 {
+                return false;
+            }
+        }
+        if len > rest.len() {
+            return false;
+        }
+        bytes = &rest[len..];
+    }
+    true
+}
+
+/// Emits the `grpc.reflection.v1alpha` messages and `CriReflection`, the reflection service
+/// registered by `CriServices` when the `reflection` flag is set.
+///
+/// The encoded descriptor sets are embedded in the expansion, so tools such as grpcurl can
+/// introspect the socket without the proto files.
+pub(crate) fn expand_reflection(protos: &Protos) -> syn::Result<proc_macro2::TokenStream> {
+    let file = syn::parse_file(&protos.reflection)?;
+
+    let descriptor_sets = protos
+        .descriptor_sets
+        .iter()
+        .map(|(name, bytes)| {
+            if !is_descriptor_set(bytes) {
+                let message = format!("{} is not an encoded FileDescriptorSet", name);
+                return Err(syn::Error::new(Span::call_site(), message));
+            }
+            Ok(Literal::byte_string(bytes))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        #[doc = "gRPC server reflection protocol generated by auto_impl"]
+        pub mod grpc_reflection_v1alpha {
+            #file
+        }
+
+        /// `grpc.reflection.v1alpha.ServerReflection` implementation backed by the bundled
+        /// file descriptors.
+        #[derive(Clone, Debug)]
+        pub struct CriReflection {
+            services: ::std::sync::Arc<Vec<String>>,
+            files: ::std::sync::Arc<Vec<(prost_types::FileDescriptorProto, Vec<u8>)>>,
+            symbols: ::std::sync::Arc<::std::collections::HashMap<String, usize>>,
+        }
+
+        impl CriReflection {
+            /// Encoded `FileDescriptorSet`s of every proto file known to the generated server.
+            pub const FILE_DESCRIPTOR_SETS: &'static [&'static [u8]] = &[#(#descriptor_sets),*];
+
+            /// Creates a reflection service listing `services`.
+            pub fn new(services: &[&str]) -> Self {
+                use prost::Message;
+
+                let mut files = Vec::new();
+                let mut symbols = ::std::collections::HashMap::new();
+                for set in Self::FILE_DESCRIPTOR_SETS {
+                    let set = prost_types::FileDescriptorSet::decode(*set).expect("the descriptor sets are checked when expanding");
+                    for file in set.file {
+                        let prefix = match file.package() {
+                            "" => String::new(),
+                            package => format!("{}.", package),
+                        };
+                        for service in &file.service {
+                            let name = format!("{}{}", prefix, service.name());
+                            for method in &service.method {
+                                symbols.insert(format!("{}.{}", name, method.name()), files.len());
+                            }
+                            symbols.insert(name, files.len());
+                        }
+                        for message in &file.message_type {
+                            Self::message_symbols(&prefix, message, files.len(), &mut symbols);
+                        }
+                        for enumeration in &file.enum_type {
+                            symbols.insert(format!("{}{}", prefix, enumeration.name()), files.len());
+                        }
+
+                        let encoded = file.encode_to_vec();
+                        files.push((file, encoded));
+                    }
+                }
+
+                CriReflection {
+                    services: ::std::sync::Arc::new(services.iter().map(|it| it.to_string()).collect()),
+                    files: ::std::sync::Arc::new(files),
+                    symbols: ::std::sync::Arc::new(symbols),
+                }
+            }
+
+            fn message_symbols(
+                prefix: &str,
+                message: &prost_types::DescriptorProto,
+                file: usize,
+                symbols: &mut ::std::collections::HashMap<String, usize>,
+            ) {
+                let name = format!("{}{}", prefix, message.name());
+                for field in &message.field {
+                    symbols.insert(format!("{}.{}", name, field.name()), file);
+                }
+                for enumeration in &message.enum_type {
+                    symbols.insert(format!("{}.{}", name, enumeration.name()), file);
+                }
+                for nested in &message.nested_type {
+                    Self::message_symbols(&format!("{}.", name), nested, file, symbols);
+                }
+                symbols.insert(name, file);
+            }
+
+            /// Encoded descriptor of the file at `index` followed by its dependencies.
+            fn file_with_dependencies(&self, index: usize) -> Vec<Vec<u8>> {
+                let mut pending = vec![index];
+                let mut seen = Vec::new();
+                while let Some(index) = pending.pop() {
+                    if seen.contains(&index) {
+                        continue;
+                    }
+                    seen.push(index);
+                    for dependency in &self.files[index].0.dependency {
+                        if let Some(it) = self.files.iter().position(|(file, _)| file.name() == dependency) {
+                            pending.push(it);
+                        }
+                    }
+                }
+                seen.into_iter().map(|it| self.files[it].1.clone()).collect()
+            }
+
+            fn handle(
+                &self,
+                request: &grpc_reflection_v1alpha::server_reflection_request::MessageRequest,
+            ) -> grpc_reflection_v1alpha::server_reflection_response::MessageResponse {
+                use grpc_reflection_v1alpha::server_reflection_request::MessageRequest;
+                use grpc_reflection_v1alpha::server_reflection_response::MessageResponse;
+
+                let found = match request {
+                    MessageRequest::FileByFilename(name) => {
+                        self.files.iter().position(|(file, _)| file.name() == name)
+                    }
+                    MessageRequest::FileContainingSymbol(symbol) => {
+                        self.symbols.get(symbol.trim_start_matches('.')).copied()
+                    }
+                    MessageRequest::ListServices(_) => {
+                        return MessageResponse::ListServicesResponse(grpc_reflection_v1alpha::ListServiceResponse {
+                            service: self
+                                .services
+                                .iter()
+                                .map(|it| grpc_reflection_v1alpha::ServiceResponse { name: it.clone() })
+                                .collect(),
+                        });
+                    }
+                    // proto3 files carry no extensions
+                    MessageRequest::FileContainingExtension(_) | MessageRequest::AllExtensionNumbersOfType(_) => None,
+                };
+
+                match found {
+                    Some(index) => MessageResponse::FileDescriptorResponse(grpc_reflection_v1alpha::FileDescriptorResponse {
+                        file_descriptor_proto: self.file_with_dependencies(index),
+                    }),
+                    None => MessageResponse::ErrorResponse(grpc_reflection_v1alpha::ErrorResponse {
+                        error_code: tonic::Code::NotFound as i32,
+                        error_message: "not found".to_string(),
+                    }),
+                }
+            }
+        }
+
+        #[tonic::async_trait]
+        impl grpc_reflection_v1alpha::server_reflection_server::ServerReflection for CriReflection {
+            type ServerReflectionInfoStream = ::std::pin::Pin<
+                Box<
+                    dyn tonic::codegen::futures_core::Stream<
+                            Item = Result<grpc_reflection_v1alpha::ServerReflectionResponse, tonic::Status>,
+                        > + Send,
+                >,
+            >;
+
+            async fn server_reflection_info(
+                &self,
+                request: tonic::Request<tonic::Streaming<grpc_reflection_v1alpha::ServerReflectionRequest>>,
+            ) -> Result<tonic::Response<Self::ServerReflectionInfoStream>, tonic::Status> {
+                let mut requests = request.into_inner();
+                let reflection = self.clone();
+                let (tx, rx) = tokio::sync::mpsc::channel(4);
+
+                tokio::spawn(async move {
+                    loop {
+                        let request = match requests.message().await {
+                            Ok(Some(it)) => it,
+                            Ok(None) => break,
+                            Err(e) => {
+                                let _ = tx.send(Err(e)).await;
+                                break;
+                            }
+                        };
+                        let response = match &request.message_request {
+                            Some(it) => Ok(grpc_reflection_v1alpha::ServerReflectionResponse {
+                                valid_host: request.host.clone(),
+                                message_response: Some(reflection.handle(it)),
+                                original_request: Some(request),
+                            }),
+                            None => Err(tonic::Status::invalid_argument("message_request is required")),
+                        };
+                        if tx.send(response).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+
+                Ok(tonic::Response::new(Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))))
+            }
+        }
+    })
+}
@@ -0,0 +1,251 @@
+use proc_macro2::Ident;
+use quote::quote;
+
+use crate::AutoImplArgs;
+
+/// Permissions of the socket of `serve_unix` unless `socket_mode` is given: owner and group.
+const SOCKET_MODE: u32 = 0o660;
+
+/// Emits `CriServices` plus the `into_cri_services` and `serve_unix` helpers on the struct.
+///
+/// Every requested service is registered once per bundled version, so a single call serves
+/// whatever kubelet version connects.
+pub(crate) fn expand_server(containers: &[Ident], args: &AutoImplArgs, struct_name: &Ident) -> proc_macro2::TokenStream {
+    let mut fields = Vec::new();
+    let mut types = Vec::new();
+    let mut paths = Vec::new();
+    for container in containers {
+        for service_type in &args.services {
+            let service_mod = quote::format_ident!("{}", service_type.get_mod());
+            let server = quote::format_ident!("{}Server", service_type.get_trait());
+
+            fields.push(quote::format_ident!("{}_{}", container, service_type.get_name()));
+            types.push(quote! { #container::#service_mod::#server<#struct_name> });
+            paths.push(quote! { #container::#service_mod::#server });
+        }
+    }
+
+    let (first, rest) = fields.split_first().expect("at least one service is required");
+    let socket_mode = format!("0o{:o}", args.socket_mode.unwrap_or(SOCKET_MODE));
+    let socket_mode = syn::LitInt::new(&socket_mode, proc_macro2::Span::call_site());
+
+    // health and reflection are registered next to the CRI services when requested
+    let mut extra_fields = Vec::new();
+    let mut extra_inits = Vec::new();
+    let mut extra_registers = Vec::new();
+    let mut extra_names = Vec::new();
+    if args.health {
+        extra_fields.push(quote! {
+            /// Health reporter, keep a clone to change serving statuses at runtime.
+            pub health: CriHealth,
+        });
+        extra_inits.push(quote! { health: CriHealth::new(CriServices::NAMES), });
+        extra_registers.push(quote! {
+            .add_service(grpc_health_v1::health_server::HealthServer::new(self.health))
+        });
+        extra_names.push(quote! {
+            <grpc_health_v1::health_server::HealthServer<CriHealth> as tonic::server::NamedService>::NAME,
+        });
+    }
+    if args.reflection {
+        extra_names.push(quote! {
+            <grpc_reflection_v1alpha::server_reflection_server::ServerReflectionServer<CriReflection> as tonic::server::NamedService>::NAME,
+        });
+        extra_fields.push(quote! {
+            pub reflection: CriReflection,
+        });
+        extra_inits.push(quote! {
+            reflection: CriReflection::new(
+                &CriServices::NAMES.iter().copied().chain([#(#extra_names)*]).collect::<Vec<_>>(),
+            ),
+        });
+        extra_registers.push(quote! {
+            .add_service(grpc_reflection_v1alpha::server_reflection_server::ServerReflectionServer::new(self.reflection))
+        });
+    }
+    let health_drain = if args.health {
+        quote! {
+            let health = services.health.clone();
+        }
+    } else {
+        quote! {}
+    };
+    let health_not_serving = if args.health {
+        quote! {
+            health.set_all(grpc_health_v1::health_check_response::ServingStatus::NotServing);
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        /// Shutdown signal of a server started by `serve_unix_with_shutdown`.
+        ///
+        /// It is attached to the extensions of every request, so long running handlers can
+        /// give up early once the server starts draining.
+        #[derive(Clone, Debug)]
+        pub struct CriShutdown(tokio::sync::watch::Receiver<bool>);
+
+        impl CriShutdown {
+            pub fn is_draining(&self) -> bool {
+                *self.0.borrow()
+            }
+
+            /// Completes once the server starts draining or has stopped.
+            pub async fn wait(&self) {
+                let mut rx = self.0.clone();
+                while !*rx.borrow() {
+                    if rx.changed().await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        /// Server streaming response that ends cleanly once the server starts draining.
+        pub struct CriDrainStream<S> {
+            inner: ::std::pin::Pin<Box<S>>,
+            shutdown: Option<::std::pin::Pin<Box<dyn ::std::future::Future<Output = ()> + Send>>>,
+        }
+
+        impl<S> CriDrainStream<S> {
+            pub fn new(inner: S, shutdown: Option<CriShutdown>) -> Self {
+                CriDrainStream {
+                    inner: Box::pin(inner),
+                    shutdown: shutdown.map(|it| {
+                        Box::pin(async move { it.wait().await })
+                            as ::std::pin::Pin<Box<dyn ::std::future::Future<Output = ()> + Send>>
+                    }),
+                }
+            }
+        }
+
+        impl<S: tonic::codegen::futures_core::Stream> tonic::codegen::futures_core::Stream for CriDrainStream<S> {
+            type Item = S::Item;
+
+            fn poll_next(
+                mut self: ::std::pin::Pin<&mut Self>,
+                cx: &mut ::std::task::Context<'_>,
+            ) -> ::std::task::Poll<Option<Self::Item>> {
+                if let Some(shutdown) = self.shutdown.as_mut() {
+                    if shutdown.as_mut().poll(cx).is_ready() {
+                        return ::std::task::Poll::Ready(None);
+                    }
+                }
+                self.inner.as_mut().poll_next(cx)
+            }
+        }
+
+        /// Every CRI service generated for the struct, one server per service and version.
+        pub struct CriServices {
+            #(pub #fields: #types,)*
+            #(#extra_fields)*
+        }
+
+        impl CriServices {
+            /// Fully qualified names of the generated CRI services.
+            pub const NAMES: &'static [&'static str] = &[
+                #(<#types as tonic::server::NamedService>::NAME,)*
+            ];
+
+            /// Registers all services on `server`.
+            pub fn add_to<L: Clone>(self, server: &mut tonic::transport::Server<L>) -> tonic::transport::server::Router<L> {
+                server.add_service(self.#first)
+                    #(.add_service(self.#rest))*
+                    #(#extra_registers)*
+            }
+        }
+
+        impl #struct_name {
+            /// Wraps `self` in a server for every generated service and version.
+            pub fn into_cri_services(self: ::std::sync::Arc<Self>) -> CriServices {
+                CriServices {
+                    #(#fields: #paths::from_arc(self.clone()),)*
+                    #(#extra_inits)*
+                }
+            }
+
+            /// Serves every generated service on the unix socket at `path`.
+            ///
+            /// A stale socket left behind by a previous run is removed first, the new one gets
+            /// the permissions of `socket_mode`, only its owner and group by default.
+            pub async fn serve_unix(
+                self: ::std::sync::Arc<Self>,
+                path: impl AsRef<::std::path::Path>,
+            ) -> Result<(), Box<dyn ::std::error::Error + Send + Sync>> {
+                self.serve_unix_with_shutdown(path, ::std::future::pending(), ::std::time::Duration::ZERO)
+                    .await
+            }
+
+            /// Like `serve_unix`, but drains the server once `signal` completes.
+            ///
+            /// New calls are refused from then on, in-flight calls get `grace_period` to finish
+            /// and event streams end cleanly. The socket file is removed before returning.
+            pub async fn serve_unix_with_shutdown<F>(
+                self: ::std::sync::Arc<Self>,
+                path: impl AsRef<::std::path::Path>,
+                signal: F,
+                grace_period: ::std::time::Duration,
+            ) -> Result<(), Box<dyn ::std::error::Error + Send + Sync>>
+            where
+                F: ::std::future::Future<Output = ()>,
+            {
+                use ::std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+                const SOCKET_MODE: u32 = #socket_mode;
+
+                let path = path.as_ref();
+                match ::std::fs::symlink_metadata(path) {
+                    Ok(meta) if meta.file_type().is_socket() => ::std::fs::remove_file(path)?,
+                    Ok(_) => {
+                        return Err(format!("{} exists and is not a socket", path.display()).into());
+                    }
+                    Err(e) if e.kind() == ::std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+                if let Some(parent) = path.parent() {
+                    ::std::fs::create_dir_all(parent)?;
+                }
+
+                let listener = tokio::net::UnixListener::bind(path)?;
+                ::std::fs::set_permissions(path, ::std::fs::Permissions::from_mode(SOCKET_MODE))?;
+                let incoming = tokio_stream::wrappers::UnixListenerStream::new(listener);
+
+                let (drain_tx, drain_rx) = tokio::sync::watch::channel(false);
+                let shutdown = CriShutdown(drain_rx);
+                let draining = shutdown.clone();
+                // interceptors return tonic's `Status`, however large clippy finds it
+                #[allow(clippy::result_large_err)]
+                let attach = move |mut request: tonic::Request<()>| {
+                    request.extensions_mut().insert(shutdown.clone());
+                    Ok(request)
+                };
+                let mut server = tonic::transport::Server::builder().layer(tonic::service::interceptor(attach));
+                let services = self.into_cri_services();
+                #health_drain
+                let serve = services
+                    .add_to(&mut server)
+                    .serve_with_incoming_shutdown(incoming, async move {
+                        signal.await;
+                        #health_not_serving
+                        let _ = drain_tx.send(true);
+                    });
+                tokio::pin!(serve);
+
+                // once drained, whatever is still running after the grace period is dropped
+                let result = tokio::select! {
+                    result = &mut serve => result.map_err(Into::into),
+                    _ = async {
+                        draining.wait().await;
+                        tokio::time::sleep(grace_period).await;
+                    } => Ok(()),
+                };
+
+                match ::std::fs::remove_file(path) {
+                    Err(e) if e.kind() != ::std::io::ErrorKind::NotFound => Err(e.into()),
+                    _ => result,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::{Token, Type};
+
+use crate::message::{messages, segment};
+use crate::method::{MethodBody, MethodInfo};
+
+/// Message fields every request must set, in both CRI versions where they exist.
+const REQUIRED: [(&str, &str); 9] = [
+    ("RunPodSandboxRequest", "config"),
+    ("PodSandboxConfig", "metadata"),
+    ("CreateContainerRequest", "config"),
+    ("ContainerConfig", "metadata"),
+    ("ContainerConfig", "image"),
+    ("PullImageRequest", "image"),
+    ("ImageStatusRequest", "image"),
+    ("RemoveImageRequest", "image"),
+    ("UpdateRuntimeConfigRequest", "runtime_config"),
+];
+
+/// Message fields that must not be empty, besides the `pod_sandbox_id` and `container_id` of
+/// every request that is not a `List...Request`.
+const NON_EMPTY: [(&str, &str); 5] = [
+    ("PodSandboxMetadata", "name"),
+    ("ContainerMetadata", "name"),
+    ("ImageSpec", "image"),
+    ("ExecSyncRequest", "cmd"),
+    ("ExecRequest", "cmd"),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RuleKind {
+    /// An optional message field must be set.
+    Required,
+    /// A string, list or map field must not be empty.
+    NonEmpty,
+}
+
+/// A `Message.field` entry of `validate(required = [...], non_empty = [...])`.
+pub(crate) struct FieldRule {
+    message: Ident,
+    field: Ident,
+    kind: RuleKind,
+}
+
+/// Parses the `(required = [...], non_empty = [...])` of `validate`.
+pub(crate) fn parse_rules(input: syn::parse::ParseStream) -> syn::Result<Vec<FieldRule>> {
+    let mut rules = Vec::new();
+    while !input.is_empty() {
+        let key = input.parse::<Ident>()?;
+        let kind = if key == "required" {
+            RuleKind::Required
+        } else if key == "non_empty" {
+            RuleKind::NonEmpty
+        } else {
+            return Err(syn::Error::new(
+                key.span(),
+                format!("unknown rule `{}`, expected `required` or `non_empty`", key),
+            ));
+        };
+        input.parse::<Token![=]>()?;
+
+        let content;
+        syn::bracketed!(content in input);
+        while !content.is_empty() {
+            let message = content.parse::<Ident>()?;
+            content.parse::<Token![.]>()?;
+            let field = content.parse::<Ident>()?;
+            rules.push(FieldRule { message, field, kind });
+
+            if content.is_empty() {
+                break;
+            }
+            content.parse::<Token![,]>()?;
+        }
+
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<Token![,]>()?;
+    }
+    Ok(rules)
+}
+
+fn find_field<'a>(file: &'a syn::File, message: &Ident, field: &Ident) -> Option<&'a syn::Field> {
+    messages(file)
+        .find(|it| it.ident == *message)?
+        .fields
+        .iter()
+        .find(|it| it.ident.as_ref() == Some(field))
+}
+
+fn accepts(kind: RuleKind, ty: &Type) -> bool {
+    match (kind, segment(ty)) {
+        (RuleKind::Required, Some((name, _))) => name == "Option",
+        (RuleKind::NonEmpty, Some((name, _))) => name == "String" || name == "Vec" || name == "HashMap",
+        _ => false,
+    }
+}
+
+/// Checks that every user rule names a field of a CRI message whose type fits the rule.
+pub(crate) fn check_rules(files: &[&syn::File], rules: &[FieldRule]) -> syn::Result<()> {
+    for rule in rules {
+        let fields = files
+            .iter()
+            .filter_map(|file| find_field(file, &rule.message, &rule.field))
+            .collect::<Vec<_>>();
+        if fields.is_empty() {
+            return Err(syn::Error::new(
+                rule.message.span(),
+                format!("`{}.{}` is not a field of any CRI message", rule.message, rule.field),
+            ));
+        }
+        if !fields.iter().all(|it| accepts(rule.kind, &it.ty)) {
+            let expected = match rule.kind {
+                RuleKind::Required => "`required` applies to message fields",
+                RuleKind::NonEmpty => "`non_empty` applies to string, repeated and map fields",
+            };
+            return Err(syn::Error::new(rule.field.span(), expected));
+        }
+    }
+    Ok(())
+}
+
+/// The rules of one version: the built-in ones that exist in it plus the user's.
+pub(crate) struct Rules {
+    rules: Vec<(String, Ident, RuleKind)>,
+    /// Messages with a rule on themselves or on a nested message.
+    validated: Vec<String>,
+}
+
+impl Rules {
+    pub(crate) fn new(file: &syn::File, user: &[FieldRule]) -> Self {
+        let builtin = REQUIRED
+            .iter()
+            .map(|(message, field)| (*message, *field, RuleKind::Required))
+            .chain(NON_EMPTY.iter().map(|(message, field)| (*message, *field, RuleKind::NonEmpty)))
+            .map(|(message, field, kind)| (message.to_string(), Ident::new(field, Span::call_site()), kind));
+        let ids = messages(file)
+            .filter(|it| {
+                let name = it.ident.to_string();
+                name.ends_with("Request") && !name.starts_with("List")
+            })
+            .flat_map(|message| {
+                message.fields.iter().filter_map(move |it| {
+                    let field = it.ident.as_ref()?;
+                    (field == "pod_sandbox_id" || field == "container_id")
+                        .then(|| (message.ident.to_string(), field.clone(), RuleKind::NonEmpty))
+                })
+            });
+
+        let mut rules = Vec::new();
+        for (message, field, kind) in builtin.chain(ids).chain(user.iter().map(|it| (it.message.to_string(), it.field.clone(), it.kind))) {
+            let message_ident = Ident::new(&message, Span::call_site());
+            let fits = find_field(file, &message_ident, &field).is_some_and(|it| accepts(kind, &it.ty));
+            if fits && !rules.iter().any(|(m, f, k)| *m == message && *f == field && *k == kind) {
+                rules.push((message, field, kind));
+            }
+        }
+
+        // a message is validated when it has a rule or a field of a validated message type
+        let mut validated = rules.iter().map(|(message, _, _)| message.clone()).collect::<Vec<_>>();
+        loop {
+            let more = messages(file)
+                .filter(|it| !validated.contains(&it.ident.to_string()))
+                .filter(|it| it.fields.iter().any(|field| nested(&field.ty).is_some_and(|(_, name)| validated.contains(&name))))
+                .map(|it| it.ident.to_string())
+                .collect::<Vec<_>>();
+            if more.is_empty() {
+                break;
+            }
+            validated.extend(more);
+        }
+
+        Rules { rules, validated }
+    }
+
+    fn is_validated(&self, ty: &Type) -> bool {
+        matches!(segment(ty), Some((name, args)) if args.is_empty() && self.validated.contains(&name))
+    }
+}
+
+/// How a field holds a message: `("Option" | "Vec" | "", name)`.
+fn nested(ty: &Type) -> Option<(&'static str, String)> {
+    let (outer, args) = segment(ty)?;
+    match (outer.as_str(), args.as_slice()) {
+        ("Option", [it]) => Some(("Option", segment(it)?.0)),
+        ("Vec", [it]) => Some(("Vec", segment(it)?.0)),
+        (_, []) => Some(("", outer)),
+        _ => None,
+    }
+}
+
+/// Emits `CriValidate` impls for the messages of a version mod that have rules.
+pub(crate) fn expand_validate_impls(file: &syn::File, rules: &Rules) -> TokenStream {
+    let impls = messages(file).filter(|it| rules.validated.contains(&it.ident.to_string())).map(|message| {
+        let name = message.ident.to_string();
+        let checks = rules.rules.iter().filter(|(m, _, _)| *m == name).map(|(_, field, kind)| {
+            let field_name = field.to_string();
+            match kind {
+                RuleKind::Required => quote! {
+                    if self.#field.is_none() {
+                        return Err(tonic::Status::invalid_argument(format!("{} is required", field(#field_name))));
+                    }
+                },
+                RuleKind::NonEmpty => quote! {
+                    if self.#field.is_empty() {
+                        return Err(tonic::Status::invalid_argument(format!("{} must not be empty", field(#field_name))));
+                    }
+                },
+            }
+        });
+        let nested = message.fields.iter().filter_map(|it| {
+            let field = it.ident.as_ref()?;
+            let field_name = field.to_string();
+            let (outer, _) = nested(&it.ty)?;
+            let inner = match outer {
+                "" => &it.ty,
+                _ => segment(&it.ty)?.1[0],
+            };
+            if !rules.is_validated(inner) {
+                return None;
+            }
+            Some(match outer {
+                "Option" => quote! {
+                    if let Some(it) = &self.#field {
+                        super::CriValidate::validate(it, &field(#field_name))?;
+                    }
+                },
+                "Vec" => quote! {
+                    for (index, it) in self.#field.iter().enumerate() {
+                        super::CriValidate::validate(it, &format!("{}[{}]", field(#field_name), index))?;
+                    }
+                },
+                _ => quote! {
+                    super::CriValidate::validate(&self.#field, &field(#field_name))?;
+                },
+            })
+        });
+
+        let ident = &message.ident;
+        quote! {
+            impl super::CriValidate for #ident {
+                fn validate(&self, path: &str) -> Result<(), tonic::Status> {
+                    let field = |name: &str| {
+                        if path.is_empty() {
+                            name.to_string()
+                        } else {
+                            format!("{}.{}", path, name)
+                        }
+                    };
+                    #(#checks)*
+                    #(#nested)*
+                    Ok(())
+                }
+            }
+        }
+    });
+
+    quote! {
+        #(#impls)*
+    }
+}
+
+/// Emits `CriValidate`, implemented by every CRI message that has rules.
+pub(crate) fn expand_validate_trait() -> TokenStream {
+    quote! {
+        /// Checks the fields of a CRI request a handler relies on before it is called.
+        pub trait CriValidate {
+            /// Fails with `InvalidArgument` naming the offending field, `path` is the field
+            /// path of `self` in the request, empty for the request itself.
+            #[allow(clippy::result_large_err)]
+            fn validate(&self, path: &str) -> Result<(), tonic::Status>;
+        }
+    }
+}
+
+/// Validates the request of `method` before it is forwarded, a rejection is the result of
+/// the call for the other features.
+pub(crate) fn validate_body(body: &mut MethodBody, rules: &Rules, method: &MethodInfo) {
+    let (Some(request), Some(request_ty)) = (method.request(), &method.request_ty) else {
+        return;
+    };
+    if !rules.is_validated(request_ty) {
+        return;
+    }
+
+    let call = &body.call;
+    body.call = quote! {
+        match super::CriValidate::validate(#request.get_ref(), "") {
+            Ok(()) => #call,
+            Err(e) => Err(e),
+        }
+    };
+}
@@ -0,0 +1,182 @@
+use std::path::PathBuf;
+
+use auto_impl_trait_engine::{Build, Config, Protos, ServiceType, SuperRemover, Version};
+use quote::quote;
+use syn::visit_mut::VisitMut;
+
+/// The `proto` dir of the engine, the one `Protos::bundled()` includes.
+fn proto_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("proto")
+}
+
+/// A path under the temporary dir unique to this run, removed if it exists.
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("auto_impl_trait_engine-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&path);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn square() -> syn::ItemStruct {
+    syn::parse_quote! {
+        pub struct Square {
+            side: i32,
+        }
+    }
+}
+
+#[test]
+fn attributes_are_split_into_services_and_options() {
+    let attr = quote!(runtime, image, mock, limits(pull_image(concurrency = 2)));
+    let config = Config::from_attribute(attr, quote!(pub struct Square;), Protos::bundled()).unwrap();
+    assert_eq!(config.services, [ServiceType::Runtime, ServiceType::Image]);
+    assert_eq!(config.options.to_string(), quote!(mock, limits(pull_image(concurrency = 2))).to_string());
+    assert_eq!(config.target.ident, "Square");
+
+    let e = Config::from_attribute(quote!(image mock), quote!(pub struct Square;), Protos::bundled());
+    assert_eq!(e.err().unwrap().to_string(), "expected `,`");
+}
+
+#[test]
+fn typed_configs_expand_as_the_attribute() {
+    let mut config = Config::new(square(), Protos::bundled());
+    config.services.push(ServiceType::Image);
+    config.options = quote!(mock);
+    let target = square();
+    let attribute = Config::from_attribute(quote!(image, mock), quote!(#target), Protos::bundled()).unwrap();
+    let source = config.to_source().unwrap();
+    assert_eq!(source, attribute.to_source().unwrap());
+    assert!(source.starts_with("///Generated by `#[derive(auto_impl(image))]`\npub struct Square {\n    side: i32,\n}\n"));
+    assert!(source.contains("\npub struct MockSquare {"));
+
+    config.version = Some(Version::V1);
+    let source = config.to_source().unwrap();
+    assert!(source.contains("\npub mod v1 {\n"));
+    assert!(!source.contains("pub mod v1alpha2"));
+    assert!(!source.contains("ImageService_v1alpha2"));
+
+    config.options = quote!(bogus);
+    assert_eq!(config.expand().unwrap_err().to_string(), "unknown auto_impl option `bogus`");
+    config.services.clear();
+    config.options = quote!(mock);
+    assert_eq!(config.expand().unwrap_err().to_string(), "proto type is required(runtime or image)");
+}
+
+#[test]
+fn protos_are_read_from_a_dir() {
+    let e = Protos::read("./missing").unwrap_err().to_string();
+    assert!(e.starts_with("read ./missing/runtime.v1.rs failed: "), "{}", e);
+
+    let mut protos = Protos::read(proto_dir()).unwrap();
+    assert!(protos.v1alpha2.contains("pub mod runtime_service_server {"));
+    let bundled = Protos::bundled();
+    assert_eq!(protos.health, bundled.health);
+    assert_eq!(protos.reflection, bundled.reflection);
+    assert_eq!(protos.descriptor_sets, bundled.descriptor_sets);
+    assert_eq!(protos.descriptor_sets[2].0, "grpc.health.v1.bin");
+    protos.get_mut(Version::V1).clear();
+    assert!(protos.v1.is_empty());
+}
+
+#[test]
+fn super_paths_are_removed() {
+    let mut ty: syn::Type = syn::parse_quote!(Result<super::VersionResponse, tonic::Status>);
+    SuperRemover.visit_type_mut(&mut ty);
+    assert_eq!(quote!(#ty).to_string(), quote!(Result<VersionResponse, tonic::Status>).to_string());
+    assert_eq!(ServiceType::Image.get_trait(), "ImageService");
+}
+
+#[test]
+fn builds_write_the_expansions_to_out_dir() {
+    let out_dir = temp_path("engine-out-dir");
+    std::fs::create_dir(&out_dir).unwrap();
+    let build = || {
+        Build::new()
+            .out_dir(&out_dir)
+            .expand("square.rs", "image, mock", "pub struct Square { side: i32 }")
+    };
+    let paths = build().generate().unwrap();
+    assert_eq!(paths, [out_dir.join("square.rs")]);
+    let source = std::fs::read_to_string(&paths[0]).unwrap();
+    let target = square();
+    let attribute = Config::from_attribute(quote!(image, mock), quote!(#target), Protos::bundled()).unwrap();
+    assert_eq!(source, attribute.to_source().unwrap());
+
+    // unchanged files are not written again
+    let modified = std::fs::metadata(&paths[0]).unwrap().modified().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    build().generate().unwrap();
+    assert_eq!(std::fs::metadata(&paths[0]).unwrap().modified().unwrap(), modified);
+
+    let e = Build::new()
+        .out_dir(&out_dir)
+        .expand("bad.rs", "image,, mock", "pub struct Square;")
+        .generate()
+        .unwrap_err();
+    assert_eq!(e.to_string(), "expected identifier");
+    let e = Build::new().out_dir(&out_dir).proto_dir("./missing").generate().unwrap_err().to_string();
+    assert!(e.starts_with("read ./missing/runtime.v1.rs failed: "), "{}", e);
+}
+
+#[test]
+fn health_and_reflection_come_from_the_protos() {
+    let mut config = Config::from_attribute(quote!(image, server, health, reflection), quote!(pub struct Square;), Protos::bundled()).unwrap();
+    config.protos.health = config.protos.health.replace("pub struct HealthCheckRequest", "pub struct HealthQuery");
+    let source = config.to_source().unwrap();
+    assert!(source.contains("pub struct HealthQuery {"));
+    assert!(source.contains("pub struct CriReflection {"));
+
+    config.protos.descriptor_sets[0].1.truncate(10);
+    let e = config.expand().unwrap_err().to_string();
+    assert_eq!(e, "runtime.v1.bin is not an encoded FileDescriptorSet");
+}
+
+#[test]
+fn builds_read_every_proto_from_the_proto_dir() {
+    let copied = temp_path("engine-proto-dir");
+    std::fs::create_dir(&copied).unwrap();
+    for name in Protos::files() {
+        std::fs::copy(proto_dir().join(name), copied.join(name)).unwrap();
+    }
+    let out_dir = temp_path("engine-proto-dir-out");
+    std::fs::create_dir(&out_dir).unwrap();
+    let build = || {
+        Build::new()
+            .proto_dir(&copied)
+            .out_dir(&out_dir)
+            .expand("square.rs", "image, server, health", "pub struct Square { side: i32 }")
+    };
+    let paths = build().generate().unwrap();
+    let source = std::fs::read_to_string(&paths[0]).unwrap();
+    let target = square();
+    let attribute = Config::from_attribute(quote!(image, server, health), quote!(#target), Protos::bundled()).unwrap();
+    assert_eq!(source, attribute.to_source().unwrap());
+
+    std::fs::remove_file(copied.join("grpc.health.v1.rs")).unwrap();
+    let e = build().generate().unwrap_err().to_string();
+    let prefix = format!("read {} failed: ", copied.join("grpc.health.v1.rs").display());
+    assert!(e.starts_with(&prefix), "{}", e);
+}
+
+#[test]
+fn expansions_of_the_flags_print_as_source() {
+    let attr = quote!(
+        runtime, image, server, health, reflection, opentelemetry, metrics, mock, mirror, chaos, json, client, log,
+        authorize
+    );
+    let config = Config::from_attribute(attr, quote!(pub struct Square;), Protos::bundled()).unwrap();
+    let source = config.to_source().unwrap();
+    assert!(source.contains("pub struct CriChaos<T> {"));
+    assert!(source.contains("pub fn authorize<T>("));
+}
+
+#[test]
+fn limits_only_apply_to_unary_methods() {
+    let expand = |attr| Config::from_attribute(attr, quote!(pub struct Square;), Protos::bundled()).unwrap().expand();
+    expand(quote!(runtime, limits(version(concurrency = 1)))).unwrap();
+    let e = expand(quote!(runtime, limits(get_container_events(concurrency = 1)))).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "`get_container_events` is a server-streaming method, `limits` only applies to unary calls"
+    );
+}
@@ -0,0 +1,6 @@
+fn main() {
+    auto_impl_trait_engine::Build::new()
+        .expand("square.rs", "image, mock", "pub struct Square { pub(crate) side: i32 }")
+        .generate()
+        .unwrap_or_else(|e| panic!("{}", e));
+}
@@ -0,0 +1,35 @@
+//! What `#[auto_impl(image, mock)]` on `Square` generates, written to `$OUT_DIR/square.rs` by
+//! the build script instead of expanded by the attribute.
+
+// lints see included code, unlike the output of the attribute
+#[allow(clippy::all)]
+mod square {
+    include!(concat!(env!("OUT_DIR"), "/square.rs"));
+}
+
+pub use square::*;
+
+/// Every image service method answers with the default response.
+macro_rules! defaults {
+    ($($method:ident),* $(,)?) => {
+        impl Square {
+            $(
+                pub async fn $method<Req, Resp: Default>(&self, _request: tonic::Request<Req>) -> Result<tonic::Response<Resp>, tonic::Status> {
+                    Ok(tonic::Response::new(Resp::default()))
+                }
+            )*
+        }
+    };
+}
+
+defaults!(list_images, image_status, pull_image, remove_image, image_fs_info);
+
+impl Square {
+    pub fn new(side: i32) -> Self {
+        Square { side }
+    }
+
+    pub fn side(&self) -> i32 {
+        self.side
+    }
+}
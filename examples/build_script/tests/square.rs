@@ -0,0 +1,29 @@
+use build_script_example::{v1, v1alpha2, MockSquare, Square};
+use tonic::{Code, Request, Status};
+use v1::image_service_server::ImageService as V1;
+use v1alpha2::image_service_server::ImageService as V1alpha2;
+
+#[tokio::test]
+async fn generated_impls_forward_to_the_struct() {
+    let square = Square::new(2);
+    assert_eq!(square.side(), 2);
+    let response = V1::list_images(&square, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+    assert!(response.into_inner().images.is_empty());
+    let request = Request::new(v1alpha2::ImageFsInfoRequest::default());
+    V1alpha2::image_fs_info(&square, request).await.unwrap();
+}
+
+#[tokio::test]
+async fn generated_mocks_are_scripted() {
+    let mock = MockSquare::new();
+    mock.v1_remove_image().returns(Err(Status::not_found("gone")));
+    let e = V1::remove_image(&mock, Request::new(v1::RemoveImageRequest::default())).await.unwrap_err();
+    assert_eq!(e.code(), Code::NotFound);
+    assert_eq!(mock.v1_remove_image().calls().len(), 1);
+}
+
+#[test]
+fn generated_source_is_readable() {
+    let source = std::fs::read_to_string(concat!(env!("OUT_DIR"), "/square.rs")).unwrap();
+    assert!(source.starts_with("///Generated by `#[derive(auto_impl(image))]`\npub struct Square {\n    pub(crate) side: i32,\n}\n"));
+}
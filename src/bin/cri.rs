@@ -0,0 +1,40 @@
+//! `cri`, a crictl-style command-line client of any CRI socket, see `cri --help`.
+
+#[path = "../cli.rs"]
+mod cli;
+#[path = "../transcode.rs"]
+mod transcode;
+
+use std::io::Write;
+
+#[tokio::main]
+async fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|it| it == "-h" || it == "--help") {
+        print!("{}", cli::USAGE);
+        return;
+    }
+    let options = match cli::Options::parse(args) {
+        Ok(it) => it,
+        Err(e) => {
+            eprint!("cri: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let result = if options.command == "expand" {
+        cli::expand(&options, &mut out)
+    } else {
+        match cli::Client::connect(&options.endpoint, options.api).await {
+            Ok(client) => cli::run(&client, &options, &mut out).await,
+            Err(e) => Err(e),
+        }
+    };
+    let _ = out.flush();
+    if let Err(e) = result {
+        eprintln!("cri: {}", e);
+        std::process::exit(1);
+    }
+}
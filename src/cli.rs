@@ -0,0 +1,632 @@
+//! `cri`, a crictl-style client of any CRI socket built on the bundled types.
+//!
+//! The runtime is asked for `runtime.v1` first and `runtime.v1alpha2` when it does not serve
+//! it, unless `--api` says which one to use. Both versions share the v1 code: v1alpha2
+//! messages are wire compatible with v1, so requests and responses are transcoded through
+//! their protobuf encoding.
+//!
+//! `cri expand` needs no runtime: it prints what `#[auto_impl(...)]` generates, through the
+//! same engine the attribute runs.
+
+// calls return `tonic::Status` as the generated clients do
+#![allow(clippy::result_large_err)]
+
+use std::error::Error;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use auto_impl_trait::auto_impl;
+use auto_impl_trait_engine as engine;
+use tonic::Code;
+
+use crate::transcode::transcode;
+
+/// Only the clients and messages are generated, `Cri` itself serves nothing.
+#[auto_impl(client, json)]
+#[allow(dead_code)]
+pub struct Cri;
+
+pub const USAGE: &str = "\
+usage: cri [-r ENDPOINT] [-o table|json|yaml] [--api v1|v1alpha2] COMMAND [ARGS]
+
+commands:
+  version          runtime name and version
+  info             runtime status and conditions
+  pods             list pod sandboxes
+  ps [-a]          list containers, only running ones unless -a
+  images           list images
+  inspect ID       container status
+  inspectp ID      pod sandbox status
+  pull IMAGE       pull an image
+  rmi IMAGE...     remove images
+  stop ID...       stop containers
+  rm ID...         remove containers
+  stats            resource usage of the running containers
+  events           follow container events, runtime.v1 only
+  expand OPTION... print what #[auto_impl(OPTION, ...)] generates, only the
+                   --api version when given, e.g. `cri expand image mock`
+                   --proto FILE   expands the --api version from FILE instead
+                   --struct ITEM  the annotated struct, `pub struct Cri;` by default
+
+ENDPOINT is a unix socket path or unix:// URL, $CONTAINER_RUNTIME_ENDPOINT or
+/run/containerd/containerd.sock by default.
+";
+
+/// Socket of the runtime when neither `-r` nor `$CONTAINER_RUNTIME_ENDPOINT` is given.
+const DEFAULT_ENDPOINT: &str = "unix:///run/containerd/containerd.sock";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Output {
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Api {
+    V1,
+    V1alpha2,
+}
+
+/// The parsed command line.
+#[derive(Debug)]
+pub struct Options {
+    pub endpoint: String,
+    pub output: Output,
+    pub api: Option<Api>,
+    pub command: String,
+    pub args: Vec<String>,
+    /// `-a` of `ps`.
+    pub all: bool,
+    /// `--proto` of `expand`.
+    pub proto: Option<String>,
+    /// `--struct` of `expand`.
+    pub item: Option<String>,
+}
+
+impl Options {
+    /// Parses the arguments after the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            endpoint: std::env::var("CONTAINER_RUNTIME_ENDPOINT")
+                .unwrap_or_else(|_| DEFAULT_ENDPOINT.to_string()),
+            output: Output::Table,
+            api: None,
+            command: String::new(),
+            args: Vec::new(),
+            all: false,
+            proto: None,
+            item: None,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("`{}` needs a value", name))
+            };
+            match arg.as_str() {
+                "-r" | "--runtime-endpoint" => options.endpoint = value(&arg)?,
+                "-o" | "--output" => {
+                    options.output = match value(&arg)?.as_str() {
+                        "table" => Output::Table,
+                        "json" => Output::Json,
+                        "yaml" => Output::Yaml,
+                        other => {
+                            return Err(format!(
+                                "unknown output `{}`, expected table, json or yaml",
+                                other
+                            ))
+                        }
+                    }
+                }
+                "--api" => {
+                    options.api = match value(&arg)?.as_str() {
+                        "v1" => Some(Api::V1),
+                        "v1alpha2" => Some(Api::V1alpha2),
+                        other => {
+                            return Err(format!("unknown api `{}`, expected v1 or v1alpha2", other))
+                        }
+                    }
+                }
+                "-a" | "--all" => options.all = true,
+                "--proto" => options.proto = Some(value(&arg)?),
+                "--struct" => options.item = Some(value(&arg)?),
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+                _ if options.command.is_empty() => options.command = arg,
+                _ => options.args.push(arg),
+            }
+        }
+
+        if options.command.is_empty() {
+            return Err("a command is required".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// A connection to the runtime in the version it speaks.
+#[derive(Clone, Debug)]
+pub enum Client {
+    V1(v1::CriClient),
+    V1alpha2(v1alpha2::CriClient),
+}
+
+/// Calls `method` of the client, v1 messages in and out whatever the version.
+macro_rules! call {
+    ($client:expr, $method:ident, $request:expr) => {
+        match $client {
+            Client::V1(client) => client.$method($request).await,
+            Client::V1alpha2(client) => match transcode(&$request) {
+                Ok(request) => client.$method(request).await.and_then(|it| transcode(&it)),
+                Err(e) => Err(e),
+            },
+        }
+    };
+}
+
+impl Client {
+    /// Connects to `endpoint`, detecting the version unless `api` is given.
+    pub async fn connect(
+        endpoint: &str,
+        api: Option<Api>,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let path = endpoint.strip_prefix("unix://").unwrap_or(endpoint);
+        match api {
+            Some(Api::V1) => Ok(Client::V1(v1::CriClient::connect_unix(path).await?)),
+            Some(Api::V1alpha2) => Ok(Client::V1alpha2(
+                v1alpha2::CriClient::connect_unix(path).await?,
+            )),
+            None => {
+                let client = v1::CriClient::connect_unix(path).await?;
+                let request = v1::VersionRequest {
+                    version: "v1".to_string(),
+                };
+                match client.version(request).await {
+                    Ok(_) => Ok(Client::V1(client)),
+                    Err(e) if e.code() == Code::Unimplemented => Ok(Client::V1alpha2(
+                        v1alpha2::CriClient::connect_unix(path).await?,
+                    )),
+                    Err(e) => Err(e.into()),
+                }
+            }
+        }
+    }
+}
+
+/// Runs the command of `options` against `client`, writing what it prints to `out`.
+pub async fn run(
+    client: &Client,
+    options: &Options,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = &options.args;
+    let output = options.output;
+    match options.command.as_str() {
+        "version" => {
+            let request = v1::VersionRequest {
+                version: "v1".to_string(),
+            };
+            let response = call!(client, version, request)?;
+            let table = || {
+                vec![
+                    vec!["Version:".to_string(), response.version.clone()],
+                    vec!["RuntimeName:".to_string(), response.runtime_name.clone()],
+                    vec![
+                        "RuntimeVersion:".to_string(),
+                        response.runtime_version.clone(),
+                    ],
+                    vec![
+                        "RuntimeApiVersion:".to_string(),
+                        response.runtime_api_version.clone(),
+                    ],
+                ]
+            };
+            render(out, output, &response, table)?;
+        }
+        "info" => {
+            let response = call!(client, status, v1::StatusRequest { verbose: true })?;
+            render(out, output.or_json(), &response, Vec::new)?;
+        }
+        "pods" => {
+            let response = call!(
+                client,
+                list_pod_sandbox,
+                v1::ListPodSandboxRequest::default()
+            )?;
+            let table = || {
+                let mut rows = vec![row(&[
+                    "POD ID",
+                    "CREATED",
+                    "STATE",
+                    "NAME",
+                    "NAMESPACE",
+                    "ATTEMPT",
+                    "RUNTIME",
+                ])];
+                for it in &response.items {
+                    let metadata = it.metadata.clone().unwrap_or_default();
+                    let state = match v1::PodSandboxState::from_i32(it.state) {
+                        Some(v1::PodSandboxState::SandboxReady) => "Ready",
+                        _ => "NotReady",
+                    };
+                    let runtime = if it.runtime_handler.is_empty() {
+                        "(default)"
+                    } else {
+                        &it.runtime_handler
+                    };
+                    rows.push(vec![
+                        short_id(&it.id),
+                        ago(it.created_at),
+                        state.to_string(),
+                        metadata.name,
+                        metadata.namespace,
+                        metadata.attempt.to_string(),
+                        runtime.to_string(),
+                    ]);
+                }
+                rows
+            };
+            render(out, output, &response, table)?;
+        }
+        "ps" => {
+            let filter = (!options.all).then(|| v1::ContainerFilter {
+                state: Some(v1::ContainerStateValue {
+                    state: v1::ContainerState::ContainerRunning as i32,
+                }),
+                ..Default::default()
+            });
+            let response = call!(
+                client,
+                list_containers,
+                v1::ListContainersRequest { filter }
+            )?;
+            let table = || {
+                let mut rows = vec![row(&[
+                    "CONTAINER",
+                    "IMAGE",
+                    "CREATED",
+                    "STATE",
+                    "NAME",
+                    "ATTEMPT",
+                    "POD ID",
+                ])];
+                for it in &response.containers {
+                    let metadata = it.metadata.clone().unwrap_or_default();
+                    let image = it
+                        .image
+                        .as_ref()
+                        .map_or(it.image_ref.clone(), |it| it.image.clone());
+                    rows.push(vec![
+                        short_id(&it.id),
+                        image,
+                        ago(it.created_at),
+                        container_state(it.state).to_string(),
+                        metadata.name,
+                        metadata.attempt.to_string(),
+                        short_id(&it.pod_sandbox_id),
+                    ]);
+                }
+                rows
+            };
+            render(out, output, &response, table)?;
+        }
+        "images" => {
+            let response = call!(client, list_images, v1::ListImagesRequest::default())?;
+            let table = || {
+                let mut rows = vec![row(&["IMAGE", "TAG", "IMAGE ID", "SIZE"])];
+                for it in &response.images {
+                    let size = human_size(it.size);
+                    let id = short_id(it.id.strip_prefix("sha256:").unwrap_or(&it.id));
+                    if it.repo_tags.is_empty() {
+                        let repo = it
+                            .repo_digests
+                            .first()
+                            .map_or("<none>", |it| it.split('@').next().unwrap_or(it));
+                        rows.push(vec![
+                            repo.to_string(),
+                            "<none>".to_string(),
+                            id.clone(),
+                            size.clone(),
+                        ]);
+                    }
+                    for tag in &it.repo_tags {
+                        let (repo, tag) = split_tag(tag);
+                        rows.push(vec![
+                            repo.to_string(),
+                            tag.to_string(),
+                            id.clone(),
+                            size.clone(),
+                        ]);
+                    }
+                }
+                rows
+            };
+            render(out, output, &response, table)?;
+        }
+        "inspect" => {
+            for id in one_or_more(args, "a container id")? {
+                let request = v1::ContainerStatusRequest {
+                    container_id: id.clone(),
+                    verbose: true,
+                };
+                let response = call!(client, container_status, request)?;
+                render(out, output.or_json(), &response, Vec::new)?;
+            }
+        }
+        "inspectp" => {
+            for id in one_or_more(args, "a pod sandbox id")? {
+                let request = v1::PodSandboxStatusRequest {
+                    pod_sandbox_id: id.clone(),
+                    verbose: true,
+                };
+                let response = call!(client, pod_sandbox_status, request)?;
+                render(out, output.or_json(), &response, Vec::new)?;
+            }
+        }
+        "pull" => {
+            for image in one_or_more(args, "an image")? {
+                let request = v1::PullImageRequest {
+                    image: Some(v1::ImageSpec {
+                        image: image.clone(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                };
+                let response = call!(client, pull_image, request)?;
+                writeln!(out, "Image is up to date for {}", response.image_ref)?;
+            }
+        }
+        "rmi" => {
+            for image in one_or_more(args, "an image")? {
+                let request = v1::RemoveImageRequest {
+                    image: Some(v1::ImageSpec {
+                        image: image.clone(),
+                        ..Default::default()
+                    }),
+                };
+                call!(client, remove_image, request)?;
+                writeln!(out, "Deleted: {}", image)?;
+            }
+        }
+        "stop" => {
+            for id in one_or_more(args, "a container id")? {
+                let request = v1::StopContainerRequest {
+                    container_id: id.clone(),
+                    timeout: 0,
+                };
+                call!(client, stop_container, request)?;
+                writeln!(out, "{}", id)?;
+            }
+        }
+        "rm" => {
+            for id in one_or_more(args, "a container id")? {
+                let request = v1::RemoveContainerRequest {
+                    container_id: id.clone(),
+                };
+                call!(client, remove_container, request)?;
+                writeln!(out, "{}", id)?;
+            }
+        }
+        "stats" => {
+            let response = call!(
+                client,
+                list_container_stats,
+                v1::ListContainerStatsRequest::default()
+            )?;
+            let table = || {
+                let mut rows = vec![row(&[
+                    "CONTAINER",
+                    "NAME",
+                    "CPU %",
+                    "MEM",
+                    "DISK",
+                    "INODES",
+                ])];
+                let value = |it: &Option<v1::UInt64Value>| it.as_ref().map_or(0, |it| it.value);
+                for it in &response.stats {
+                    let attributes = it.attributes.clone().unwrap_or_default();
+                    let cpu = it.cpu.as_ref().map_or(0, |it| value(&it.usage_nano_cores));
+                    let memory = it
+                        .memory
+                        .as_ref()
+                        .map_or(0, |it| value(&it.working_set_bytes));
+                    let layer = it.writable_layer.clone().unwrap_or_default();
+                    rows.push(vec![
+                        short_id(&attributes.id),
+                        attributes.metadata.unwrap_or_default().name,
+                        format!("{:.2}", cpu as f64 / 10_000_000.0),
+                        human_size(memory),
+                        human_size(value(&layer.used_bytes)),
+                        value(&layer.inodes_used).to_string(),
+                    ]);
+                }
+                rows
+            };
+            render(out, output, &response, table)?;
+        }
+        "events" => {
+            let Client::V1(client) = client else {
+                return Err("container events are only served by runtime.v1".into());
+            };
+            let mut events = client
+                .get_container_events(v1::GetEventsRequest::default())
+                .await?;
+            while let Some(event) = events.message().await? {
+                match output {
+                    Output::Table => {
+                        let kind = v1::ContainerEventType::from_i32(event.container_event_type)
+                            .map_or("UNKNOWN", |it| it.as_str_name());
+                        writeln!(
+                            out,
+                            "{}   {}   {}",
+                            ago(event.created_at),
+                            short_id(&event.container_id),
+                            kind
+                        )?;
+                    }
+                    _ => render(out, output, &event, Vec::new)?,
+                }
+                out.flush()?;
+            }
+        }
+        other => return Err(format!("unknown command `{}`\n\n{}", other, USAGE).into()),
+    }
+    Ok(())
+}
+
+/// Prints what `#[auto_impl(...)]` with the arguments of `options` generates, as formatted
+/// Rust.
+pub fn expand(options: &Options, out: &mut dyn Write) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tokens = |source: &str| {
+        source
+            .parse::<proc_macro2::TokenStream>()
+            .map_err(|e| format!("invalid tokens `{}`: {}", source, e))
+    };
+    let protos = engine::Protos::bundled();
+    let attr = tokens(&options.args.join(", "))?;
+    let item = tokens(options.item.as_deref().unwrap_or("pub struct Cri;"))?;
+    let mut config = engine::Config::from_attribute(attr, item, protos)?;
+    config.version = options.api.map(|it| match it {
+        Api::V1 => engine::Version::V1,
+        Api::V1alpha2 => engine::Version::V1alpha2,
+    });
+    if let Some(path) = &options.proto {
+        let Some(version) = config.version else {
+            return Err("`--proto` needs `--api` to say which version it holds".into());
+        };
+        *config.protos.get_mut(version) = std::fs::read_to_string(path).map_err(|e| format!("read {} failed: {}", path, e))?;
+    }
+
+    write!(out, "{}", config.to_source()?)?;
+    Ok(())
+}
+
+impl Output {
+    /// JSON for the commands whose output is not a table.
+    fn or_json(self) -> Self {
+        match self {
+            Output::Table => Output::Json,
+            it => it,
+        }
+    }
+}
+
+/// Writes `message` as JSON or YAML, or the rows of `table` aligned in columns.
+fn render<M, F>(out: &mut dyn Write, output: Output, message: &M, table: F) -> std::io::Result<()>
+where
+    M: CriJson,
+    F: FnOnce() -> Vec<Vec<String>>,
+{
+    match output {
+        Output::Json => writeln!(out, "{}", message.to_value().to_json()),
+        Output::Yaml => write!(out, "{}", message.to_value().to_yaml()),
+        Output::Table => {
+            let rows = table();
+            let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+            let widths = (0..columns)
+                .map(|column| {
+                    rows.iter()
+                        .filter_map(|it| it.get(column))
+                        .map(|it| it.chars().count())
+                        .max()
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<_>>();
+            for it in &rows {
+                let mut line = String::new();
+                for (column, cell) in it.iter().enumerate() {
+                    if column + 1 < it.len() {
+                        line.push_str(&format!("{:width$}   ", cell, width = widths[column]));
+                    } else {
+                        line.push_str(cell);
+                    }
+                }
+                writeln!(out, "{}", line)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn row(cells: &[&str]) -> Vec<String> {
+    cells.iter().map(|it| it.to_string()).collect()
+}
+
+fn one_or_more<'a>(args: &'a [String], what: &str) -> Result<&'a [String], String> {
+    if args.is_empty() {
+        Err(format!("{} is required", what))
+    } else {
+        Ok(args)
+    }
+}
+
+/// The first 13 characters of an id, as crictl prints them.
+fn short_id(id: &str) -> String {
+    id.chars().take(13).collect()
+}
+
+fn container_state(state: i32) -> &'static str {
+    match v1::ContainerState::from_i32(state) {
+        Some(v1::ContainerState::ContainerCreated) => "Created",
+        Some(v1::ContainerState::ContainerRunning) => "Running",
+        Some(v1::ContainerState::ContainerExited) => "Exited",
+        _ => "Unknown",
+    }
+}
+
+/// `registry/name:tag` as `(registry/name, tag)`, the tag is after the last `:` of the last
+/// path segment.
+fn split_tag(reference: &str) -> (&str, &str) {
+    let name_start = reference.rfind('/').map_or(0, |it| it + 1);
+    match reference[name_start..].rfind(':') {
+        Some(it) => (
+            &reference[..name_start + it],
+            &reference[name_start + it + 1..],
+        ),
+        None => (reference, "<none>"),
+    }
+}
+
+/// A size in decimal units with three significant digits, such as `1.05MB`.
+pub fn human_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "kB", "MB", "GB", "TB", "PB"];
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit + 1 < UNITS.len() {
+        size /= 1000.0;
+        unit += 1;
+    }
+    let digits = if size >= 100.0 {
+        0
+    } else if size >= 10.0 {
+        1
+    } else {
+        2
+    };
+    let number = format!("{:.*}", digits, size);
+    let number = if number.contains('.') {
+        number.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &number
+    };
+    format!("{}{}", number, UNITS[unit])
+}
+
+/// How long ago the nanosecond timestamp `created_at` was, such as `5 minutes ago`.
+pub fn ago(created_at: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as i64;
+    let seconds = (now - created_at).max(0) / 1_000_000_000;
+    let (count, unit) = match seconds {
+        0 => return "Less than a second ago".to_string(),
+        it if it < 60 => (it, "second"),
+        it if it < 3_600 => (it / 60, "minute"),
+        it if it < 86_400 => (it / 3_600, "hour"),
+        it => (it / 86_400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
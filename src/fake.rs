@@ -0,0 +1,695 @@
+//! In-memory CRI runtime built on the generated traits.
+//!
+//! `FakeRuntime` keeps pod sandboxes, containers and images in memory and follows their CRI
+//! lifecycle without running anything, which makes it a kubelet-side test double. Both
+//! versions share the v1 implementation: v1alpha2 messages are wire compatible with v1, so
+//! requests and responses are transcoded through their protobuf encoding.
+
+// handlers return `tonic::Status` as the generated traits do
+#![allow(clippy::result_large_err)]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use auto_impl_trait::auto_impl;
+use prost::Message;
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status};
+
+use crate::transcode::transcode;
+
+/// Directory reported as the image filesystem.
+const IMAGE_FS: &str = "/var/lib/fake-cri/images";
+/// Size reported for every pulled image.
+const IMAGE_SIZE: u64 = 1 << 20;
+
+#[auto_impl(runtime, image, context, server, health)]
+#[derive(Clone, Default)]
+pub struct FakeRuntime {
+    state: Arc<Mutex<State>>,
+}
+
+pub type GetContainerEventsStream = tokio_stream::wrappers::UnboundedReceiverStream<Result<v1::ContainerEventResponse, Status>>;
+
+#[derive(Default)]
+struct State {
+    sandboxes: HashMap<String, v1::PodSandboxStatus>,
+    containers: HashMap<String, Container>,
+    images: HashMap<String, v1::Image>,
+    runtime_config: Option<v1::RuntimeConfig>,
+    subscribers: Vec<mpsc::UnboundedSender<Result<v1::ContainerEventResponse, Status>>>,
+    last_id: u64,
+}
+
+struct Container {
+    pod_sandbox_id: String,
+    status: v1::ContainerStatus,
+}
+
+impl State {
+    /// A new 64 hex digit id, as other runtimes use.
+    fn next_id(&mut self) -> String {
+        self.last_id += 1;
+        format!("{:064x}", self.last_id)
+    }
+
+    fn sandbox(&self, id: &str) -> Result<&v1::PodSandboxStatus, Status> {
+        self.sandboxes
+            .get(id)
+            .ok_or_else(|| Status::not_found(format!("pod sandbox {} not found", id)))
+    }
+
+    fn container(&self, id: &str) -> Result<&Container, Status> {
+        self.containers
+            .get(id)
+            .ok_or_else(|| Status::not_found(format!("container {} not found", id)))
+    }
+
+    fn container_mut(&mut self, id: &str) -> Result<&mut Container, Status> {
+        self.containers
+            .get_mut(id)
+            .ok_or_else(|| Status::not_found(format!("container {} not found", id)))
+    }
+
+    fn running(&self, id: &str) -> Result<&Container, Status> {
+        let container = self.container(id)?;
+        if container.status.state != v1::ContainerState::ContainerRunning as i32 {
+            return Err(Status::failed_precondition(format!("container {} is not running", id)));
+        }
+        Ok(container)
+    }
+
+    /// The image `spec` refers to by id, tag or digest.
+    fn image(&self, spec: &v1::ImageSpec) -> Option<&v1::Image> {
+        let name = normalize(&spec.image);
+        self.images.values().find(|it| {
+            it.id == spec.image || it.repo_tags.contains(&name) || it.repo_digests.contains(&spec.image)
+        })
+    }
+
+    fn emit(&mut self, container_id: &str, event: v1::ContainerEventType) {
+        let pod_sandbox_metadata = self
+            .containers
+            .get(container_id)
+            .and_then(|it| self.sandboxes.get(&it.pod_sandbox_id))
+            .and_then(|it| it.metadata.clone());
+        let event = v1::ContainerEventResponse {
+            container_id: container_id.to_string(),
+            container_event_type: event as i32,
+            created_at: now(),
+            pod_sandbox_metadata,
+        };
+        self.subscribers.retain(|it| it.send(Ok(event.clone())).is_ok());
+    }
+
+    /// Stops the container if it is running, returns whether it was.
+    fn stop(&mut self, id: &str) -> Result<bool, Status> {
+        let container = self.container_mut(id)?;
+        if container.status.state == v1::ContainerState::ContainerExited as i32 {
+            return Ok(false);
+        }
+        let status = &mut container.status;
+        status.state = v1::ContainerState::ContainerExited as i32;
+        status.finished_at = now();
+        status.exit_code = 0;
+        status.reason = "Completed".to_string();
+        self.emit(id, v1::ContainerEventType::ContainerStoppedEvent);
+        Ok(true)
+    }
+
+    fn remove(&mut self, id: &str) {
+        if self.containers.contains_key(id) {
+            self.emit(id, v1::ContainerEventType::ContainerDeletedEvent);
+            self.containers.remove(id);
+        }
+    }
+
+    /// Ids of the containers of a pod sandbox.
+    fn containers_of(&self, pod_sandbox_id: &str) -> Vec<String> {
+        self.containers
+            .iter()
+            .filter(|(_, it)| it.pod_sandbox_id == pod_sandbox_id)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+/// Nanoseconds since the epoch, the unit of CRI timestamps.
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |it| it.as_nanos() as i64)
+}
+
+/// `busybox` to `docker.io/library/busybox:latest`, as kubelet refers to images.
+fn normalize(image: &str) -> String {
+    let (name, tagged) = match image.rsplit_once('/') {
+        Some((_, last)) => (image.to_string(), last.contains(':') || last.contains('@')),
+        None => (image.to_string(), image.contains(':') || image.contains('@')),
+    };
+    let name = match name.split_once('/') {
+        Some((registry, _)) if registry.contains('.') || registry.contains(':') || registry == "localhost" => name,
+        Some(_) => format!("docker.io/{}", name),
+        None => format!("docker.io/library/{}", name),
+    };
+    if tagged {
+        name
+    } else {
+        format!("{}:latest", name)
+    }
+}
+
+/// Whether every `selector` label is set to the same value in `labels`.
+fn selects(selector: &HashMap<String, String>, labels: &HashMap<String, String>) -> bool {
+    selector.iter().all(|(key, value)| labels.get(key) == Some(value))
+}
+
+/// Inherent methods the generated impls forward to, generic over the message types so they
+/// serve both versions, each handled by the `v1_<method>` of the same name.
+macro_rules! versioned {
+    ($($method:ident => $v1:ident,)*) => {
+        impl FakeRuntime {
+            $(
+                pub async fn $method<Req, Resp>(&self, request: Request<Req>) -> Result<Response<Resp>, Status>
+                where
+                    Req: Message,
+                    Resp: Message + Default,
+                {
+                    let response = self.$v1(transcode(request.get_ref())?)?;
+                    Ok(Response::new(transcode(&response)?))
+                }
+            )*
+        }
+    };
+}
+
+versioned! {
+    run_pod_sandbox => v1_run_pod_sandbox,
+    stop_pod_sandbox => v1_stop_pod_sandbox,
+    remove_pod_sandbox => v1_remove_pod_sandbox,
+    pod_sandbox_status => v1_pod_sandbox_status,
+    list_pod_sandbox => v1_list_pod_sandbox,
+    create_container => v1_create_container,
+    start_container => v1_start_container,
+    stop_container => v1_stop_container,
+    remove_container => v1_remove_container,
+    list_containers => v1_list_containers,
+    container_status => v1_container_status,
+    update_container_resources => v1_update_container_resources,
+    reopen_container_log => v1_reopen_container_log,
+    exec_sync => v1_exec_sync,
+    exec => v1_exec,
+    attach => v1_attach,
+    port_forward => v1_port_forward,
+    container_stats => v1_container_stats,
+    list_container_stats => v1_list_container_stats,
+    pod_sandbox_stats => v1_pod_sandbox_stats,
+    list_pod_sandbox_stats => v1_list_pod_sandbox_stats,
+    update_runtime_config => v1_update_runtime_config,
+    status => v1_status,
+    checkpoint_container => v1_checkpoint_container,
+    list_images => v1_list_images,
+    image_status => v1_image_status,
+    pull_image => v1_pull_image,
+    remove_image => v1_remove_image,
+    image_fs_info => v1_image_fs_info,
+}
+
+impl FakeRuntime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|it| it.into_inner())
+    }
+
+    /// Reports the CRI version the call came in on.
+    pub async fn version<Req, Resp>(&self, request: Request<Req>) -> Result<Response<Resp>, Status>
+    where
+        Req: Message,
+        Resp: Message + Default,
+    {
+        let version = CriContext::get(&request).map_or(CriVersion::V1, |it| it.version);
+        let response = v1::VersionResponse {
+            version: "0.1.0".to_string(),
+            runtime_name: "fake".to_string(),
+            runtime_version: env!("CARGO_PKG_VERSION").to_string(),
+            runtime_api_version: version.to_string(),
+        };
+        Ok(Response::new(transcode(&response)?))
+    }
+
+    /// Streams the lifecycle events of containers from now on.
+    pub async fn get_container_events(&self, _request: Request<v1::GetEventsRequest>) -> Result<Response<GetContainerEventsStream>, Status> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.state().subscribers.push(tx);
+        Ok(Response::new(tokio_stream::wrappers::UnboundedReceiverStream::new(rx)))
+    }
+
+    fn v1_run_pod_sandbox(&self, request: v1::RunPodSandboxRequest) -> Result<v1::RunPodSandboxResponse, Status> {
+        let config = request
+            .config
+            .ok_or_else(|| Status::invalid_argument("config is required"))?;
+        let mut state = self.state();
+        let id = state.next_id();
+        let ip = format!("10.88.{}.{}", state.last_id / 254 % 256, state.last_id % 254 + 1);
+        let status = v1::PodSandboxStatus {
+            id: id.clone(),
+            metadata: config.metadata,
+            state: v1::PodSandboxState::SandboxReady as i32,
+            created_at: now(),
+            network: Some(v1::PodSandboxNetworkStatus {
+                ip,
+                additional_ips: Vec::new(),
+            }),
+            linux: None,
+            labels: config.labels,
+            annotations: config.annotations,
+            runtime_handler: request.runtime_handler,
+        };
+        state.sandboxes.insert(id.clone(), status);
+        Ok(v1::RunPodSandboxResponse { pod_sandbox_id: id })
+    }
+
+    /// Stopping a missing or stopped pod sandbox succeeds, its containers are stopped with it.
+    fn v1_stop_pod_sandbox(&self, request: v1::StopPodSandboxRequest) -> Result<v1::StopPodSandboxResponse, Status> {
+        let mut state = self.state();
+        for id in state.containers_of(&request.pod_sandbox_id) {
+            state.stop(&id)?;
+        }
+        if let Some(sandbox) = state.sandboxes.get_mut(&request.pod_sandbox_id) {
+            sandbox.state = v1::PodSandboxState::SandboxNotready as i32;
+        }
+        Ok(v1::StopPodSandboxResponse {})
+    }
+
+    /// Removing a missing pod sandbox succeeds, its containers are removed with it.
+    fn v1_remove_pod_sandbox(&self, request: v1::RemovePodSandboxRequest) -> Result<v1::RemovePodSandboxResponse, Status> {
+        let mut state = self.state();
+        for id in state.containers_of(&request.pod_sandbox_id) {
+            state.stop(&id)?;
+            state.remove(&id);
+        }
+        state.sandboxes.remove(&request.pod_sandbox_id);
+        Ok(v1::RemovePodSandboxResponse {})
+    }
+
+    fn v1_pod_sandbox_status(&self, request: v1::PodSandboxStatusRequest) -> Result<v1::PodSandboxStatusResponse, Status> {
+        let state = self.state();
+        let status = state.sandbox(&request.pod_sandbox_id)?.clone();
+        Ok(v1::PodSandboxStatusResponse {
+            status: Some(status),
+            info: HashMap::new(),
+        })
+    }
+
+    fn v1_list_pod_sandbox(&self, request: v1::ListPodSandboxRequest) -> Result<v1::ListPodSandboxResponse, Status> {
+        let filter = request.filter.unwrap_or_default();
+        let state = self.state();
+        let mut items = state
+            .sandboxes
+            .values()
+            .filter(|it| filter.id.is_empty() || it.id == filter.id)
+            .filter(|it| match &filter.state {
+                Some(state) => it.state == state.state,
+                None => true,
+            })
+            .filter(|it| selects(&filter.label_selector, &it.labels))
+            .map(|it| v1::PodSandbox {
+                id: it.id.clone(),
+                metadata: it.metadata.clone(),
+                state: it.state,
+                created_at: it.created_at,
+                labels: it.labels.clone(),
+                annotations: it.annotations.clone(),
+                runtime_handler: it.runtime_handler.clone(),
+            })
+            .collect::<Vec<_>>();
+        items.sort_by_key(|it| it.created_at);
+        Ok(v1::ListPodSandboxResponse { items })
+    }
+
+    fn v1_create_container(&self, request: v1::CreateContainerRequest) -> Result<v1::CreateContainerResponse, Status> {
+        let config = request
+            .config
+            .ok_or_else(|| Status::invalid_argument("config is required"))?;
+        let mut state = self.state();
+        let sandbox = state.sandbox(&request.pod_sandbox_id)?;
+        if sandbox.state != v1::PodSandboxState::SandboxReady as i32 {
+            return Err(Status::failed_precondition(format!("pod sandbox {} is not ready", sandbox.id)));
+        }
+        let log_directory = request.sandbox_config.map(|it| it.log_directory).unwrap_or_default();
+        let spec = config.image.unwrap_or_default();
+        let image_ref = state
+            .image(&spec)
+            .map(|it| it.id.clone())
+            .ok_or_else(|| Status::not_found(format!("image {} not found", spec.image)))?;
+
+        let id = state.next_id();
+        let status = v1::ContainerStatus {
+            id: id.clone(),
+            metadata: config.metadata,
+            state: v1::ContainerState::ContainerCreated as i32,
+            created_at: now(),
+            image: Some(spec),
+            image_ref,
+            labels: config.labels,
+            annotations: config.annotations,
+            mounts: config.mounts,
+            log_path: match (log_directory.as_str(), config.log_path.as_str()) {
+                ("", path) | (path, "") => path.to_string(),
+                (directory, path) => format!("{}/{}", directory.trim_end_matches('/'), path),
+            },
+            resources: Some(v1::ContainerResources {
+                linux: config.linux.and_then(|it| it.resources),
+                windows: config.windows.and_then(|it| it.resources),
+            }),
+            ..Default::default()
+        };
+        state.containers.insert(
+            id.clone(),
+            Container {
+                pod_sandbox_id: request.pod_sandbox_id,
+                status,
+            },
+        );
+        state.emit(&id, v1::ContainerEventType::ContainerCreatedEvent);
+        Ok(v1::CreateContainerResponse { container_id: id })
+    }
+
+    fn v1_start_container(&self, request: v1::StartContainerRequest) -> Result<v1::StartContainerResponse, Status> {
+        let mut state = self.state();
+        let container = state.container_mut(&request.container_id)?;
+        if container.status.state != v1::ContainerState::ContainerCreated as i32 {
+            return Err(Status::failed_precondition(format!(
+                "container {} is not in the created state",
+                request.container_id
+            )));
+        }
+        container.status.state = v1::ContainerState::ContainerRunning as i32;
+        container.status.started_at = now();
+        state.emit(&request.container_id, v1::ContainerEventType::ContainerStartedEvent);
+        Ok(v1::StartContainerResponse {})
+    }
+
+    /// Stopping an exited container succeeds.
+    fn v1_stop_container(&self, request: v1::StopContainerRequest) -> Result<v1::StopContainerResponse, Status> {
+        self.state().stop(&request.container_id)?;
+        Ok(v1::StopContainerResponse {})
+    }
+
+    /// Removing a missing container succeeds, a running one is stopped first.
+    fn v1_remove_container(&self, request: v1::RemoveContainerRequest) -> Result<v1::RemoveContainerResponse, Status> {
+        let mut state = self.state();
+        if state.containers.contains_key(&request.container_id) {
+            state.stop(&request.container_id)?;
+            state.remove(&request.container_id);
+        }
+        Ok(v1::RemoveContainerResponse {})
+    }
+
+    fn v1_list_containers(&self, request: v1::ListContainersRequest) -> Result<v1::ListContainersResponse, Status> {
+        let filter = request.filter.unwrap_or_default();
+        let state = self.state();
+        let mut containers = state
+            .containers
+            .values()
+            .filter(|it| filter.id.is_empty() || it.status.id == filter.id)
+            .filter(|it| filter.pod_sandbox_id.is_empty() || it.pod_sandbox_id == filter.pod_sandbox_id)
+            .filter(|it| match &filter.state {
+                Some(state) => it.status.state == state.state,
+                None => true,
+            })
+            .filter(|it| selects(&filter.label_selector, &it.status.labels))
+            .map(|it| v1::Container {
+                id: it.status.id.clone(),
+                pod_sandbox_id: it.pod_sandbox_id.clone(),
+                metadata: it.status.metadata.clone(),
+                image: it.status.image.clone(),
+                image_ref: it.status.image_ref.clone(),
+                state: it.status.state,
+                created_at: it.status.created_at,
+                labels: it.status.labels.clone(),
+                annotations: it.status.annotations.clone(),
+            })
+            .collect::<Vec<_>>();
+        containers.sort_by_key(|it| it.created_at);
+        Ok(v1::ListContainersResponse { containers })
+    }
+
+    fn v1_container_status(&self, request: v1::ContainerStatusRequest) -> Result<v1::ContainerStatusResponse, Status> {
+        let state = self.state();
+        let status = state.container(&request.container_id)?.status.clone();
+        Ok(v1::ContainerStatusResponse {
+            status: Some(status),
+            info: HashMap::new(),
+        })
+    }
+
+    fn v1_update_container_resources(
+        &self,
+        request: v1::UpdateContainerResourcesRequest,
+    ) -> Result<v1::UpdateContainerResourcesResponse, Status> {
+        let mut state = self.state();
+        let container = state.container_mut(&request.container_id)?;
+        container.status.resources = Some(v1::ContainerResources {
+            linux: request.linux,
+            windows: request.windows,
+        });
+        Ok(v1::UpdateContainerResourcesResponse {})
+    }
+
+    fn v1_reopen_container_log(&self, request: v1::ReopenContainerLogRequest) -> Result<v1::ReopenContainerLogResponse, Status> {
+        self.state().running(&request.container_id)?;
+        Ok(v1::ReopenContainerLogResponse {})
+    }
+
+    /// Nothing runs, so every command succeeds without output.
+    fn v1_exec_sync(&self, request: v1::ExecSyncRequest) -> Result<v1::ExecSyncResponse, Status> {
+        self.state().running(&request.container_id)?;
+        Ok(v1::ExecSyncResponse::default())
+    }
+
+    fn v1_exec(&self, request: v1::ExecRequest) -> Result<v1::ExecResponse, Status> {
+        self.state().running(&request.container_id)?;
+        Err(Status::unimplemented("the fake runtime has no streaming server"))
+    }
+
+    fn v1_attach(&self, request: v1::AttachRequest) -> Result<v1::AttachResponse, Status> {
+        self.state().running(&request.container_id)?;
+        Err(Status::unimplemented("the fake runtime has no streaming server"))
+    }
+
+    fn v1_port_forward(&self, request: v1::PortForwardRequest) -> Result<v1::PortForwardResponse, Status> {
+        self.state().sandbox(&request.pod_sandbox_id)?;
+        Err(Status::unimplemented("the fake runtime has no streaming server"))
+    }
+
+    fn v1_container_stats(&self, request: v1::ContainerStatsRequest) -> Result<v1::ContainerStatsResponse, Status> {
+        let state = self.state();
+        let container = state.container(&request.container_id)?;
+        Ok(v1::ContainerStatsResponse {
+            stats: Some(container_stats(container)),
+        })
+    }
+
+    fn v1_list_container_stats(&self, request: v1::ListContainerStatsRequest) -> Result<v1::ListContainerStatsResponse, Status> {
+        let filter = request.filter.unwrap_or_default();
+        let state = self.state();
+        let stats = state
+            .containers
+            .values()
+            .filter(|it| filter.id.is_empty() || it.status.id == filter.id)
+            .filter(|it| filter.pod_sandbox_id.is_empty() || it.pod_sandbox_id == filter.pod_sandbox_id)
+            .filter(|it| selects(&filter.label_selector, &it.status.labels))
+            .map(container_stats)
+            .collect();
+        Ok(v1::ListContainerStatsResponse { stats })
+    }
+
+    fn v1_pod_sandbox_stats(&self, request: v1::PodSandboxStatsRequest) -> Result<v1::PodSandboxStatsResponse, Status> {
+        let state = self.state();
+        let sandbox = state.sandbox(&request.pod_sandbox_id)?;
+        Ok(v1::PodSandboxStatsResponse {
+            stats: Some(pod_sandbox_stats(&state, sandbox)),
+        })
+    }
+
+    fn v1_list_pod_sandbox_stats(&self, request: v1::ListPodSandboxStatsRequest) -> Result<v1::ListPodSandboxStatsResponse, Status> {
+        let filter = request.filter.unwrap_or_default();
+        let state = self.state();
+        let stats = state
+            .sandboxes
+            .values()
+            .filter(|it| filter.id.is_empty() || it.id == filter.id)
+            .filter(|it| selects(&filter.label_selector, &it.labels))
+            .map(|it| pod_sandbox_stats(&state, it))
+            .collect();
+        Ok(v1::ListPodSandboxStatsResponse { stats })
+    }
+
+    fn v1_update_runtime_config(&self, request: v1::UpdateRuntimeConfigRequest) -> Result<v1::UpdateRuntimeConfigResponse, Status> {
+        self.state().runtime_config = request.runtime_config;
+        Ok(v1::UpdateRuntimeConfigResponse {})
+    }
+
+    /// The runtime and its network are always ready.
+    fn v1_status(&self, request: v1::StatusRequest) -> Result<v1::StatusResponse, Status> {
+        let condition = |kind: &str| v1::RuntimeCondition {
+            r#type: kind.to_string(),
+            status: true,
+            reason: String::new(),
+            message: String::new(),
+        };
+        let mut info = HashMap::new();
+        if request.verbose {
+            let pod_cidr = self
+                .state()
+                .runtime_config
+                .as_ref()
+                .and_then(|it| it.network_config.as_ref())
+                .map(|it| it.pod_cidr.clone())
+                .unwrap_or_default();
+            info.insert("config".to_string(), format!("{{\"podCIDR\":\"{}\"}}", pod_cidr));
+        }
+        Ok(v1::StatusResponse {
+            status: Some(v1::RuntimeStatus {
+                conditions: vec![condition("RuntimeReady"), condition("NetworkReady")],
+            }),
+            info,
+        })
+    }
+
+    fn v1_checkpoint_container(&self, request: v1::CheckpointContainerRequest) -> Result<v1::CheckpointContainerResponse, Status> {
+        self.state().container(&request.container_id)?;
+        Ok(v1::CheckpointContainerResponse {})
+    }
+
+    fn v1_list_images(&self, request: v1::ListImagesRequest) -> Result<v1::ListImagesResponse, Status> {
+        let state = self.state();
+        let mut images = match request.filter.and_then(|it| it.image) {
+            Some(spec) if !spec.image.is_empty() => state.image(&spec).cloned().into_iter().collect(),
+            _ => state.images.values().cloned().collect::<Vec<_>>(),
+        };
+        images.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(v1::ListImagesResponse { images })
+    }
+
+    /// A missing image is not an error, the response has no image.
+    fn v1_image_status(&self, request: v1::ImageStatusRequest) -> Result<v1::ImageStatusResponse, Status> {
+        let spec = request.image.unwrap_or_default();
+        Ok(v1::ImageStatusResponse {
+            image: self.state().image(&spec).cloned(),
+            info: HashMap::new(),
+        })
+    }
+
+    /// Pulling records the image, pulling it again returns the same reference.
+    fn v1_pull_image(&self, request: v1::PullImageRequest) -> Result<v1::PullImageResponse, Status> {
+        let spec = request
+            .image
+            .filter(|it| !it.image.is_empty())
+            .ok_or_else(|| Status::invalid_argument("image is required"))?;
+        let mut state = self.state();
+        if let Some(image) = state.image(&spec) {
+            return Ok(v1::PullImageResponse {
+                image_ref: image.id.clone(),
+            });
+        }
+
+        let name = normalize(&spec.image);
+        let digest = state.next_id();
+        let id = format!("sha256:{}", digest);
+        let repository = name.rsplit_once([':', '@']).map_or(name.as_str(), |(it, _)| it);
+        let image = v1::Image {
+            id: id.clone(),
+            repo_digests: vec![format!("{}@sha256:{}", repository, digest)],
+            repo_tags: if name.contains('@') { Vec::new() } else { vec![name.clone()] },
+            size: IMAGE_SIZE,
+            spec: Some(v1::ImageSpec {
+                image: name,
+                annotations: spec.annotations,
+            }),
+            ..Default::default()
+        };
+        state.images.insert(id.clone(), image);
+        Ok(v1::PullImageResponse { image_ref: id })
+    }
+
+    /// Removing a missing image succeeds.
+    fn v1_remove_image(&self, request: v1::RemoveImageRequest) -> Result<v1::RemoveImageResponse, Status> {
+        let spec = request.image.unwrap_or_default();
+        let mut state = self.state();
+        if let Some(id) = state.image(&spec).map(|it| it.id.clone()) {
+            state.images.remove(&id);
+        }
+        Ok(v1::RemoveImageResponse {})
+    }
+
+    fn v1_image_fs_info(&self, _request: v1::ImageFsInfoRequest) -> Result<v1::ImageFsInfoResponse, Status> {
+        let state = self.state();
+        Ok(v1::ImageFsInfoResponse {
+            image_filesystems: vec![v1::FilesystemUsage {
+                timestamp: now(),
+                fs_id: Some(v1::FilesystemIdentifier {
+                    mountpoint: IMAGE_FS.to_string(),
+                }),
+                used_bytes: Some(v1::UInt64Value {
+                    value: state.images.values().map(|it| it.size).sum(),
+                }),
+                inodes_used: Some(v1::UInt64Value {
+                    value: state.images.len() as u64,
+                }),
+            }],
+        })
+    }
+}
+
+/// Stats of a container, which uses no resources.
+fn container_stats(container: &Container) -> v1::ContainerStats {
+    let timestamp = now();
+    let zero = || Some(v1::UInt64Value { value: 0 });
+    v1::ContainerStats {
+        attributes: Some(v1::ContainerAttributes {
+            id: container.status.id.clone(),
+            metadata: container.status.metadata.clone(),
+            labels: container.status.labels.clone(),
+            annotations: container.status.annotations.clone(),
+        }),
+        cpu: Some(v1::CpuUsage {
+            timestamp,
+            usage_core_nano_seconds: zero(),
+            usage_nano_cores: zero(),
+        }),
+        memory: Some(v1::MemoryUsage {
+            timestamp,
+            working_set_bytes: zero(),
+            ..Default::default()
+        }),
+        writable_layer: None,
+    }
+}
+
+fn pod_sandbox_stats(state: &State, sandbox: &v1::PodSandboxStatus) -> v1::PodSandboxStats {
+    let containers = state
+        .containers
+        .values()
+        .filter(|it| it.pod_sandbox_id == sandbox.id)
+        .map(container_stats)
+        .collect();
+    v1::PodSandboxStats {
+        attributes: Some(v1::PodSandboxAttributes {
+            id: sandbox.id.clone(),
+            metadata: sandbox.metadata.clone(),
+            labels: sandbox.labels.clone(),
+            annotations: sandbox.annotations.clone(),
+        }),
+        linux: Some(v1::LinuxPodSandboxStats {
+            containers,
+            ..Default::default()
+        }),
+        windows: None,
+    }
+}
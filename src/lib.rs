@@ -1,295 +1,113 @@
-// #[cfg(all(feature = "v1", feature = "v1alpha2"))]
-// compile_error!("features `v1` and `v1alpha2` are mutually exclusive");
+//! `#[auto_impl(...)]`, the attribute over the `Config` of `auto_impl_trait_engine`.
 
-use proc_macro::{TokenStream};
-
-use proc_macro2::Span;
-use quote::{quote, ToTokens};
-use syn::{Ident, TraitItem, Block, Token, Type, TypePath, AngleBracketedGenericArguments, GenericArgument, ConstParam, ItemMod};
-use syn::visit_mut::{visit_angle_bracketed_generic_arguments_mut, visit_const_param_mut, visit_item_mod_mut, visit_type_path_mut, VisitMut};
-
-const RUNTIME_MOD: &str = "runtime_service_server";
-const RUNTIME_TRAIT: &str = "RuntimeService";
-const IMAGE_MOD: &str = "image_service_server";
-const IMAGE_TRAIT: &str = "ImageService";
-
-#[inline]
-fn remove_fisrt(segments: &mut syn::punctuated::Punctuated<syn::PathSegment, syn::Token![::]>) {
-    let mut segment: syn::punctuated::Punctuated<_, Token![::]> = syn::punctuated::Punctuated::new();
-    segments.iter().skip(1).for_each(|it| segment.push(it.clone()));
-    segments.clear();
-    segments.extend(segment);
-}
-
-struct SuperRemover;
-
-impl VisitMut for SuperRemover {
-    fn visit_type_path_mut(&mut self, i: &mut TypePath) {
-        visit_type_path_mut(self, i);
-
-        if i.path.segments.len() > 1 {
-            if i.path.segments.first().unwrap().ident == "super" {
-                remove_fisrt(&mut i.path.segments);
-            }
-        }
-    }
-
-    fn visit_const_param_mut(&mut self, i: &mut ConstParam) {
-        visit_const_param_mut(self, i);
-
-        match &mut i.ty {
-            Type::Path(p) => {
-                self.visit_type_path_mut(&mut p.clone());
-            }
-            _ => { /* NOOP */ }
-        }
-    }
-
-
-    fn visit_angle_bracketed_generic_arguments_mut(&mut self, i: &mut AngleBracketedGenericArguments) {
-        visit_angle_bracketed_generic_arguments_mut(self, i);
-        i.args.iter_mut().for_each(|it| {
-            match it {
-                GenericArgument::Type(t) => {
-                    match t {
-                        Type::Path(p) => {
-                            self.visit_type_path_mut(p);
-                        }
-                        _ => {}
-                    }
-                }
-                GenericArgument::Constraint(_) => {}
-                _ => {}
-            }
-        })
-    }
-}
-
-struct RuntimeRedundantRemover;
-impl VisitMut for RuntimeRedundantRemover {
-    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
-        visit_item_mod_mut(self, i);
-
-        // remove redundant mod
-        if i.ident == IMAGE_MOD {
-            i.vis = syn::Visibility::Inherited;
-        }
-    }
-}
-
-struct ImageRedundantRemover;
-impl VisitMut for ImageRedundantRemover {
-    fn visit_item_mod_mut(&mut self, i: &mut ItemMod) {
-        visit_item_mod_mut(self, i);
-
-        // remove redundant mod
-        if i.ident == RUNTIME_MOD {
-            i.vis = syn::Visibility::Inherited;
-        }
-    }
-}
-
-#[derive(Clone, Copy)]
-enum ServiceType {
-    Runtime,
-    Image,
-}
-
-impl ServiceType {
-    const fn get_mod(&self) -> &str {
-        match self {
-            ServiceType::Runtime => RUNTIME_MOD,
-            ServiceType::Image => IMAGE_MOD,
-        }
-    }
-
-    const fn get_trait(&self) -> &str {
-        match self {
-            ServiceType::Runtime => RUNTIME_TRAIT,
-            ServiceType::Image => IMAGE_TRAIT,
-        }
-    }
-}
-
-impl syn::parse::Parse for ServiceType {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        if input.is_empty() {
-            Err(syn::Error::new(
-                Span::call_site(),
-                "proto type is required(runtime or image)",
-            ))
-        } else {
-            let ident = input.parse::<Ident>()?;
-            if ident == "runtime" {
-                Ok(ServiceType::Runtime)
-            } else if ident == "image" {
-                Ok(ServiceType::Image)
-            } else {
-                Err(syn::Error::new(
-                    Span::call_site(),
-                    "proto type is required(runtime or image)",
-                ))
-            }
-        }
-    }
-}
-
-fn expand(file_content: String, container: Ident, service_type: &ServiceType, struct_ast: &syn::ItemStruct) -> proc_macro2::TokenStream {
-    let mut file = syn::parse_file(&*file_content).expect("parse file faild");
-
-    match &service_type {
-        ServiceType::Runtime => {
-            RuntimeRedundantRemover.visit_file_mut(&mut file);
-        }
-        ServiceType::Image => {
-            ImageRedundantRemover.visit_file_mut(&mut file);
-        }
-    }
-
-    let struct_name = struct_ast.ident.clone();
-    let trait_name = quote::format_ident!("{}", service_type.get_trait());
-    let trait_meta = file.items.iter().find_map::<syn::ItemMod, _>(|item| {
-        if let syn::Item::Mod(m) = item {
-            if service_type.get_mod() == m.ident.to_string().as_str() {
-                Some(m.clone())
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    })
-        .expect(format!("{} is not found", service_type.get_mod()).as_str())
-        .content
-        .expect(format!("{} is empty mod", service_type.get_mod()).as_str());
-    let trait_meta = trait_meta
-        .1
-        .iter()
-        .find_map(|it| {
-            if let syn::Item::Trait(t) = it {
-                if service_type.get_trait() == t.ident.to_string().as_str() {
-                    Some(t)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
-        .expect("find RuntimeService faild");
-
-    let mut trait_items = trait_meta.items.clone();
-    let trait_items = trait_items.iter_mut().filter_map(|it| {
-        match it {
-            TraitItem::Method(m) => {
-                let name = quote::format_ident!("{}", m.sig.ident);
-
-                SuperRemover.visit_trait_item_method_mut(m);
-
-                let args = m.sig.inputs.iter_mut().filter_map(|it| {
-                    match it {
-                        syn::FnArg::Typed(t) => {
-                            match *t.pat {
-                                syn::Pat::Ident(ref mut i) => {
-                                    Some(i.ident.clone())
-                                }
-                                _ => None,
-                            }
-                        }
-                        _ => None
-                    }
-                }).collect::<Vec<_>>();
-
-                if m.sig.asyncness.is_some() {
-                    m.default = Some(syn::parse::<Block>(TokenStream::from(quote! {
-                        {self.#name(#(#args),*).await}
-                    }.to_token_stream())).unwrap());
-                } else {
-                    m.default = Some(syn::parse::<Block>(TokenStream::from(quote! {
-                        {self.#name(#(#args),*)}
-                    }.to_token_stream())).unwrap());
-                }
-
-                Some(quote! {
-                    #m
-                })
-            }
-            TraitItem::Type(t) => {
-                let name = quote::format_ident!("{}", t.ident);
-                Some(quote! {
-                    type #name = super::#name;
-                })
-            }
-            _ => { None /* not used */ }
-        }
-    });
-
-    let trait_alias = quote::format_ident!("{}_{}", service_type.get_trait(), container);
-
-    let use_block = match service_type {
-        ServiceType::Runtime => {
-            quote! {
-                use #container::runtime_service_server::#trait_name as #trait_alias;
-            }
-        }
-        ServiceType::Image => {
-            quote! {
-                use #container::image_service_server::#trait_name as #trait_alias;
-            }
-        }
-    };
-
-    (quote! {
-        pub mod #container {
-            #file
-
-            #[tonic::async_trait]
-            impl super::#trait_alias for super::#struct_name {
-                #(#trait_items)*
-            }
-        }
-        #use_block
-    }).into()
-}
+use auto_impl_trait_engine::{Config, Protos};
+use proc_macro::TokenStream;
 
 ///
 /// This crate is for internal use only.
 ///
+/// Usage: `#[auto_impl(runtime)]`, `#[auto_impl(image)]` or `#[auto_impl(runtime, image)]`,
+/// optionally followed by flags. `client` and `json` also work without a service, e.g.
+/// `#[auto_impl(client, json)]` generates the messages and clients of both versions only:
+///
+/// - `context`: generate `CriContext` and attach one to every request's extensions before
+///   it is forwarded, handlers read it back with `CriContext::get(&request)`.
+/// - `authorize`: reject calls with `PermissionDenied` unless the `SO_PEERCRED` of the unix
+///   socket peer passes the struct's `CriPolicy` (a field of that type, or root only), which
+///   has separate allow-lists for read-only methods such as `list_containers` and mutating ones
+///   such as `remove_pod_sandbox`, or a callback.
+/// - `audit`: append a hash-chained JSON-lines record of every mutating call (timestamp, peer
+///   credentials, method, target ids and outcome) to the struct's `CriAuditLog` field, a size
+///   rotated local file created with mode `0600`. Records are written on the blocking thread
+///   pool of `tokio`, and those that cannot be written are logged as `tracing` errors of the
+///   `cri` target. The calling crate needs `sha2`, `tokio` and `tracing`.
+/// - `read_only = [method, ...]`, `mutating = [method, ...]`: override the built-in
+///   classification used by `authorize`, `audit` and `mirror`, where only status, stats and
+///   list methods are read-only.
+/// - `server`: generate `CriServices`, `into_cri_services`, `serve_unix` and
+///   `serve_unix_with_shutdown` on the struct, the calling crate needs `tokio` (with the
+///   `net`, `sync`, `time` and `macros` features) and `tokio-stream` (with the `net` feature).
+/// - `socket_mode = 0o600`: with `server`, the permissions `serve_unix` gives the socket instead
+///   of `0o660`, owner and group.
+/// - `health`: with `server`, also register `grpc.health.v1.Health` reporting every generated
+///   service as serving, statuses are changed through `CriServices::health`.
+/// - `reflection`: with `server`, also register `grpc.reflection.v1alpha.ServerReflection`
+///   built from the bundled file descriptors, the calling crate needs `prost-types`.
+/// - `layer = Type`: generate a `<Service>Hooks` trait per version (e.g. `v1::RuntimeServiceHooks`)
+///   with no-op `before_<method>`/`after_<method>` callbacks, and run the ones `Type` implements
+///   around every call, once `authorize` and `validate` accepted it. `Type` is taken from a field
+///   of the struct, or created with `Default`.
+/// - `tracing`: run every generated method in a `cri` span carrying the method, service,
+///   version, the targeted pod sandbox, container or image and the result code, the calling
+///   crate needs `tracing`.
+/// - `opentelemetry`: implies `tracing`, parents those spans to the W3C `traceparent` the
+///   caller sent in the request metadata, and generates `CriTraceContext` whose `interceptor`
+///   injects the current span's context into outgoing client calls, as the `CriClient`
+///   methods and the `proxy` forwarding do. The calling crate needs `opentelemetry` and
+///   `tracing-opentelemetry`.
+/// - `metrics`: count the requests, errors by `tonic::Code`, in-flight calls and latency of
+///   every generated method in `CRI_METRICS`, which renders them in the Prometheus text format
+///   and serves them over HTTP with `CRI_METRICS.serve_tcp(addr)` or
+///   `CRI_METRICS.serve_unix(path)`. The calling crate needs `tokio` (with the `io-util` feature).
+/// - `log`: log every request and response as `tracing` debug events of the `cri` target, with
+///   the fields of `CriRedact` hidden, the calling crate needs `tracing`.
+/// - `redact = [field, ...]`: with `log`, the fields to hide instead of the credentials of
+///   `AuthConfig` and the values of `envs`, whose keys are kept. An entry is a field name of
+///   any message or `Message.field`, and must name an existing field of a CRI message.
+/// - `validate`: reject requests missing fields handlers rely on with `InvalidArgument` naming
+///   the field path, e.g. `config.metadata is required`. The built-in rules require the configs,
+///   metadata and images of the create, run and image requests, and non-empty ids and commands.
+///   `validate(required = [Message.field, ...], non_empty = [Message.field, ...])` adds rules.
+/// - `limits(method(timeout = "30s", concurrency = 2), ...)`: fail calls of `method` that run
+///   longer than `timeout` (`ms`, `s`, `m` or `h`) with `DeadlineExceeded`, and calls made while
+///   `concurrency` others are running with `ResourceExhausted`. The limits hold for both
+///   versions of the method together, timeouts need the `time` feature of `tokio`. Only unary
+///   methods can be limited, not server-streaming ones such as `get_container_events`.
+/// - `client`: generate `v1::CriClient` and `v1alpha2::CriClient`, clients of both services
+///   built on the bundled `runtime_service_client` and `image_service_client` and sharing the
+///   message types of the impls, connected with `connect(uri)` or `connect_unix(path)`. The
+///   calling crate needs `tokio` (with the `net` feature).
+/// - `proxy`: forward every call to the upstream CRI endpoint held in the struct's
+///   `CriUpstream` field, connected with `CriUpstream::connect_unix(path)`. The calls go through
+///   a `<Service>Proxy` trait per version (e.g. `v1::ImageServiceProxy`) the struct implements,
+///   whose methods forward by default, so a shim only writes the methods it changes. Implies
+///   `client`, and server streams are `CriProxyStream`s instead of the struct's stream types.
+/// - `bridge = v1` or `bridge = v1alpha2`: implies `proxy`, and forwards the calls of both
+///   versions to the upstream's services of the given version, converting the requests and
+///   responses of the other version. Methods the given version lacks, such as
+///   `checkpoint_container` with `bridge = v1alpha2`, fail with `Unimplemented`. The calling
+///   crate needs `tokio-stream` if a bridged method is a server stream.
+/// - `mock`: generate `Mock<Struct>`, implementing the same traits for tests. It records every
+///   call, answers from per-method scripts set through handles such as `v1_stop_container()`
+///   (queued responses, a closure, or `Default`), and asserts with
+///   `expect_called("stop_container").times(1)`. The calling crate needs `tokio-stream`.
+/// - `mirror`: generate `CriMirror<P, S>`, implementing the same traits by serving every call
+///   with the primary `P` and sending copies of the read-only unary calls to the shadow `S` in
+///   the background. Field-level differences between their answers, listed by the `CriDiff`
+///   impls of the messages, are logged as `tracing` warnings of the `cri` target and the
+///   shadow's answer is dropped. The calling crate needs `tokio` and `tracing`.
+/// - `record`: write every call with its version, start, duration, encoded request and response
+///   and result code to the struct's `CriRecorder` field, a file of length-delimited protobuf
+///   `CriRecord`s. `CriRecording::open(path)` reads one back, and `replay(&target)` re-issues
+///   its calls against any implementation of the generated traits, returning the
+///   `CriDivergence`s where the code or response differs. Requests are not redacted, so
+///   recordings contain the registry credentials of `PullImageRequest.auth` and the container
+///   `envs`; the file is created with mode `0600`. Records that cannot be written are logged
+///   as `tracing` warnings of the `cri` target, the calling crate needs `tracing`.
+/// - `chaos`: generate `CriChaos<T>`, implementing the same traits by forwarding to `T` with
+///   the faults of its `CriFaults` injected: latency, error codes, truncated response lists and
+///   dropped server streams, per method and version, by probability or by request field values
+///   such as `match=labels.io.kubernetes.pod.name=web-0`. Faults are changed at runtime with
+///   `CriFaults::add` or over the HTTP control API of `CriFaults::serve_unix(path)`. The calling
+///   crate needs `tokio` (with the `time` and `io-util` features).
+/// - `json`: generate `CriJson`, implemented by every message, converting it to a `CriValue`
+///   that renders as JSON (`to_json`) or YAML (`to_yaml`) with the field names and enumeration
+///   value names of the protobuf JSON mapping.
+///
 #[proc_macro_attribute]
 pub fn auto_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let service_type = syn::parse_macro_input!(attr as ServiceType);
-    let struct_ast = syn::parse_macro_input!(item as syn::ItemStruct);
-
-
-    let file_content_v1 = std::fs::read_to_string("./proto/runtime.v1.rs").expect("read ./proto/runtime.v1.rs faild");
-    let file_content_v1alpha2 = std::fs::read_to_string("./proto/runtime.v1alpha2.rs").expect("read ./proto/runtime.v1alpha2.rs faild");
-
-    let container_v1 = quote::format_ident!("v1");
-    let v1 = expand(file_content_v1, container_v1, &service_type, &struct_ast);
-    let container_v1alpha2 = quote::format_ident!("v1alpha2");
-    let v1alpha2 = expand(file_content_v1alpha2, container_v1alpha2, &service_type, &struct_ast);
-
-
-    let doc = match &service_type {
-        ServiceType::Runtime => {
-            quote! {
-                #[doc = "Generated by `#[derive(auto_impl(runtime))]`"]
-            }
-        }
-        ServiceType::Image => {
-            quote! {
-                #[doc = "Generated by `#[derive(auto_impl(image))]`"]
-            }
-        }
-    };
-
-    (quote! {
-        #doc
-        #struct_ast
-
-        #[doc = "Version v1 impls generated by auto_impl"]
-        #v1
-        #[doc = "Version v1alpha2 Impls generated by auto_impl"]
-        #v1alpha2
-    }).into()
+    Config::from_attribute(attr.into(), item.into(), Protos::bundled())
+        .and_then(|config| config.expand())
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
@@ -1,12 +1,20 @@
-use tonic::Request;
-use auto_impl_trait::auto_impl;
+//! Serves the in-memory `FakeRuntime` on a unix socket, `/tmp/fake-cri.sock` unless another
+//! path is given, e.g. for `crictl --runtime-endpoint unix:///tmp/fake-cri.sock`.
 
-#[auto_impl(image)]
-pub struct Square {
-    side: i32,
+#[cfg(feature = "fake")]
+mod fake;
+#[cfg(feature = "fake")]
+mod transcode;
+
+#[cfg(feature = "fake")]
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let path = std::env::args().nth(1).unwrap_or_else(|| "/tmp/fake-cri.sock".to_string());
+    println!("serving the fake CRI runtime on {}", path);
+    std::sync::Arc::new(fake::FakeRuntime::new()).serve_unix(path).await
 }
 
+#[cfg(not(feature = "fake"))]
 fn main() {
-    let square = Square { side: 5 };
-    square.list_images(Request::new(v1::ListImagesRequest::default()));
+    eprintln!("the fake CRI runtime is built with `--features fake`");
 }
@@ -0,0 +1,14 @@
+//! Conversion between the messages of the two CRI versions, shared by the `cri` binary and the
+//! fake runtime, which serve v1alpha2 through their v1 code.
+
+// errors are `tonic::Status` as the generated traits return
+#![allow(clippy::result_large_err)]
+
+use prost::Message;
+use tonic::Status;
+
+/// Re-encodes a message of one CRI version as the same message of another, as the `cri_bridge`
+/// of a `bridge` proxy does: both versions share their field numbers.
+pub(crate) fn transcode<A: Message, B: Message + Default>(message: &A) -> Result<B, Status> {
+    B::decode(message.encode_to_vec().as_slice()).map_err(|e| Status::internal(format!("transcoding failed: {}", e)))
+}
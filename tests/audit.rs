@@ -0,0 +1,174 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use auto_impl_trait::auto_impl;
+use sha2::Digest;
+use tonic::Request;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+
+#[macro_use]
+mod common;
+
+/// Fails `remove_image` with a message the log has to escape.
+async fn answer<Req>(method: &str, _request: Request<Req>) -> Result<(), tonic::Status> {
+    match method {
+        "remove_image" => Err(tonic::Status::not_found("image \"busybox\" gone")),
+        _ => Ok(()),
+    }
+}
+
+fixture! {
+    answer = answer;
+    #[auto_impl(image, audit)]
+    pub struct Audited {
+        log: CriAuditLog,
+    }
+}
+
+async fn pull(audited: &Audited, image: &str) {
+    let request = v1::PullImageRequest {
+        image: Some(v1::ImageSpec {
+            image: image.to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    ImageService_v1::pull_image(audited, Request::new(request)).await.unwrap();
+}
+
+fn lines(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path).unwrap().lines().map(str::to_string).collect()
+}
+
+fn field<'a>(line: &'a str, name: &str) -> &'a str {
+    let start = line.find(&format!("\"{}\":\"", name)).unwrap() + name.len() + 4;
+    &line[start..start + line[start..].find('"').unwrap()]
+}
+
+/// Checks the hash of every line and that each one continues the chain of the previous one,
+/// returns the last hash.
+#[track_caller]
+fn verify(lines: &[String], mut prev: String) -> String {
+    for line in lines {
+        assert_eq!(field(line, "prev"), prev, "broken chain at {}", line);
+        let hashed = &line[..line.rfind(",\"hash\"").unwrap()];
+        let hash = sha2::Sha256::digest(hashed.as_bytes()).iter().map(|it| format!("{:02x}", it)).collect::<String>();
+        assert_eq!(field(line, "hash"), hash, "wrong hash of {}", line);
+        prev = hash;
+    }
+    prev
+}
+
+#[tokio::test]
+async fn mutating_calls_are_chained() {
+    let path = common::temp_path("audit-chain").join("audit.log");
+    let audited = Audited {
+        log: CriAuditLog::open(&path, 1 << 20, 3).unwrap(),
+    };
+    pull(&audited, "busybox").await;
+    let list = Request::new(v1alpha2::ListImagesRequest::default());
+    ImageService_v1alpha2::list_images(&audited, list).await.unwrap();
+    let remove = Request::new(v1alpha2::RemoveImageRequest::default());
+    ImageService_v1alpha2::remove_image(&audited, remove).await.unwrap_err();
+
+    // `list_images` is read-only and not recorded
+    let records = lines(&path);
+    assert_eq!(records.len(), 2);
+    assert!(records[0].contains("\"method\":\"pull_image\",\"service\":\"ImageService\",\"version\":\"v1\""));
+    assert!(records[0].contains("busybox"));
+    assert!(records[0].contains("\"code\":\"Ok\""));
+    assert!(records[1].contains("\"method\":\"remove_image\",\"service\":\"ImageService\",\"version\":\"v1alpha2\""));
+    assert!(records[1].contains("\"code\":\"NotFound\",\"message\":\"image \\\"busybox\\\" gone\""));
+    let last = verify(&records, "0".repeat(64));
+
+    // a log opened again continues the chain
+    let audited = Audited {
+        log: CriAuditLog::open(&path, 1 << 20, 3).unwrap(),
+    };
+    pull(&audited, "alpine").await;
+    let records = lines(&path);
+    assert_eq!(records.len(), 3);
+    verify(&records[2..], last);
+
+    // an edited record breaks the chain
+    let edited = records[0].replace("busybox", "alpine");
+    let result = std::panic::catch_unwind(|| verify(&[edited, records[1].clone()], "0".repeat(64)));
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn rotation_keeps_the_chain() {
+    let path = common::temp_path("audit-rotation").join("audit.log");
+    let rotated = |index: usize| path.with_file_name(format!("audit.log.{}", index));
+    let audited = Audited {
+        log: CriAuditLog::open(&path, 1, 2).unwrap(),
+    };
+    for image in ["a", "b", "c", "d"] {
+        pull(&audited, image).await;
+    }
+
+    // every record exceeds `max_bytes`, so each file holds one and the oldest is dropped
+    assert!(!rotated(3).exists());
+    let (oldest, older, newest) = (lines(&rotated(2)), lines(&rotated(1)), lines(&path));
+    assert_eq!([oldest.len(), older.len(), newest.len()], [1, 1, 1]);
+    assert!(oldest[0].contains("\"image\":\"b\""), "{}", oldest[0]);
+    assert!(newest[0].contains("\"image\":\"d\""), "{}", newest[0]);
+    let prev = field(&oldest[0], "prev").to_string();
+    verify(&[oldest, older, newest].concat(), prev);
+}
+
+/// Keeps the `error` field of every `cri` error.
+#[derive(Clone, Default)]
+struct Errors(Arc<Mutex<Vec<String>>>);
+
+impl Visit for Errors {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "error" {
+            self.0.lock().unwrap().push(format!("{:?}", value));
+        }
+    }
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Errors {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "cri" && *event.metadata().level() == tracing::Level::ERROR {
+            event.record(&mut self.clone());
+        }
+    }
+}
+
+#[tokio::test]
+async fn failed_writes_are_logged() {
+    let errors = Errors::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(errors.clone()));
+
+    let path = common::temp_path("audit-failure").join("audit.log");
+    let audited = Audited {
+        log: CriAuditLog::open(&path, 1, 1).unwrap(),
+    };
+    pull(&audited, "a").await;
+    assert!(errors.0.lock().unwrap().is_empty());
+
+    // the rotation cannot move the log over a directory
+    std::fs::create_dir_all(path.with_file_name("audit.log.1").join("taken")).unwrap();
+    pull(&audited, "b").await;
+    assert_eq!(errors.0.lock().unwrap().len(), 1);
+    assert_eq!(lines(&path).len(), 1);
+}
+
+#[tokio::test]
+async fn logs_are_only_readable_by_their_owner() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let path = common::temp_path("audit-mode").join("audit.log");
+    let audited = Audited {
+        log: CriAuditLog::open(&path, 1, 1).unwrap(),
+    };
+    assert_eq!(mode(&path), 0o600);
+    pull(&audited, "a").await;
+    pull(&audited, "b").await;
+    assert_eq!(mode(&path), 0o600);
+    assert_eq!(mode(&path.with_file_name("audit.log.1")), 0o600);
+}
@@ -0,0 +1,102 @@
+use std::os::unix::fs::MetadataExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request, Status};
+
+#[macro_use]
+mod common;
+
+/// Counts the requests that reached the hooks.
+#[derive(Default)]
+pub struct Seen(AtomicUsize);
+
+impl v1::ImageServiceHooks for Seen {
+    fn before_remove_image(&self, _request: &mut Request<v1::RemoveImageRequest>) -> Result<(), Status> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl v1alpha2::ImageServiceHooks for Seen {}
+
+fixture! {
+    #[auto_impl(image, authorize, server, layer = Seen)]
+    pub struct Guarded {
+        policy: CriPolicy,
+        seen: Seen,
+    }
+}
+
+/// Uid and gid of this process, as the owner of a file it creates.
+fn credentials() -> (u32, u32) {
+    let path = common::temp_path("authorize-owner");
+    std::fs::write(&path, "").unwrap();
+    let meta = std::fs::metadata(&path).unwrap();
+    (meta.uid(), meta.gid())
+}
+
+async fn serve(guarded: Guarded, name: &str) -> (Arc<Guarded>, tonic::transport::Channel) {
+    let path = common::temp_path(name);
+    let guarded = Arc::new(guarded);
+    tokio::spawn(guarded.clone().serve_unix(path.clone()));
+    (guarded, common::connect_unix(&path).await)
+}
+
+#[tokio::test]
+async fn allow_lists_split_read_only_and_mutating_methods() {
+    let (uid, gid) = credentials();
+    let policy = CriPolicy::new(CriAllowList::new().uid(uid), CriAllowList::new());
+    let (guarded, channel) = serve(Guarded { policy, seen: Seen::default() }, "authorize.sock").await;
+
+    let _: v1::ListImagesResponse =
+        common::unary(&channel, "/runtime.v1.ImageService/ListImages", v1::ListImagesRequest::default()).await.unwrap();
+    let e = common::unary::<_, v1::RemoveImageResponse>(
+        &channel,
+        "/runtime.v1.ImageService/RemoveImage",
+        v1::RemoveImageRequest::default(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(e.code(), Code::PermissionDenied);
+    assert_eq!(e.message(), format!("uid {} gid {} may not call remove_image", uid, gid));
+    // rejected before any hook saw the request
+    assert_eq!(guarded.seen.0.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn callbacks_decide_by_method() {
+    let policy = CriPolicy::from_fn(|_, method, access| method == "remove_image" && access == CriAccess::Mutating);
+    let (guarded, channel) = serve(Guarded { policy, seen: Seen::default() }, "authorize-fn.sock").await;
+
+    let e = common::unary::<_, v1alpha2::ListImagesResponse>(
+        &channel,
+        "/runtime.v1alpha2.ImageService/ListImages",
+        v1alpha2::ListImagesRequest::default(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(e.code(), Code::PermissionDenied);
+    // allowed through, the fixture answers that the image is gone
+    let e = common::unary::<_, v1::RemoveImageResponse>(
+        &channel,
+        "/runtime.v1.ImageService/RemoveImage",
+        v1::RemoveImageRequest::default(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(e.code(), Code::NotFound);
+    assert_eq!(guarded.seen.0.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn calls_without_peer_credentials_are_denied() {
+    let guarded = Guarded {
+        policy: CriPolicy::from_fn(|_, _, _| true),
+        seen: Seen::default(),
+    };
+    let e = ImageService_v1::list_images(&guarded, Request::new(v1::ListImagesRequest::default())).await.unwrap_err();
+    assert_eq!(e.code(), Code::PermissionDenied);
+    assert_eq!(e.message(), "peer credentials are unavailable");
+}
@@ -0,0 +1,118 @@
+use std::sync::{Arc, Mutex};
+
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request, Status};
+
+#[macro_use]
+mod common;
+
+/// Records the image filters of the v1 listings that reached the upstream.
+#[derive(Default)]
+pub struct Listings(Mutex<Vec<Option<v1::ImageFilter>>>);
+
+impl v1::ImageServiceHooks for Listings {
+    fn before_list_images(&self, request: &mut Request<v1::ListImagesRequest>) -> Result<(), Status> {
+        self.0.lock().unwrap().push(request.get_ref().filter.clone());
+        Ok(())
+    }
+}
+
+impl v1alpha2::ImageServiceHooks for Listings {}
+impl v1::RuntimeServiceHooks for Listings {}
+impl v1alpha2::RuntimeServiceHooks for Listings {}
+
+fixture! {
+    runtime;
+    #[auto_impl(runtime, image, server, layer = Listings)]
+    pub struct Upstream {
+        listings: Listings,
+    }
+}
+
+/// Serves a new `Upstream` on the socket `name`.
+async fn upstream(name: &str) -> (Arc<Upstream>, std::path::PathBuf) {
+    let path = common::temp_path(name);
+    let upstream = Arc::new(Upstream { listings: Listings::default() });
+    tokio::spawn(upstream.clone().serve_unix(path.clone()));
+    // waits for it to listen
+    common::connect_unix(&path).await;
+    (upstream, path)
+}
+
+mod to_v1 {
+    use auto_impl_trait::auto_impl;
+
+    #[auto_impl(runtime, image, bridge = v1)]
+    pub struct Bridge {
+        pub upstream: CriUpstream,
+    }
+
+    impl v1::RuntimeServiceProxy for Bridge {}
+    impl v1alpha2::RuntimeServiceProxy for Bridge {}
+    impl v1::ImageServiceProxy for Bridge {}
+    impl v1alpha2::ImageServiceProxy for Bridge {}
+}
+
+mod to_v1alpha2 {
+    use auto_impl_trait::auto_impl;
+
+    #[auto_impl(runtime, bridge = v1alpha2)]
+    pub struct Bridge {
+        pub upstream: CriUpstream,
+    }
+
+    impl v1::RuntimeServiceProxy for Bridge {}
+    impl v1alpha2::RuntimeServiceProxy for Bridge {}
+}
+
+use to_v1::v1alpha2::image_service_server::ImageService as _;
+use to_v1alpha2::v1::runtime_service_server::RuntimeService as _;
+
+#[tokio::test]
+async fn v1alpha2_calls_are_converted_for_a_v1_upstream() {
+    let (upstream, path) = upstream("bridge-upstream.sock").await;
+    let bridge = to_v1::Bridge {
+        upstream: to_v1::CriUpstream::connect_unix(&path).await.unwrap(),
+    };
+
+    let filter = to_v1::v1alpha2::ImageFilter {
+        image: Some(to_v1::v1alpha2::ImageSpec {
+            image: "busybox".to_string(),
+            annotations: [("note".to_string(), "kept".to_string())].into(),
+        }),
+    };
+    let request = to_v1::v1alpha2::ListImagesRequest { filter: Some(filter) };
+    bridge.list_images(Request::new(request)).await.unwrap();
+
+    let filter = upstream.listings.0.lock().unwrap()[0].clone();
+    let image = filter.and_then(|it| it.image).unwrap();
+    assert_eq!(image.image, "busybox");
+    assert_eq!(image.annotations["note"], "kept");
+
+    // the upstream's errors come back as is
+    let e = bridge
+        .remove_image(Request::new(to_v1::v1alpha2::RemoveImageRequest::default()))
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), Code::NotFound);
+    assert_eq!(e.message(), "image gone");
+}
+
+#[tokio::test]
+async fn methods_without_counterpart_are_unimplemented() {
+    let (_upstream, path) = upstream("bridge-v1alpha2-upstream.sock").await;
+    let bridge = to_v1alpha2::Bridge {
+        upstream: to_v1alpha2::CriUpstream::connect_unix(&path).await.unwrap(),
+    };
+
+    bridge.version(Request::new(to_v1alpha2::v1::VersionRequest::default())).await.unwrap();
+
+    let e = bridge
+        .checkpoint_container(Request::new(to_v1alpha2::v1::CheckpointContainerRequest::default()))
+        .await
+        .unwrap_err();
+    assert_eq!(e.code(), Code::Unimplemented);
+    assert_eq!(e.message(), "runtime.v1.RuntimeService/CheckpointContainer has no counterpart in runtime.v1alpha2");
+    let events = bridge.get_container_events(Request::new(to_v1alpha2::v1::GetEventsRequest::default())).await;
+    assert_eq!(events.err().map(|it| it.code()), Some(Code::Unimplemented));
+}
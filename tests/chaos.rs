@@ -0,0 +1,150 @@
+// scripted responses return `tonic::Status` as the generated traits do
+#![allow(clippy::result_large_err)]
+
+use std::time::{Duration, Instant};
+
+use auto_impl_trait::auto_impl;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_stream::StreamExt;
+use tonic::{Code, Request};
+
+#[macro_use]
+mod common;
+
+fixture! {
+    runtime;
+    #[auto_impl(runtime, image, chaos, mock)]
+    pub struct Runtime;
+}
+
+use v1::image_service_server::ImageService as V1Image;
+use v1::runtime_service_server::RuntimeService as V1Runtime;
+use v1alpha2::runtime_service_server::RuntimeService as V1alpha2Runtime;
+
+fn run_pod(name: &str) -> Request<v1::RunPodSandboxRequest> {
+    Request::new(v1::RunPodSandboxRequest {
+        config: Some(v1::PodSandboxConfig {
+            labels: [("io.kubernetes.pod.name".to_string(), name.to_string())].into(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+#[tokio::test]
+async fn faults_apply_to_matching_calls() {
+    let chaos = CriChaos::new(MockRuntime::new());
+    let fault = "method=run_pod_sandbox&version=v1&match=labels.io.kubernetes.pod.name=web-0&code=unavailable";
+    chaos.faults().add(fault.parse().unwrap());
+
+    let e = V1Runtime::run_pod_sandbox(&chaos, run_pod("web-0")).await.unwrap_err();
+    assert_eq!(e.code(), Code::Unavailable);
+    assert_eq!(e.message(), "fault injected by CriChaos");
+    V1Runtime::run_pod_sandbox(&chaos, run_pod("web-1")).await.unwrap();
+    let request = Request::new(v1alpha2::RunPodSandboxRequest::default());
+    V1alpha2Runtime::run_pod_sandbox(&chaos, request).await.unwrap();
+    // failed calls never reach the wrapped implementation
+    assert_eq!(chaos.inner().v1_run_pod_sandbox().calls().len(), 1);
+
+    chaos.faults().clear();
+    chaos.faults().add("method=*&latency=50ms&probability=1".parse().unwrap());
+    let start = Instant::now();
+    V1Runtime::version(&chaos, Request::new(v1::VersionRequest::default())).await.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    chaos.faults().clear();
+    chaos.faults().add("method=version&code=internal&probability=0".parse().unwrap());
+    V1Runtime::version(&chaos, Request::new(v1::VersionRequest::default())).await.unwrap();
+}
+
+#[tokio::test]
+async fn responses_are_truncated_and_streams_dropped() {
+    let chaos = CriChaos::new(MockRuntime::new());
+    chaos.inner().v1_list_images().returns_with(|_| {
+        Ok(v1::ListImagesResponse {
+            images: vec![v1::Image::default(); 3],
+        })
+    });
+    chaos.inner().v1_get_container_events().returns_with(|_| {
+        let events = vec![Ok(v1::ContainerEventResponse::default()); 3];
+        Ok(Box::pin(tokio_stream::iter(events)))
+    });
+    chaos.faults().add("method=list_images&truncate=1".parse().unwrap());
+    chaos.faults().add("method=get_container_events&drop_after=1".parse().unwrap());
+
+    let images = V1Image::list_images(&chaos, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+    assert_eq!(images.into_inner().images.len(), 1);
+
+    let request = Request::new(v1::GetEventsRequest::default());
+    let events = V1Runtime::get_container_events(&chaos, request).await.unwrap().into_inner();
+    let events = events.collect::<Vec<_>>().await;
+    assert_eq!(events.len(), 2);
+    assert!(events[0].is_ok());
+    assert_eq!(events[1].as_ref().unwrap_err().code(), Code::Unavailable);
+}
+
+#[test]
+fn faults_are_parsed_from_query_strings() {
+    let fault = "method=list_containers&latency=2s&code=DEADLINE_EXCEEDED&message=slow%20disk&match=metadata.name=a%3Db"
+        .parse::<CriFault>()
+        .unwrap();
+    assert_eq!(fault.method.as_deref(), Some("list_containers"));
+    assert_eq!(fault.latency, Some(Duration::from_secs(2)));
+    assert_eq!(fault.code, Some(Code::DeadlineExceeded));
+    assert_eq!(fault.message.as_deref(), Some("slow disk"));
+    assert_eq!(fault.matches, [("metadata.name".to_string(), "a=b".to_string())]);
+    assert_eq!("code=14".parse::<CriFault>().unwrap().code, Some(Code::Unavailable));
+
+    assert_eq!("probability=2".parse::<CriFault>().unwrap_err(), "invalid `probability`: `2`");
+    assert_eq!("latency=soon".parse::<CriFault>().unwrap_err(), "invalid `latency`: `soon`");
+    assert_eq!("retries=3".parse::<CriFault>().unwrap_err(), "unknown fault key `retries`");
+}
+
+#[test]
+fn faults_round_trip_through_their_query_strings() {
+    let fault = CriFault {
+        method: Some("list_containers".to_string()),
+        matches: vec![("labels.app".to_string(), "a b&c=d+e".to_string())],
+        code: Some(Code::Unavailable),
+        message: Some("a&b=c 50%".to_string()),
+        ..Default::default()
+    };
+    let query = fault.to_string();
+    assert!(query.contains("&message=a%26b%3Dc+50%25"), "{}", query);
+    assert_eq!(query.parse::<CriFault>().unwrap(), fault);
+}
+
+/// Sends `request` to the control API at `path` and returns the response.
+async fn control(path: &std::path::Path, request: &str) -> String {
+    let mut stream = loop {
+        match tokio::net::UnixStream::connect(path).await {
+            Ok(it) => break it,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    stream.write_all(format!("{} HTTP/1.1\r\nhost: cri\r\n\r\n", request).as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn faults_are_controlled_over_http() {
+    let chaos = CriChaos::new(MockRuntime::new());
+    let path = common::temp_path("chaos.sock");
+    tokio::spawn(chaos.faults().clone().serve_unix(path.clone()));
+
+    let response = control(&path, "POST /faults?method=version&code=unavailable").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    let e = V1Runtime::version(&chaos, Request::new(v1::VersionRequest::default())).await.unwrap_err();
+    assert_eq!(e.code(), Code::Unavailable);
+
+    let response = control(&path, "GET /faults").await;
+    assert!(response.ends_with("\r\n\r\nmethod=version&probability=1&code=Unavailable\n"));
+    let response = control(&path, "POST /faults?code=teapot").await;
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+    control(&path, "DELETE /faults").await;
+    assert!(chaos.faults().list().is_empty());
+    V1Runtime::version(&chaos, Request::new(v1::VersionRequest::default())).await.unwrap();
+}
@@ -0,0 +1,180 @@
+#![cfg(all(feature = "cli", feature = "fake"))]
+
+use std::sync::Arc;
+
+#[path = "../src/cli.rs"]
+mod cli;
+#[allow(unused_macros)]
+mod common;
+#[path = "../src/fake.rs"]
+mod fake;
+#[path = "../src/transcode.rs"]
+mod transcode;
+
+use cli::{Api, Client, Options, Output};
+
+/// Serves a `FakeRuntime` at a fresh socket named `name` and returns its endpoint.
+async fn serve(name: &str) -> String {
+    let path = common::temp_path(name);
+    tokio::spawn(Arc::new(fake::FakeRuntime::new()).serve_unix(path.clone()));
+    common::connect_unix(&path).await;
+    format!("unix://{}", path.display())
+}
+
+/// Runs `cri` with the arguments of `line` and returns what it prints.
+async fn cri(endpoint: &str, line: &str) -> Result<String, String> {
+    let args = ["-r", endpoint]
+        .into_iter()
+        .chain(line.split_whitespace())
+        .map(String::from);
+    let options = Options::parse(args)?;
+    let client = Client::connect(&options.endpoint, options.api)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut out = Vec::new();
+    cli::run(&client, &options, &mut out)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn options_are_parsed() {
+    let args = "-o yaml --api v1alpha2 ps -a"
+        .split_whitespace()
+        .map(String::from);
+    let options = Options::parse(args).unwrap();
+    assert_eq!(options.output, Output::Yaml);
+    assert_eq!(options.api, Some(Api::V1alpha2));
+    assert_eq!(options.command, "ps");
+    assert!(options.all);
+
+    let parse = |line: &str| Options::parse(line.split_whitespace().map(String::from)).unwrap_err();
+    assert_eq!(
+        parse("-o xml pods"),
+        "unknown output `xml`, expected table, json or yaml"
+    );
+    assert_eq!(parse("pods -r"), "`-r` needs a value");
+    assert_eq!(parse("-o json"), "a command is required");
+}
+
+#[test]
+fn sizes_are_decimal() {
+    assert_eq!(cli::human_size(0), "0B");
+    assert_eq!(cli::human_size(1 << 20), "1.05MB");
+    assert_eq!(cli::human_size(123_456_789), "123MB");
+    assert_eq!(cli::human_size(10_000), "10kB");
+}
+
+#[tokio::test]
+async fn images_are_pulled_listed_and_removed() {
+    let endpoint = serve("cli-images").await;
+
+    let out = cri(&endpoint, "pull busybox").await.unwrap();
+    assert!(out.starts_with("Image is up to date for sha256:"));
+    let out = cri(&endpoint, "images").await.unwrap();
+    let lines = out.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(
+        lines[0],
+        "IMAGE                       TAG      IMAGE ID        SIZE"
+    );
+    assert!(lines[1].starts_with("docker.io/library/busybox   latest   "));
+    assert!(lines[1].ends_with("   1.05MB"));
+
+    let out = cri(&endpoint, "-o json images").await.unwrap();
+    assert!(out.contains("\"repoTags\": [\n        \"docker.io/library/busybox:latest\"\n      ]"));
+    let out = cri(&endpoint, "-o yaml images").await.unwrap();
+    assert!(out.starts_with("images:\n  - id: sha256:"));
+    assert!(out.contains("\n    repoTags:\n      - docker.io/library/busybox:latest\n"));
+    assert!(out.contains("\n    uid: null\n    username: \"\"\n"));
+
+    assert_eq!(
+        cri(&endpoint, "rmi busybox").await.unwrap(),
+        "Deleted: busybox\n"
+    );
+    assert_eq!(
+        cri(&endpoint, "images").await.unwrap(),
+        "IMAGE   TAG   IMAGE ID   SIZE\n"
+    );
+}
+
+#[tokio::test]
+async fn both_versions_are_spoken() {
+    let endpoint = serve("cli-versions").await;
+
+    let out = cri(&endpoint, "version").await.unwrap();
+    assert!(out.contains("RuntimeName:         fake\n"));
+    assert!(out.ends_with("RuntimeApiVersion:   v1\n"));
+    let out = cri(&endpoint, "--api v1alpha2 version").await.unwrap();
+    assert!(out.ends_with("RuntimeApiVersion:   v1alpha2\n"));
+
+    cri(&endpoint, "--api v1alpha2 pull nginx:1.25")
+        .await
+        .unwrap();
+    let out = cri(&endpoint, "--api v1alpha2 images").await.unwrap();
+    assert!(out
+        .lines()
+        .nth(1)
+        .unwrap()
+        .starts_with("docker.io/library/nginx   1.25   "));
+
+    let e = cri(&endpoint, "--api v1alpha2 events").await.unwrap_err();
+    assert_eq!(e, "container events are only served by runtime.v1");
+}
+
+#[tokio::test]
+async fn missing_objects_fail() {
+    let endpoint = serve("cli-missing").await;
+
+    assert_eq!(cri(&endpoint, "pods").await.unwrap().lines().count(), 1);
+    assert_eq!(cri(&endpoint, "ps -a").await.unwrap().lines().count(), 1);
+    let e = cri(&endpoint, "inspectp 0123").await.unwrap_err();
+    assert!(e.contains("pod sandbox 0123 not found"), "{}", e);
+    assert_eq!(
+        cri(&endpoint, "rm").await.unwrap_err(),
+        "a container id is required"
+    );
+    assert!(cri(&endpoint, "attach")
+        .await
+        .unwrap_err()
+        .starts_with("unknown command `attach`"));
+}
+
+/// Runs `cri expand` with the arguments of `line`.
+fn expand(line: &[&str]) -> Result<String, String> {
+    let options = Options::parse(["expand"].iter().chain(line).map(|it| it.to_string()))?;
+    let mut out = Vec::new();
+    cli::expand(&options, &mut out).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[test]
+fn expansions_are_printed() {
+    let out = expand(&["--struct", "pub struct Square { side: i32 }", "image", "mock"]).unwrap();
+    assert!(out.starts_with("///Generated by `#[derive(auto_impl(image))]`\npub struct Square {\n    side: i32,\n}\n"));
+    assert!(out.contains("\npub mod v1 {\n"));
+    assert!(out.contains("\npub mod v1alpha2 {\n"));
+    assert!(out.contains("pub struct MockSquare {"));
+
+    let out = expand(&["--api", "v1alpha2", "image"]).unwrap();
+    assert!(!out.contains("pub mod v1 {"));
+    assert!(out.contains("\nuse v1alpha2::image_service_server::ImageService as ImageService_v1alpha2;\n"));
+    assert!(!out.contains("ImageService_v1;"));
+
+    assert_eq!(expand(&["bogus"]).unwrap_err(), "unknown auto_impl option `bogus`");
+    let e = expand(&["--proto", "runtime.v1.rs", "image"]).unwrap_err();
+    assert_eq!(e, "`--proto` needs `--api` to say which version it holds");
+}
+
+#[test]
+fn expansions_use_the_given_proto() {
+    let path = common::temp_path("cli-proto.rs");
+    let source = include_str!("../engine/proto/runtime.v1.rs").replace("pub struct VersionRequest", "pub struct VersionQuery");
+    std::fs::write(&path, source).unwrap();
+    let proto = path.to_str().unwrap();
+
+    let out = expand(&["--api", "v1", "--proto", proto, "client"]).unwrap();
+    assert!(out.contains("pub struct VersionQuery {"));
+    assert!(!out.contains("pub struct VersionRequest {"));
+}
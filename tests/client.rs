@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use auto_impl_trait::auto_impl;
+use tonic::Code;
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, server, client)]
+    pub struct Served;
+}
+
+#[tokio::test]
+async fn clients_of_both_versions_call_over_a_unix_socket() {
+    let path = common::temp_path("client.sock");
+    tokio::spawn(Arc::new(Served).serve_unix(path.clone()));
+    // waits for the server to listen
+    common::connect_unix(&path).await;
+
+    let client = v1::CriClient::connect_unix(&path).await.unwrap();
+    let images = client.list_images(v1::ListImagesRequest::default()).await.unwrap();
+    assert!(images.images.is_empty());
+    let e = client.remove_image(v1::RemoveImageRequest::default()).await.unwrap_err();
+    assert_eq!(e.code(), Code::NotFound);
+    assert_eq!(e.message(), "image gone");
+    // the runtime service is not served
+    let e = client.version(v1::VersionRequest::default()).await.unwrap_err();
+    assert_eq!(e.code(), Code::Unimplemented);
+
+    let client = v1alpha2::CriClient::connect_unix(&path).await.unwrap();
+    client.image_fs_info(v1alpha2::ImageFsInfoRequest::default()).await.unwrap();
+}
@@ -0,0 +1,137 @@
+//! Fixtures of the option tests: structs annotated with `#[auto_impl(image, ...)]` and the
+//! image service methods their generated impls forward to.
+
+// every test uses a part of it
+#![allow(dead_code)]
+
+/// Declares the annotated struct and its image service methods, generic over the messages of
+/// both versions. Each method hands its name and request to `answer`, `crate::common::answer`
+/// unless another is given with `answer = path;`, and returns the `Default` response when it
+/// succeeds.
+///
+/// `fixture!(runtime; ...)` adds the runtime service methods, whose container events never
+/// arrive, and declares their `GetContainerEventsStream`.
+macro_rules! fixture {
+    (runtime; $(#[$attr:meta])* pub struct $name:ident $($body:tt)*) => {
+        fixture!(answer = crate::common::answer; $(#[$attr])* pub struct $name $($body)*);
+
+        pub type GetContainerEventsStream = crate::common::Pending<v1::ContainerEventResponse>;
+
+        #[allow(dead_code)]
+        impl $name {
+            fixture!(@methods crate::common::answer; version, run_pod_sandbox, stop_pod_sandbox,
+                remove_pod_sandbox, pod_sandbox_status, list_pod_sandbox, create_container,
+                start_container, stop_container, remove_container, list_containers, container_status,
+                update_container_resources, reopen_container_log, exec_sync, exec, attach, port_forward,
+                container_stats, list_container_stats, pod_sandbox_stats, list_pod_sandbox_stats,
+                update_runtime_config, status, checkpoint_container, get_container_events);
+        }
+    };
+    (answer = $answer:path; $(#[$attr:meta])* pub struct $name:ident $($body:tt)*) => {
+        $(#[$attr])*
+        pub struct $name $($body)*
+
+        #[allow(dead_code)]
+        impl $name {
+            fixture!(@methods $answer; list_images, image_status, pull_image, remove_image, image_fs_info);
+        }
+    };
+    (@methods $answer:path; $($method:ident),*) => {
+        $(
+            pub async fn $method<Req, Resp>(&self, request: tonic::Request<Req>) -> Result<tonic::Response<Resp>, tonic::Status>
+            where
+                Req: Send,
+                Resp: Default,
+            {
+                $answer(stringify!($method), request).await?;
+                Ok(tonic::Response::new(Resp::default()))
+            }
+        )*
+    };
+    ($(#[$attr:meta])* pub struct $name:ident $($body:tt)*) => {
+        fixture!(answer = crate::common::answer; $(#[$attr])* pub struct $name $($body)*);
+    };
+}
+
+/// Server stream that never yields, until the server ends it.
+pub struct Pending<T>(std::marker::PhantomData<fn() -> T>);
+
+impl<T> Default for Pending<T> {
+    fn default() -> Self {
+        Pending(std::marker::PhantomData)
+    }
+}
+
+impl<T> tokio_stream::Stream for Pending<T> {
+    type Item = Result<T, tonic::Status>;
+
+    fn poll_next(self: std::pin::Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+        std::task::Poll::Pending
+    }
+}
+
+/// Answers the calls of the fixtures: `remove_image` fails with `NotFound` as if the image was
+/// gone, and `image_fs_info` takes 100ms as a slow disk would.
+pub async fn answer<Req>(method: &str, _request: tonic::Request<Req>) -> Result<(), tonic::Status> {
+    match method {
+        "remove_image" => Err(tonic::Status::not_found("image gone")),
+        "image_fs_info" => {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A path in the temporary directory no other test uses, removed if a previous run left it.
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("auto_impl_trait-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&path);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Connects to the unix socket at `path`, waiting for the server to listen on it.
+pub async fn connect_unix(path: &std::path::Path) -> tonic::transport::Channel {
+    for _ in 0..100 {
+        let socket = path.to_path_buf();
+        let connector = tower::service_fn(move |_| tokio::net::UnixStream::connect(socket.clone()));
+        let endpoint = tonic::transport::Endpoint::from_static("http://[::]:50051");
+        if let Ok(channel) = endpoint.connect_with_connector(connector).await {
+            return channel;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("nothing listens on {}", path.display());
+}
+
+/// Calls the unary gRPC method at `path`, such as `/runtime.v1.ImageService/ListImages`.
+pub async fn unary<Req, Resp>(channel: &tonic::transport::Channel, path: &'static str, request: Req) -> Result<Resp, tonic::Status>
+where
+    Req: prost::Message + 'static,
+    Resp: prost::Message + Default + 'static,
+{
+    let mut grpc = tonic::client::Grpc::new(channel.clone());
+    grpc.ready().await.map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+    let path = tonic::codegen::http::uri::PathAndQuery::from_static(path);
+    let response = grpc.unary(tonic::Request::new(request), path, tonic::codec::ProstCodec::default()).await?;
+    Ok(response.into_inner())
+}
+
+/// Calls the server streaming gRPC method at `path`, such as
+/// `/runtime.v1.RuntimeService/GetContainerEvents`.
+pub async fn server_streaming<Req, Resp>(
+    channel: &tonic::transport::Channel,
+    path: &'static str,
+    request: Req,
+) -> Result<tonic::Streaming<Resp>, tonic::Status>
+where
+    Req: prost::Message + 'static,
+    Resp: prost::Message + Default + 'static,
+{
+    let mut grpc = tonic::client::Grpc::new(channel.clone());
+    grpc.ready().await.map_err(|e| tonic::Status::unavailable(e.to_string()))?;
+    let path = tonic::codegen::http::uri::PathAndQuery::from_static(path);
+    let response = grpc.server_streaming(tonic::Request::new(request), path, tonic::codec::ProstCodec::default()).await?;
+    Ok(response.into_inner())
+}
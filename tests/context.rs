@@ -0,0 +1,69 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use auto_impl_trait::auto_impl;
+use tonic::Request;
+
+#[macro_use]
+mod common;
+
+/// Contexts the handlers received, by request id.
+static SEEN: Mutex<Vec<CriContext>> = Mutex::new(Vec::new());
+
+async fn answer<Req>(_method: &str, request: Request<Req>) -> Result<(), tonic::Status> {
+    let ctx = CriContext::get(&request).ok_or_else(|| tonic::Status::internal("no context"))?;
+    SEEN.lock().unwrap().push(ctx.clone());
+    Ok(())
+}
+
+fixture! {
+    answer = answer;
+    #[auto_impl(image, context)]
+    pub struct Contexts;
+}
+
+fn seen(request_id: &str) -> CriContext {
+    SEEN.lock().unwrap().iter().find(|it| it.request_id == request_id).cloned().unwrap()
+}
+
+#[tokio::test]
+async fn handlers_get_the_context_of_the_call() {
+    let mut request = Request::new(v1::ListImagesRequest::default());
+    request.metadata_mut().insert("x-request-id", "list-1".parse().unwrap());
+    request.metadata_mut().insert("grpc-timeout", "5S".parse().unwrap());
+    request.metadata_mut().insert("user-agent", "kubelet".parse().unwrap());
+    ImageService_v1::list_images(&Contexts, request).await.unwrap();
+
+    let ctx = seen("list-1");
+    assert_eq!(ctx.version, CriVersion::V1);
+    assert_eq!(ctx.metadata.get("user-agent").unwrap(), "kubelet");
+    let remaining = ctx.remaining().unwrap();
+    assert!(remaining <= Duration::from_secs(5) && remaining > Duration::from_secs(4), "{:?}", remaining);
+    assert!(!ctx.is_expired());
+    // an in-process call has no peer
+    assert_eq!(ctx.peer, None);
+
+    let mut request = Request::new(v1alpha2::PullImageRequest::default());
+    request.metadata_mut().insert("x-request-id", "pull-1".parse().unwrap());
+    request.metadata_mut().insert("grpc-timeout", "1n".parse().unwrap());
+    ImageService_v1alpha2::pull_image(&Contexts, request).await.unwrap();
+
+    let ctx = seen("pull-1");
+    assert_eq!(ctx.version, CriVersion::V1alpha2);
+    assert!(ctx.is_expired());
+}
+
+#[tokio::test]
+async fn calls_without_a_request_id_get_a_unique_one() {
+    let before = SEEN.lock().unwrap().len();
+    for _ in 0..2 {
+        let request = Request::new(v1::ImageStatusRequest::default());
+        ImageService_v1::image_status(&Contexts, request).await.unwrap();
+    }
+    let seen = SEEN.lock().unwrap();
+    // the other test's calls all send an id
+    let generated = seen[before..].iter().filter(|it| !it.request_id.contains('-')).collect::<Vec<_>>();
+    assert_eq!(generated.len(), 2);
+    assert_ne!(generated[0].request_id, generated[1].request_id);
+    assert!(generated.iter().all(|it| it.request_id.len() == 16 && it.deadline.is_none()));
+}
@@ -0,0 +1,295 @@
+#![cfg(feature = "fake")]
+
+use std::collections::HashMap;
+
+use tokio_stream::StreamExt;
+use tonic::{Code, Request, Status};
+
+#[path = "../src/fake.rs"]
+mod fake;
+#[path = "../src/transcode.rs"]
+mod transcode;
+
+use fake::{v1, v1alpha2, FakeRuntime};
+use v1::image_service_server::ImageService as V1Image;
+use v1::runtime_service_server::RuntimeService as V1Runtime;
+use v1alpha2::image_service_server::ImageService as V1alpha2Image;
+use v1alpha2::runtime_service_server::RuntimeService as V1alpha2Runtime;
+
+fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+}
+
+async fn run_pod(runtime: &FakeRuntime, name: &str, app: &str) -> String {
+    let config = v1::PodSandboxConfig {
+        metadata: Some(v1::PodSandboxMetadata {
+            name: name.to_string(),
+            uid: format!("{}-uid", name),
+            namespace: "default".to_string(),
+            attempt: 0,
+        }),
+        labels: labels(&[("app", app)]),
+        ..Default::default()
+    };
+    let request = v1::RunPodSandboxRequest {
+        config: Some(config),
+        runtime_handler: String::new(),
+    };
+    V1Runtime::run_pod_sandbox(runtime, Request::new(request)).await.unwrap().into_inner().pod_sandbox_id
+}
+
+async fn create_container(runtime: &FakeRuntime, pod_sandbox_id: &str, name: &str) -> Result<String, Status> {
+    let config = v1::ContainerConfig {
+        metadata: Some(v1::ContainerMetadata {
+            name: name.to_string(),
+            attempt: 0,
+        }),
+        image: Some(v1::ImageSpec {
+            image: "busybox".to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let request = v1::CreateContainerRequest {
+        pod_sandbox_id: pod_sandbox_id.to_string(),
+        config: Some(config),
+        sandbox_config: None,
+    };
+    Ok(V1Runtime::create_container(runtime, Request::new(request)).await?.into_inner().container_id)
+}
+
+async fn start_container(runtime: &FakeRuntime, container_id: &str) -> Result<(), Status> {
+    let request = v1::StartContainerRequest {
+        container_id: container_id.to_string(),
+    };
+    V1Runtime::start_container(runtime, Request::new(request)).await.map(drop)
+}
+
+async fn stop_pod(runtime: &FakeRuntime, pod_sandbox_id: &str) {
+    let request = v1::StopPodSandboxRequest {
+        pod_sandbox_id: pod_sandbox_id.to_string(),
+    };
+    V1Runtime::stop_pod_sandbox(runtime, Request::new(request)).await.unwrap();
+}
+
+async fn pull(runtime: &FakeRuntime, image: &str) -> String {
+    let request = v1::PullImageRequest {
+        image: Some(v1::ImageSpec {
+            image: image.to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    V1Image::pull_image(runtime, Request::new(request)).await.unwrap().into_inner().image_ref
+}
+
+async fn container_state(runtime: &FakeRuntime, container_id: &str) -> v1::ContainerState {
+    let request = v1::ContainerStatusRequest {
+        container_id: container_id.to_string(),
+        verbose: false,
+    };
+    let status = V1Runtime::container_status(runtime, Request::new(request)).await.unwrap().into_inner().status;
+    v1::ContainerState::from_i32(status.unwrap().state).unwrap()
+}
+
+async fn pods(runtime: &FakeRuntime, filter: v1::PodSandboxFilter) -> Vec<String> {
+    let request = v1::ListPodSandboxRequest { filter: Some(filter) };
+    let response = V1Runtime::list_pod_sandbox(runtime, Request::new(request)).await.unwrap();
+    response.into_inner().items.into_iter().map(|it| it.id).collect()
+}
+
+async fn containers(runtime: &FakeRuntime, filter: Option<v1::ContainerFilter>) -> Vec<String> {
+    let request = v1::ListContainersRequest { filter };
+    let response = V1Runtime::list_containers(runtime, Request::new(request)).await.unwrap();
+    response.into_inner().containers.into_iter().map(|it| it.id).collect()
+}
+
+#[tokio::test]
+async fn pod_and_container_lifecycle() {
+    let runtime = FakeRuntime::new();
+    let image_ref = pull(&runtime, "busybox").await;
+    assert_eq!(pull(&runtime, "docker.io/library/busybox:latest").await, image_ref);
+
+    let pod = run_pod(&runtime, "web", "web").await;
+    let container = create_container(&runtime, &pod, "nginx").await.unwrap();
+    assert_eq!(container_state(&runtime, &container).await, v1::ContainerState::ContainerCreated);
+
+    start_container(&runtime, &container).await.unwrap();
+    assert_eq!(container_state(&runtime, &container).await, v1::ContainerState::ContainerRunning);
+    assert_eq!(start_container(&runtime, &container).await.unwrap_err().code(), Code::FailedPrecondition);
+
+    stop_pod(&runtime, &pod).await;
+    assert_eq!(container_state(&runtime, &container).await, v1::ContainerState::ContainerExited);
+    let status = v1::PodSandboxStatusRequest {
+        pod_sandbox_id: pod.clone(),
+        verbose: false,
+    };
+    let status = V1Runtime::pod_sandbox_status(&runtime, Request::new(status)).await.unwrap().into_inner().status;
+    assert_eq!(status.unwrap().state, v1::PodSandboxState::SandboxNotready as i32);
+    assert_eq!(create_container(&runtime, &pod, "late").await.unwrap_err().code(), Code::FailedPrecondition);
+
+    let remove = v1::RemovePodSandboxRequest {
+        pod_sandbox_id: pod.clone(),
+    };
+    V1Runtime::remove_pod_sandbox(&runtime, Request::new(remove.clone())).await.unwrap();
+    assert!(containers(&runtime, None).await.is_empty());
+
+    // stopping and removing what is gone succeeds, as kubelet retries them
+    stop_pod(&runtime, &pod).await;
+    V1Runtime::remove_pod_sandbox(&runtime, Request::new(remove)).await.unwrap();
+    let missing = v1::PodSandboxStatusRequest {
+        pod_sandbox_id: pod,
+        verbose: false,
+    };
+    let e = V1Runtime::pod_sandbox_status(&runtime, Request::new(missing)).await.unwrap_err();
+    assert_eq!(e.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn create_container_needs_a_pulled_image() {
+    let runtime = FakeRuntime::new();
+    let pod = run_pod(&runtime, "web", "web").await;
+    assert_eq!(create_container(&runtime, &pod, "nginx").await.unwrap_err().code(), Code::NotFound);
+    assert_eq!(create_container(&runtime, "missing", "nginx").await.unwrap_err().code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn list_filters() {
+    let runtime = FakeRuntime::new();
+    pull(&runtime, "busybox").await;
+    let web = run_pod(&runtime, "web", "web").await;
+    let db = run_pod(&runtime, "db", "db").await;
+    let running = create_container(&runtime, &web, "nginx").await.unwrap();
+    let created = create_container(&runtime, &web, "sidecar").await.unwrap();
+    create_container(&runtime, &db, "postgres").await.unwrap();
+    start_container(&runtime, &running).await.unwrap();
+    stop_pod(&runtime, &db).await;
+
+    assert_eq!(pods(&runtime, v1::PodSandboxFilter::default()).await, vec![web.clone(), db.clone()]);
+    let by_label = v1::PodSandboxFilter {
+        label_selector: labels(&[("app", "db")]),
+        ..Default::default()
+    };
+    assert_eq!(pods(&runtime, by_label).await, vec![db.clone()]);
+    let ready = v1::PodSandboxFilter {
+        state: Some(v1::PodSandboxStateValue {
+            state: v1::PodSandboxState::SandboxReady as i32,
+        }),
+        ..Default::default()
+    };
+    assert_eq!(pods(&runtime, ready).await, vec![web.clone()]);
+    let by_id = v1::PodSandboxFilter {
+        id: db.clone(),
+        ..Default::default()
+    };
+    assert_eq!(pods(&runtime, by_id).await, vec![db]);
+
+    let of_web = v1::ContainerFilter {
+        pod_sandbox_id: web,
+        ..Default::default()
+    };
+    assert_eq!(containers(&runtime, Some(of_web)).await, vec![running.clone(), created.clone()]);
+    let state = |state: v1::ContainerState| {
+        Some(v1::ContainerFilter {
+            state: Some(v1::ContainerStateValue { state: state as i32 }),
+            ..Default::default()
+        })
+    };
+    assert_eq!(containers(&runtime, state(v1::ContainerState::ContainerRunning)).await, vec![running]);
+    assert_eq!(containers(&runtime, state(v1::ContainerState::ContainerCreated)).await, vec![created]);
+    assert_eq!(containers(&runtime, state(v1::ContainerState::ContainerExited)).await.len(), 1);
+}
+
+#[tokio::test]
+async fn container_events() {
+    let runtime = FakeRuntime::new();
+    let events = V1Runtime::get_container_events(&runtime, Request::new(v1::GetEventsRequest {})).await.unwrap();
+    let mut events = events.into_inner();
+    pull(&runtime, "busybox").await;
+    let pod = run_pod(&runtime, "web", "web").await;
+    let container = create_container(&runtime, &pod, "nginx").await.unwrap();
+    start_container(&runtime, &container).await.unwrap();
+    let remove = v1::RemoveContainerRequest {
+        container_id: container.clone(),
+    };
+    V1Runtime::remove_container(&runtime, Request::new(remove)).await.unwrap();
+
+    let mut received = Vec::new();
+    for _ in 0..4 {
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event.container_id, container);
+        assert_eq!(event.pod_sandbox_metadata.unwrap().name, "web");
+        received.push(v1::ContainerEventType::from_i32(event.container_event_type).unwrap());
+    }
+    assert_eq!(
+        received,
+        vec![
+            v1::ContainerEventType::ContainerCreatedEvent,
+            v1::ContainerEventType::ContainerStartedEvent,
+            v1::ContainerEventType::ContainerStoppedEvent,
+            v1::ContainerEventType::ContainerDeletedEvent,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn v1alpha2_shares_the_v1_state() {
+    let runtime = FakeRuntime::new();
+    let version = v1alpha2::VersionRequest {
+        version: "v1alpha2".to_string(),
+    };
+    let version = V1alpha2Runtime::version(&runtime, Request::new(version)).await.unwrap().into_inner();
+    assert_eq!(version.runtime_api_version, "v1alpha2");
+
+    let pull = v1alpha2::PullImageRequest {
+        image: Some(v1alpha2::ImageSpec {
+            image: "busybox".to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let image_ref = V1alpha2Image::pull_image(&runtime, Request::new(pull)).await.unwrap().into_inner().image_ref;
+    let images = V1Image::list_images(&runtime, Request::new(v1::ListImagesRequest { filter: None })).await.unwrap();
+    assert_eq!(images.into_inner().images.iter().map(|it| &it.id).collect::<Vec<_>>(), vec![&image_ref]);
+
+    let pod = run_pod(&runtime, "web", "web").await;
+    let config = v1alpha2::ContainerConfig {
+        metadata: Some(v1alpha2::ContainerMetadata {
+            name: "nginx".to_string(),
+            attempt: 0,
+        }),
+        image: Some(v1alpha2::ImageSpec {
+            image: "busybox".to_string(),
+            ..Default::default()
+        }),
+        labels: labels(&[("tier", "front")]),
+        ..Default::default()
+    };
+    let create = v1alpha2::CreateContainerRequest {
+        pod_sandbox_id: pod.clone(),
+        config: Some(config),
+        sandbox_config: None,
+    };
+    let container = V1alpha2Runtime::create_container(&runtime, Request::new(create)).await.unwrap();
+    let container = container.into_inner().container_id;
+
+    let list = v1alpha2::ListContainersRequest {
+        filter: Some(v1alpha2::ContainerFilter {
+            pod_sandbox_id: pod,
+            ..Default::default()
+        }),
+    };
+    let listed = V1alpha2Runtime::list_containers(&runtime, Request::new(list)).await.unwrap().into_inner().containers;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, container);
+    assert_eq!(listed[0].labels, labels(&[("tier", "front")]));
+    assert_eq!(listed[0].state, v1alpha2::ContainerState::ContainerCreated as i32);
+    assert_eq!(container_state(&runtime, &container).await, v1::ContainerState::ContainerCreated);
+
+    let missing = v1alpha2::ContainerStatusRequest {
+        container_id: "missing".to_string(),
+        verbose: false,
+    };
+    let e = V1alpha2Runtime::container_status(&runtime, Request::new(missing)).await.unwrap_err();
+    assert_eq!(e.code(), Code::NotFound);
+}
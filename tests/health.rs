@@ -0,0 +1,129 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use auto_impl_trait::auto_impl;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::Request;
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, server, health, reflection)]
+    pub struct Served;
+}
+
+use grpc_health_v1::health_check_response::ServingStatus;
+use grpc_health_v1::health_server::Health;
+use grpc_health_v1::HealthCheckRequest;
+
+async fn check(health: &CriHealth, service: &str) -> Result<ServingStatus, tonic::Status> {
+    let request = Request::new(HealthCheckRequest { service: service.to_string() });
+    let status = health.check(request).await?.into_inner().status;
+    Ok(ServingStatus::from_i32(status).unwrap())
+}
+
+#[tokio::test]
+async fn every_generated_service_starts_out_serving() {
+    let services = Arc::new(Served).into_cri_services();
+    assert_eq!(check(&services.health, "").await.unwrap(), ServingStatus::Serving);
+    assert_eq!(check(&services.health, "runtime.v1.ImageService").await.unwrap(), ServingStatus::Serving);
+    assert_eq!(check(&services.health, "runtime.v1alpha2.ImageService").await.unwrap(), ServingStatus::Serving);
+    let unknown = check(&services.health, "runtime.v1.RuntimeService").await.unwrap_err();
+    assert_eq!(unknown.code(), tonic::Code::NotFound);
+
+    // a clone kept by the caller changes what the registered service reports
+    let health = services.health.clone();
+    health.set_serving_status("runtime.v1.ImageService", ServingStatus::NotServing);
+    assert_eq!(check(&services.health, "runtime.v1.ImageService").await.unwrap(), ServingStatus::NotServing);
+    assert_eq!(check(&services.health, "").await.unwrap(), ServingStatus::Serving);
+}
+
+#[tokio::test]
+async fn watchers_see_not_serving_once_draining() {
+    let path = common::temp_path("health.sock");
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(Arc::new(Served).serve_unix_with_shutdown(
+        path.clone(),
+        async {
+            let _ = stopped.await;
+        },
+        Duration::from_millis(200),
+    ));
+    let channel = common::connect_unix(&path).await;
+    let mut watch = common::server_streaming::<_, grpc_health_v1::HealthCheckResponse>(
+        &channel,
+        "/grpc.health.v1.Health/Watch",
+        HealthCheckRequest { service: String::new() },
+    )
+    .await
+    .unwrap();
+    let first = watch.message().await.unwrap().unwrap();
+    assert_eq!(first.status, ServingStatus::Serving as i32);
+
+    stop.send(()).unwrap();
+    let next = tokio::time::timeout(Duration::from_secs(2), watch.message()).await.unwrap();
+    assert_eq!(next.unwrap().unwrap().status, ServingStatus::NotServing as i32);
+    server.await.unwrap().unwrap();
+}
+
+async fn next(
+    responses: &mut tonic::Streaming<grpc_reflection_v1alpha::ServerReflectionResponse>,
+) -> grpc_reflection_v1alpha::server_reflection_response::MessageResponse {
+    responses.message().await.unwrap().unwrap().message_response.unwrap()
+}
+
+#[tokio::test]
+async fn reflection_lists_and_describes_the_services() {
+    use grpc_reflection_v1alpha::server_reflection_request::MessageRequest;
+    use grpc_reflection_v1alpha::server_reflection_response::MessageResponse;
+    use grpc_reflection_v1alpha::{ServerReflectionRequest, ServerReflectionResponse};
+
+    let path = common::temp_path("reflection.sock");
+    let server = tokio::spawn(Arc::new(Served).serve_unix(path.clone()));
+    let channel = common::connect_unix(&path).await;
+
+    let requests = [
+        MessageRequest::ListServices(String::new()),
+        MessageRequest::FileContainingSymbol("runtime.v1.ImageService".to_string()),
+        MessageRequest::FileContainingSymbol("runtime.v1.NoSuchService".to_string()),
+    ]
+    .map(|it| ServerReflectionRequest { host: String::new(), message_request: Some(it) });
+    let mut grpc = tonic::client::Grpc::new(channel);
+    grpc.ready().await.unwrap();
+    let mut responses = grpc
+        .streaming::<_, _, ServerReflectionResponse, _>(
+            Request::new(tokio_stream::iter(requests)),
+            PathAndQuery::from_static("/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo"),
+            tonic::codec::ProstCodec::default(),
+        )
+        .await
+        .unwrap()
+        .into_inner();
+
+    match next(&mut responses).await {
+        MessageResponse::ListServicesResponse(it) => {
+            let mut names = it.service.into_iter().map(|it| it.name).collect::<Vec<_>>();
+            names.sort();
+            assert_eq!(
+                names,
+                [
+                    "grpc.health.v1.Health",
+                    "grpc.reflection.v1alpha.ServerReflection",
+                    "runtime.v1.ImageService",
+                    "runtime.v1alpha2.ImageService",
+                ]
+            );
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    match next(&mut responses).await {
+        MessageResponse::FileDescriptorResponse(it) => assert!(!it.file_descriptor_proto.is_empty()),
+        other => panic!("unexpected {:?}", other),
+    }
+    match next(&mut responses).await {
+        MessageResponse::ErrorResponse(it) => assert_eq!(it.error_code, tonic::Code::NotFound as i32),
+        other => panic!("unexpected {:?}", other),
+    }
+    server.abort();
+}
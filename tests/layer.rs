@@ -0,0 +1,90 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request, Response, Status};
+
+#[macro_use]
+mod common;
+
+/// Rejects filtered listings and counts the streams handed out.
+#[derive(Default)]
+pub struct Guard {
+    streams: AtomicUsize,
+}
+
+impl v1::ImageServiceHooks for Guard {
+    fn before_list_images(&self, request: &mut Request<v1::ListImagesRequest>) -> Result<(), Status> {
+        match request.get_ref().filter {
+            Some(_) => Err(Status::invalid_argument("filters are not supported")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl v1::RuntimeServiceHooks for Guard {
+    fn after_get_container_events(&self, response: &Result<Response<GetContainerEventsStream>, Status>) {
+        if response.is_ok() {
+            self.streams.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl v1alpha2::ImageServiceHooks for Guard {}
+impl v1alpha2::RuntimeServiceHooks for Guard {}
+
+fixture! {
+    runtime;
+    // hooks see the struct's own stream, not the one `server` wraps to end on drain
+    #[auto_impl(runtime, image, server, layer = Guard)]
+    pub struct Hooked {
+        guard: Guard,
+    }
+}
+
+fn hooked() -> Hooked {
+    Hooked { guard: Guard::default() }
+}
+
+#[tokio::test]
+async fn before_hooks_reject_calls() {
+    let hooked = hooked();
+    ImageService_v1::list_images(&hooked, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+
+    let filtered = v1::ListImagesRequest {
+        filter: Some(v1::ImageFilter::default()),
+    };
+    let status = ImageService_v1::list_images(&hooked, Request::new(filtered)).await.unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+    assert_eq!(status.message(), "filters are not supported");
+}
+
+#[tokio::test]
+async fn after_hooks_see_streams_served_over_the_socket() {
+    let path = common::temp_path("layer.sock");
+    let served = Arc::new(hooked());
+    tokio::spawn(served.clone().serve_unix(path.clone()));
+    let channel = common::connect_unix(&path).await;
+
+    let _events = common::server_streaming::<_, v1::ContainerEventResponse>(
+        &channel,
+        "/runtime.v1.RuntimeService/GetContainerEvents",
+        v1::GetEventsRequest::default(),
+    )
+    .await
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(served.guard.streams.load(Ordering::SeqCst), 1);
+
+    // the v1alpha2 hooks are the no-op defaults
+    let _: v1alpha2::ListImagesResponse = common::unary(
+        &channel,
+        "/runtime.v1alpha2.ImageService/ListImages",
+        v1alpha2::ListImagesRequest {
+            filter: Some(v1alpha2::ImageFilter::default()),
+        },
+    )
+    .await
+    .unwrap();
+}
@@ -0,0 +1,61 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request};
+
+#[macro_use]
+mod common;
+
+// `image_fs_info` of the fixtures takes 100ms
+fixture! {
+    #[auto_impl(image, limits(image_fs_info(timeout = "50ms")))]
+    pub struct TimedOut;
+}
+
+mod concurrency {
+    use auto_impl_trait::auto_impl;
+
+    fixture! {
+        #[auto_impl(image, limits(image_fs_info(concurrency = 1)))]
+        pub struct Limited;
+    }
+}
+
+use concurrency::v1::image_service_server::ImageService as Limited_v1;
+use concurrency::v1alpha2::image_service_server::ImageService as Limited_v1alpha2;
+use concurrency::Limited;
+
+#[tokio::test]
+async fn timeout_fails_with_deadline_exceeded() {
+    let request = Request::new(v1::ImageFsInfoRequest::default());
+    let e = ImageService_v1::image_fs_info(&TimedOut, request).await.unwrap_err();
+    assert_eq!(e.code(), Code::DeadlineExceeded);
+    assert_eq!(e.message(), "image_fs_info exceeded its timeout of 50ms");
+
+    // methods without a limit take as long as they need
+    let request = Request::new(v1::ListImagesRequest::default());
+    ImageService_v1::list_images(&TimedOut, request).await.unwrap();
+}
+
+#[tokio::test]
+async fn concurrency_is_shared_by_both_versions() {
+    let limited = Arc::new(Limited);
+    let running = tokio::spawn({
+        let limited = limited.clone();
+        async move { Limited_v1::image_fs_info(&*limited, Request::new(concurrency::v1::ImageFsInfoRequest::default())).await }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let request = Request::new(concurrency::v1::ImageFsInfoRequest::default());
+    let e = Limited_v1::image_fs_info(&*limited, request).await.unwrap_err();
+    assert_eq!(e.code(), Code::ResourceExhausted);
+    assert_eq!(e.message(), "image_fs_info is limited to 1 concurrent calls");
+    let request = Request::new(concurrency::v1alpha2::ImageFsInfoRequest::default());
+    let e = Limited_v1alpha2::image_fs_info(&*limited, request).await.unwrap_err();
+    assert_eq!(e.code(), Code::ResourceExhausted);
+
+    running.await.unwrap().unwrap();
+    let request = Request::new(concurrency::v1alpha2::ImageFsInfoRequest::default());
+    Limited_v1alpha2::image_fs_info(&*limited, request).await.unwrap();
+}
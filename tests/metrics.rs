@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use auto_impl_trait::auto_impl;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tonic::Request;
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, metrics)]
+    pub struct Counted;
+}
+
+fn has_line(rendered: &str, line: &str) -> bool {
+    rendered.lines().any(|it| it == line)
+}
+
+// one test, `CRI_METRICS` is shared by every call of the binary
+#[tokio::test]
+async fn counts_calls_and_serves_them_over_http() {
+    ImageService_v1::list_images(&Counted, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+    ImageService_v1::list_images(&Counted, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+    ImageService_v1alpha2::remove_image(&Counted, Request::new(v1alpha2::RemoveImageRequest::default()))
+        .await
+        .unwrap_err();
+    let slow = tokio::spawn(async {
+        ImageService_v1::image_fs_info(&Counted, Request::new(v1::ImageFsInfoRequest {})).await
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let rendered = CRI_METRICS.render();
+    let v1_list = r#"service="ImageService",method="list_images",version="v1""#;
+    let v1alpha2_remove = r#"service="ImageService",method="remove_image",version="v1alpha2""#;
+    let v1_fs_info = r#"service="ImageService",method="image_fs_info",version="v1""#;
+    assert!(has_line(&rendered, &format!("cri_requests_total{{{}}} 2", v1_list)), "{}", rendered);
+    assert!(has_line(&rendered, &format!("cri_errors_total{{{},code=\"NotFound\"}} 1", v1alpha2_remove)));
+    assert!(!rendered.contains(&format!("cri_errors_total{{{}", v1_list)));
+    assert!(has_line(&rendered, &format!("cri_requests_in_flight{{{}}} 1", v1_fs_info)));
+    assert!(has_line(&rendered, &format!("cri_request_duration_seconds_count{{{}}} 2", v1_list)));
+
+    slow.await.unwrap().unwrap();
+    assert!(has_line(&CRI_METRICS.render(), &format!("cri_requests_in_flight{{{}}} 0", v1_fs_info)));
+
+    let path = common::temp_path("metrics.sock");
+    tokio::spawn(CRI_METRICS.serve_unix(path.clone()));
+    let mut stream = loop {
+        match tokio::net::UnixStream::connect(&path).await {
+            Ok(it) => break it,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nhost: cri\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("content-type: text/plain; version=0.0.4\r\n"));
+    assert!(has_line(&response, &format!("cri_requests_total{{{}}} 2", v1_list)));
+}
+
+#[tokio::test]
+async fn other_files_are_not_replaced() {
+    let path = common::temp_path("metrics-file.sock");
+    std::fs::write(&path, "data").unwrap();
+    let error = CRI_METRICS.serve_unix(path.clone()).await.unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(error.to_string(), format!("{} exists and is not a socket", path.display()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+}
@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use auto_impl_trait::auto_impl;
+use tonic::{Request, Status};
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, mock, mirror, mutating = [image_fs_info])]
+    pub struct Images;
+}
+
+use v1::image_service_server::ImageService as V1;
+
+/// Keeps the `diff` field of every `cri` event.
+#[derive(Clone, Default)]
+struct Diffs(Arc<Mutex<Vec<String>>>);
+
+impl Visit for Diffs {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "diff" {
+            self.0.lock().unwrap().push(format!("{:?}", value));
+        }
+    }
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Diffs {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "cri" {
+            event.record(&mut self.clone());
+        }
+    }
+}
+
+fn images(ids: &[&str]) -> v1::ListImagesResponse {
+    v1::ListImagesResponse {
+        images: ids
+            .iter()
+            .map(|id| v1::Image {
+                id: id.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+/// Waits for the shadow calls the mirror sent in the background.
+async fn shadow_calls(shadow: &MockImages, count: usize) {
+    for _ in 0..100 {
+        if shadow.calls().len() >= count {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    // lets the mirror compare the answers
+    tokio::time::sleep(Duration::from_millis(10)).await;
+}
+
+#[tokio::test]
+async fn read_only_calls_are_compared_with_the_shadow() {
+    let diffs = Diffs::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(diffs.clone()));
+
+    let (primary, shadow) = (MockImages::new(), MockImages::new());
+    let mirror = CriMirror::new(primary.clone(), shadow.clone());
+    primary.v1_list_images().returns(Ok(images(&["a", "b"]))).returns(Ok(images(&["a"])));
+    shadow.v1_list_images().returns(Ok(images(&["a", "c"]))).returns(Ok(images(&["a"])));
+
+    // the primary answers, whatever the shadow says
+    let response = V1::list_images(&mirror, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+    assert_eq!(response.into_inner(), images(&["a", "b"]));
+    shadow_calls(&shadow, 1).await;
+    assert_eq!(*diffs.0.lock().unwrap(), [r#"images[1].id: "b" != "c""#]);
+
+    V1::list_images(&mirror, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+    shadow_calls(&shadow, 2).await;
+    assert_eq!(diffs.0.lock().unwrap().len(), 1);
+
+    shadow.v1_image_status().returns(Err(Status::unavailable("starting")));
+    V1::image_status(&mirror, Request::new(v1::ImageStatusRequest::default())).await.unwrap();
+    shadow_calls(&shadow, 3).await;
+    assert_eq!(diffs.0.lock().unwrap()[1], "shadow failed with Unavailable: starting");
+}
+
+#[tokio::test]
+async fn mutating_calls_only_reach_the_primary() {
+    let (primary, shadow) = (MockImages::new(), MockImages::new());
+    let mirror = CriMirror::new(primary.clone(), shadow.clone());
+
+    V1::pull_image(&mirror, Request::new(v1::PullImageRequest::default())).await.unwrap();
+    // read-only by default, made mutating by the option
+    V1::image_fs_info(&mirror, Request::new(v1::ImageFsInfoRequest::default())).await.unwrap();
+    V1::list_images(&mirror, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+    shadow_calls(&shadow, 1).await;
+
+    primary.expect_called("pull_image").times(1);
+    primary.expect_called("image_fs_info").times(1);
+    assert_eq!(shadow.calls().iter().map(|it| it.method).collect::<Vec<_>>(), ["list_images"]);
+}
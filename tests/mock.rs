@@ -0,0 +1,95 @@
+// scripted responses return `tonic::Status` as the generated traits do
+#![allow(clippy::result_large_err)]
+
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request, Status};
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, mock)]
+    pub struct Images;
+}
+
+use v1::image_service_server::ImageService as V1;
+use v1alpha2::image_service_server::ImageService as V1alpha2;
+
+fn status_request(image: &str) -> Request<v1::ImageStatusRequest> {
+    Request::new(v1::ImageStatusRequest {
+        image: Some(v1::ImageSpec {
+            image: image.to_string(),
+            ..Default::default()
+        }),
+        verbose: false,
+    })
+}
+
+fn image(id: &str) -> v1::ImageStatusResponse {
+    v1::ImageStatusResponse {
+        image: Some(v1::Image {
+            id: id.to_string(),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn scripted_responses() {
+    let mock = MockImages::new();
+    mock.v1_image_status()
+        .returns(Ok(image("first")))
+        .returns(Err(Status::not_found("gone")))
+        .returns_with(|request| Ok(image(&request.image.as_ref().unwrap().image)));
+
+    // queued responses come first, then the closure
+    let id = |response: v1::ImageStatusResponse| response.image.unwrap().id;
+    assert_eq!(id(V1::image_status(&mock, status_request("a")).await.unwrap().into_inner()), "first");
+    assert_eq!(V1::image_status(&mock, status_request("b")).await.unwrap_err().code(), Code::NotFound);
+    assert_eq!(id(V1::image_status(&mock, status_request("c")).await.unwrap().into_inner()), "c");
+    assert_eq!(id(V1::image_status(&mock, status_request("d")).await.unwrap().into_inner()), "d");
+
+    // the versions are scripted apart
+    let request = Request::new(v1alpha2::ImageStatusRequest::default());
+    assert_eq!(V1alpha2::image_status(&mock, request).await.unwrap().into_inner(), Default::default());
+
+    mock.v1_image_status().returns_default();
+    assert_eq!(V1::image_status(&mock, status_request("e")).await.unwrap().into_inner(), Default::default());
+
+    let images = mock.v1_image_status().calls().into_iter().map(|it| it.image.unwrap().image).collect::<Vec<_>>();
+    assert_eq!(images, ["a", "b", "c", "d", "e"]);
+    assert_eq!(mock.v1alpha2_image_status().calls().len(), 1);
+}
+
+#[tokio::test]
+async fn expectations_count_calls_of_every_version() {
+    let mock = MockImages::new();
+    V1::pull_image(&mock, Request::new(v1::PullImageRequest::default())).await.unwrap();
+    V1alpha2::pull_image(&mock, Request::new(v1alpha2::PullImageRequest::default())).await.unwrap();
+    V1::list_images(&mock, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+
+    mock.expect_called("pull_image").times(2);
+    mock.expect_called("pull_image").at_least(1);
+    mock.expect_called("list_images").times(1);
+    mock.expect_called("remove_image").never();
+
+    let calls = mock.calls();
+    assert_eq!(
+        calls.iter().map(|it| (it.version, it.method)).collect::<Vec<_>>(),
+        [("v1", "pull_image"), ("v1alpha2", "pull_image"), ("v1", "list_images")]
+    );
+    assert!(calls[1].request::<v1alpha2::PullImageRequest>().is_some());
+    assert!(calls[1].request::<v1::PullImageRequest>().is_none());
+
+    mock.reset();
+    mock.expect_called("pull_image").never();
+}
+
+#[tokio::test]
+#[should_panic(expected = "expected `pull_image` to be called 2 times")]
+async fn wrong_count_panics() {
+    let mock = MockImages::new();
+    V1::pull_image(&mock, Request::new(v1::PullImageRequest::default())).await.unwrap();
+    mock.expect_called("pull_image").times(2);
+}
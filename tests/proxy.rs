@@ -0,0 +1,145 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request, Status};
+
+#[macro_use]
+mod common;
+
+/// Counts the listings that reached the upstream.
+#[derive(Default)]
+pub struct Forwarded(AtomicUsize);
+
+impl v1::ImageServiceHooks for Forwarded {
+    fn before_list_images(&self, _request: &mut Request<v1::ListImagesRequest>) -> Result<(), Status> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl v1alpha2::ImageServiceHooks for Forwarded {}
+impl v1::RuntimeServiceHooks for Forwarded {}
+impl v1alpha2::RuntimeServiceHooks for Forwarded {}
+
+fixture! {
+    runtime;
+    #[auto_impl(runtime, image, server, layer = Forwarded)]
+    pub struct Upstream {
+        forwarded: Forwarded,
+    }
+}
+
+/// Serves a new `Upstream` on the socket `name`.
+async fn upstream(name: &str) -> (Arc<Upstream>, std::path::PathBuf) {
+    let path = common::temp_path(name);
+    let upstream = Arc::new(Upstream { forwarded: Forwarded::default() });
+    tokio::spawn(upstream.clone().serve_unix(path.clone()));
+    // waits for it to listen
+    common::connect_unix(&path).await;
+    (upstream, path)
+}
+
+mod shim {
+    use auto_impl_trait::auto_impl;
+    use tonic::{Request, Response, Status};
+
+    #[auto_impl(image, proxy)]
+    pub struct Shim {
+        pub upstream: CriUpstream,
+    }
+
+    #[tonic::async_trait]
+    impl v1::ImageServiceProxy for Shim {
+        async fn remove_image(&self, _request: Request<v1::RemoveImageRequest>) -> Result<Response<v1::RemoveImageResponse>, Status> {
+            Err(Status::permission_denied("images are read-only"))
+        }
+    }
+
+    impl v1alpha2::ImageServiceProxy for Shim {}
+}
+
+use shim::v1::image_service_server::ImageService as ShimV1;
+use shim::v1alpha2::image_service_server::ImageService as ShimV1alpha2;
+
+#[tokio::test]
+async fn calls_are_forwarded_unless_overridden() {
+    let (upstream, path) = upstream("proxy-upstream.sock").await;
+    let shim = shim::Shim {
+        upstream: shim::CriUpstream::connect_unix(&path).await.unwrap(),
+    };
+
+    ShimV1::list_images(&shim, Request::new(shim::v1::ListImagesRequest::default())).await.unwrap();
+    ShimV1alpha2::list_images(&shim, Request::new(shim::v1alpha2::ListImagesRequest::default())).await.unwrap();
+    assert_eq!(upstream.forwarded.0.load(Ordering::SeqCst), 1);
+
+    // the upstream's answer comes back as is
+    let e = ShimV1alpha2::remove_image(&shim, Request::new(shim::v1alpha2::RemoveImageRequest::default())).await.unwrap_err();
+    assert_eq!(e.code(), Code::NotFound);
+    assert_eq!(e.message(), "image gone");
+    let e = ShimV1::remove_image(&shim, Request::new(shim::v1::RemoveImageRequest::default())).await.unwrap_err();
+    assert_eq!(e.code(), Code::PermissionDenied);
+}
+
+mod served {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use auto_impl_trait::auto_impl;
+    use tonic::{Response, Status};
+
+    /// Counts the upstream streams handed out.
+    #[derive(Default)]
+    pub struct Streams(pub AtomicUsize);
+
+    impl v1::RuntimeServiceHooks for Streams {
+        fn after_get_container_events(
+            &self,
+            response: &Result<Response<CriProxyStream<v1::ContainerEventResponse>>, Status>,
+        ) {
+            if response.is_ok() {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    impl v1alpha2::RuntimeServiceHooks for Streams {}
+    impl v1::ImageServiceHooks for Streams {}
+    impl v1alpha2::ImageServiceHooks for Streams {}
+
+    // the hooks see the proxied stream, not the one `server` wraps to end on drain
+    #[auto_impl(runtime, image, proxy, server, layer = Streams)]
+    pub struct Served {
+        pub upstream: CriUpstream,
+        pub streams: Streams,
+    }
+
+    impl v1::RuntimeServiceProxy for Served {}
+    impl v1alpha2::RuntimeServiceProxy for Served {}
+    impl v1::ImageServiceProxy for Served {}
+    impl v1alpha2::ImageServiceProxy for Served {}
+}
+
+#[tokio::test]
+async fn served_proxies_forward_streams_through_the_hooks() {
+    let (upstream, upstream_path) = upstream("proxy-served-upstream.sock").await;
+    let served = Arc::new(served::Served {
+        upstream: served::CriUpstream::connect_unix(&upstream_path).await.unwrap(),
+        streams: served::Streams::default(),
+    });
+    let path = common::temp_path("proxy-served.sock");
+    tokio::spawn(served.clone().serve_unix(path.clone()));
+    let channel = common::connect_unix(&path).await;
+
+    let _: v1::ListImagesResponse =
+        common::unary(&channel, "/runtime.v1.ImageService/ListImages", v1::ListImagesRequest::default()).await.unwrap();
+    assert_eq!(upstream.forwarded.0.load(Ordering::SeqCst), 1);
+
+    let _events = common::server_streaming::<_, v1::ContainerEventResponse>(
+        &channel,
+        "/runtime.v1.RuntimeService/GetContainerEvents",
+        v1::GetEventsRequest::default(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(served.streams.0.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,139 @@
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request};
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, record, mock)]
+    pub struct Recorded {
+        recorder: CriRecorder,
+    }
+}
+
+use v1::image_service_server::ImageService as V1;
+use v1alpha2::image_service_server::ImageService as V1alpha2;
+
+/// Records a session of three calls at `name`.
+async fn session(name: &str) -> CriRecording {
+    let path = common::temp_path(name);
+    let recorded = Recorded {
+        recorder: CriRecorder::create(&path).unwrap(),
+    };
+
+    let request = v1::ListImagesRequest {
+        filter: Some(v1::ImageFilter {
+            image: Some(v1::ImageSpec {
+                image: "busybox".to_string(),
+                ..Default::default()
+            }),
+        }),
+    };
+    V1::list_images(&recorded, Request::new(request)).await.unwrap();
+    V1::remove_image(&recorded, Request::new(v1::RemoveImageRequest::default())).await.unwrap_err();
+    V1alpha2::image_fs_info(&recorded, Request::new(v1alpha2::ImageFsInfoRequest::default())).await.unwrap();
+
+    CriRecording::open(&path).unwrap()
+}
+
+#[tokio::test]
+async fn every_call_is_recorded() {
+    let recording = session("record-session").await;
+
+    let calls = recording
+        .records
+        .iter()
+        .map(|it| (it.version.as_str(), it.service.as_str(), it.method.as_str(), Code::from_i32(it.code)))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        calls,
+        [
+            ("v1", "ImageService", "list_images", Code::Ok),
+            ("v1", "ImageService", "remove_image", Code::NotFound),
+            ("v1alpha2", "ImageService", "image_fs_info", Code::Ok),
+        ]
+    );
+
+    let request = <v1::ListImagesRequest as prost::Message>::decode(recording.records[0].request.as_slice()).unwrap();
+    assert_eq!(request.filter.unwrap().image.unwrap().image, "busybox");
+    assert_eq!(recording.records[0].response.as_deref(), Some(&[][..]));
+    assert_eq!(recording.records[1].response, None);
+    assert_eq!(recording.records[1].message, "image gone");
+    // `image_fs_info` of the fixture takes 100ms
+    assert!(recording.records[2].duration >= 100_000_000);
+    assert!(recording.records[2].started_at >= recording.records[0].started_at);
+}
+
+#[tokio::test]
+async fn replay_reports_divergences() {
+    let recording = session("record-replay").await;
+
+    let mock = MockRecorded::new();
+    mock.v1_remove_image().returns(Err(tonic::Status::not_found("gone too")));
+    assert!(recording.replay(&mock).await.is_empty());
+    let calls = mock.v1_list_images().calls();
+    assert_eq!(calls[0].filter.as_ref().unwrap().image.as_ref().unwrap().image, "busybox");
+
+    let mock = MockRecorded::new();
+    mock.v1_list_images().returns(Ok(v1::ListImagesResponse {
+        images: vec![v1::Image::default()],
+    }));
+    let divergences = recording.replay(&mock).await;
+    assert_eq!(divergences.len(), 2);
+    assert_eq!(divergences[0].index, 0);
+    assert!(divergences[0].reason.starts_with("recorded ListImagesResponse { images: [] }, replayed"));
+    assert_eq!(
+        divergences[1].to_string(),
+        "#1 runtime.v1.ImageService/remove_image: recorded NotFound, replayed Ok"
+    );
+}
+
+mod runtime {
+    use auto_impl_trait::auto_impl;
+
+    fixture! {
+        runtime;
+        #[auto_impl(runtime, image, record)]
+        pub struct Recorded {
+            pub recorder: CriRecorder,
+        }
+    }
+}
+
+#[tokio::test]
+async fn server_streams_are_replayed_by_their_code() {
+    use runtime::v1::runtime_service_server::RuntimeService as V1Runtime;
+
+    let path = common::temp_path("record-runtime");
+    let recorded = runtime::Recorded {
+        recorder: runtime::CriRecorder::create(&path).unwrap(),
+    };
+    V1Runtime::version(&recorded, Request::new(runtime::v1::VersionRequest::default())).await.unwrap();
+    let _events = V1Runtime::get_container_events(&recorded, Request::new(runtime::v1::GetEventsRequest::default()))
+        .await
+        .unwrap();
+
+    let recording = runtime::CriRecording::open(&path).unwrap();
+    assert_eq!(recording.records[1].method, "get_container_events");
+    assert_eq!(recording.records[1].response, None);
+
+    let replayed = runtime::Recorded {
+        recorder: runtime::CriRecorder::create(common::temp_path("record-runtime-replayed")).unwrap(),
+    };
+    assert!(recording.replay(&replayed).await.is_empty());
+}
+
+#[test]
+fn recordings_are_only_readable_by_their_owner() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    let path = common::temp_path("record-mode");
+    CriRecorder::create(&path).unwrap();
+    assert_eq!(mode(&path), 0o600);
+
+    // replaced recordings too
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    CriRecorder::create(&path).unwrap();
+    assert_eq!(mode(&path), 0o600);
+}
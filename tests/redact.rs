@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex};
+
+use auto_impl_trait::auto_impl;
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, log)]
+    pub struct Logged;
+}
+
+mod custom {
+    use auto_impl_trait::auto_impl;
+
+    fixture! {
+        #[auto_impl(image, log, redact = [AuthConfig.server_address, annotations])]
+        pub struct Logged;
+    }
+}
+
+fn auth() -> v1::AuthConfig {
+    v1::AuthConfig {
+        username: "user".to_string(),
+        password: "secret".to_string(),
+        auth: String::new(),
+        server_address: "registry.example.com".to_string(),
+        identity_token: "token".to_string(),
+        registry_token: String::new(),
+    }
+}
+
+#[test]
+fn credentials_and_envs_are_redacted_by_default() {
+    let mut request = v1::PullImageRequest {
+        image: Some(v1::ImageSpec {
+            image: "busybox".to_string(),
+            annotations: [("owner".to_string(), "me".to_string())].into(),
+        }),
+        auth: Some(auth()),
+        sandbox_config: None,
+    };
+    CriRedact::redact(&mut request);
+    let auth = request.auth.unwrap();
+    assert_eq!(auth.username, "[REDACTED]");
+    assert_eq!(auth.password, "[REDACTED]");
+    assert_eq!(auth.identity_token, "[REDACTED]");
+    // empty fields stay empty, so the log tells them apart
+    assert_eq!(auth.auth, "");
+    assert_eq!(auth.registry_token, "");
+    assert_eq!(auth.server_address, "registry.example.com");
+    assert_eq!(request.image.unwrap().annotations["owner"], "me");
+
+    let mut config = v1alpha2::ContainerConfig {
+        envs: vec![v1alpha2::KeyValue {
+            key: "TOKEN".to_string(),
+            value: "secret".to_string(),
+        }],
+        ..Default::default()
+    };
+    CriRedact::redact(&mut config);
+    assert_eq!(config.envs[0].key, "TOKEN");
+    assert_eq!(config.envs[0].value, "[REDACTED]");
+}
+
+#[test]
+fn redact_replaces_the_defaults() {
+    let mut request = custom::v1::PullImageRequest {
+        image: Some(custom::v1::ImageSpec {
+            image: "busybox".to_string(),
+            annotations: [("owner".to_string(), "me".to_string())].into(),
+        }),
+        auth: Some(custom::v1::AuthConfig {
+            password: "secret".to_string(),
+            server_address: "registry.example.com".to_string(),
+            ..Default::default()
+        }),
+        sandbox_config: None,
+    };
+    custom::CriRedact::redact(&mut request);
+    let auth = request.auth.unwrap();
+    assert_eq!(auth.password, "secret");
+    assert_eq!(auth.server_address, "[REDACTED]");
+    assert_eq!(request.image.unwrap().annotations["owner"], "[REDACTED]");
+}
+
+/// Keeps the `request` and `response` fields of every `cri` event.
+#[derive(Clone, Default)]
+struct Events(Arc<Mutex<Vec<String>>>);
+
+impl Visit for Events {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "request" || field.name() == "response" {
+            self.0.lock().unwrap().push(format!("{}: {:?}", field.name(), value));
+        }
+    }
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Events {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "cri" {
+            event.record(&mut self.clone());
+        }
+    }
+}
+
+#[tokio::test]
+async fn logged_calls_hide_the_credentials() {
+    let events = Events::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(events.clone()));
+
+    let request = tonic::Request::new(v1::PullImageRequest {
+        image: Some(v1::ImageSpec {
+            image: "busybox".to_string(),
+            ..Default::default()
+        }),
+        auth: Some(auth()),
+        ..Default::default()
+    });
+    ImageService_v1::pull_image(&Logged, request).await.unwrap();
+
+    let events = events.0.lock().unwrap();
+    assert_eq!(events.len(), 2);
+    assert!(events[0].starts_with("request: PullImageRequest"));
+    assert!(events[0].contains("busybox"));
+    assert!(events[0].contains("registry.example.com"));
+    assert!(!events[0].contains("secret"));
+    assert!(!events[0].contains("\"token\""));
+    assert!(events[1].starts_with("response: PullImageResponse"));
+}
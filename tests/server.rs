@@ -0,0 +1,72 @@
+use std::os::unix::fs::PermissionsExt;
+use std::sync::Arc;
+
+use auto_impl_trait::auto_impl;
+use tonic::Code;
+
+#[macro_use]
+mod common;
+
+fixture! {
+    #[auto_impl(image, server)]
+    pub struct Served;
+}
+
+mod private {
+    use auto_impl_trait::auto_impl;
+
+    fixture! {
+        #[auto_impl(image, server, socket_mode = 0o600)]
+        pub struct Served;
+    }
+}
+
+fn mode(path: &std::path::Path) -> u32 {
+    std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[tokio::test]
+async fn serves_both_versions_on_a_unix_socket() {
+    let path = common::temp_path("server").join("cri.sock");
+    // a socket left behind by a previous run
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+    tokio::spawn(Arc::new(Served).serve_unix(path.clone()));
+    let channel = common::connect_unix(&path).await;
+    assert_eq!(mode(&path), 0o660);
+
+    let images: v1::ListImagesResponse =
+        common::unary(&channel, "/runtime.v1.ImageService/ListImages", v1::ListImagesRequest::default()).await.unwrap();
+    assert_eq!(images, Default::default());
+    let status = common::unary::<_, v1alpha2::RemoveImageResponse>(
+        &channel,
+        "/runtime.v1alpha2.ImageService/RemoveImage",
+        v1alpha2::RemoveImageRequest::default(),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!((status.code(), status.message()), (Code::NotFound, "image gone"));
+    // only the requested services are served
+    let status = common::unary::<_, v1::VersionResponse>(&channel, "/runtime.v1.RuntimeService/Version", v1::VersionRequest::default())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unimplemented);
+}
+
+#[tokio::test]
+async fn socket_mode_sets_the_permissions() {
+    let path = common::temp_path("server-mode.sock");
+    tokio::spawn(Arc::new(private::Served).serve_unix(path.clone()));
+    common::connect_unix(&path).await;
+    assert_eq!(mode(&path), 0o600);
+}
+
+#[tokio::test]
+async fn other_files_are_not_replaced() {
+    let path = common::temp_path("server-file.sock");
+    std::fs::write(&path, "data").unwrap();
+    let error = Arc::new(Served).serve_unix(path.clone()).await.unwrap_err();
+    assert_eq!(error.to_string(), format!("{} exists and is not a socket", path.display()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "data");
+}
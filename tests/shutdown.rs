@@ -0,0 +1,66 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use auto_impl_trait::auto_impl;
+
+#[macro_use]
+mod common;
+
+fixture! {
+    runtime;
+    #[auto_impl(runtime, image, server)]
+    pub struct Served;
+}
+
+#[tokio::test]
+async fn shutdown_ends_event_streams_and_removes_the_socket() {
+    let path = common::temp_path("shutdown.sock");
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(Arc::new(Served).serve_unix_with_shutdown(
+        path.clone(),
+        async {
+            let _ = stopped.await;
+        },
+        Duration::from_secs(5),
+    ));
+    let channel = common::connect_unix(&path).await;
+    let mut events = common::server_streaming::<_, v1::ContainerEventResponse>(
+        &channel,
+        "/runtime.v1.RuntimeService/GetContainerEvents",
+        v1::GetEventsRequest::default(),
+    )
+    .await
+    .unwrap();
+
+    stop.send(()).unwrap();
+    // the stream ends cleanly rather than being cut off after the grace period
+    let end = tokio::time::timeout(Duration::from_secs(2), events.message()).await.unwrap();
+    assert!(end.unwrap().is_none());
+    tokio::time::timeout(Duration::from_secs(2), server).await.unwrap().unwrap().unwrap();
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn in_flight_calls_get_the_grace_period() {
+    let path = common::temp_path("shutdown-grace.sock");
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(Arc::new(Served).serve_unix_with_shutdown(
+        path.clone(),
+        async {
+            let _ = stopped.await;
+        },
+        Duration::from_secs(5),
+    ));
+    let channel = common::connect_unix(&path).await;
+
+    // `image_fs_info` takes 100ms, the shutdown starts while it runs
+    let call = tokio::spawn(async move {
+        common::unary::<_, v1::ImageFsInfoResponse>(&channel, "/runtime.v1.ImageService/ImageFsInfo", v1::ImageFsInfoRequest {})
+            .await
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    stop.send(()).unwrap();
+    call.await.unwrap().unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use auto_impl_trait::auto_impl;
+use tonic::Request;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+#[macro_use]
+mod common;
+
+fixture! {
+    runtime;
+    #[auto_impl(runtime, image, tracing)]
+    pub struct Traced;
+}
+
+type Fields = BTreeMap<String, String>;
+
+/// Collects the fields of every `cri` span, in creation order.
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<Vec<Fields>>>);
+
+struct Collect<'a>(&'a mut Fields);
+
+impl Visit for Collect<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+struct Index(usize);
+
+impl<S: tracing::Subscriber + for<'a> LookupSpan<'a>> tracing_subscriber::Layer<S> for Spans {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "cri" {
+            return;
+        }
+        let mut fields = Fields::new();
+        attrs.record(&mut Collect(&mut fields));
+        let mut spans = self.0.lock().unwrap();
+        ctx.span(id).unwrap().extensions_mut().insert(Index(spans.len()));
+        spans.push(fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(Index(index)) = ctx.span(id).unwrap().extensions().get::<Index>() {
+            values.record(&mut Collect(&mut self.0.lock().unwrap()[*index]));
+        }
+    }
+}
+
+fn fields(pairs: &[(&str, &str)]) -> Fields {
+    pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+#[tokio::test]
+async fn every_call_runs_in_a_cri_span() {
+    let spans = Spans::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+    let request = v1::RemoveImageRequest {
+        image: Some(v1::ImageSpec {
+            image: "busybox".to_string(),
+            ..Default::default()
+        }),
+    };
+    ImageService_v1::remove_image(&Traced, Request::new(request)).await.unwrap_err();
+    let request = v1alpha2::StopContainerRequest {
+        container_id: "c1".to_string(),
+        timeout: 0,
+    };
+    RuntimeService_v1alpha2::stop_container(&Traced, Request::new(request)).await.unwrap();
+    ImageService_v1::image_fs_info(&Traced, Request::new(v1::ImageFsInfoRequest {})).await.unwrap();
+
+    assert_eq!(
+        *spans.0.lock().unwrap(),
+        [
+            fields(&[
+                ("method", "remove_image"),
+                ("service", "ImageService"),
+                ("version", "v1"),
+                ("image", "busybox"),
+                ("code", "NotFound"),
+            ]),
+            fields(&[
+                ("method", "stop_container"),
+                ("service", "RuntimeService"),
+                ("version", "v1alpha2"),
+                ("container_id", "c1"),
+                ("code", "Ok"),
+            ]),
+            fields(&[
+                ("method", "image_fs_info"),
+                ("service", "ImageService"),
+                ("version", "v1"),
+                ("code", "Ok"),
+            ]),
+        ]
+    );
+}
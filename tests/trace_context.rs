@@ -0,0 +1,139 @@
+use std::sync::{Arc, Mutex};
+
+use auto_impl_trait::auto_impl;
+use opentelemetry::trace::{TraceContextExt, TracerProvider};
+use tonic::metadata::MetadataMap;
+use tonic::Request;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+#[macro_use]
+mod common;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+/// `traceparent` the `list_images` handler would send to the calls it makes.
+static OUTGOING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// `traceparent` the `image_status` handler received.
+static INCOMING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+async fn answer<Req>(method: &str, request: Request<Req>) -> Result<(), tonic::Status> {
+    let traceparent = |metadata: &MetadataMap| {
+        let traceparent = metadata.get("traceparent").map(|it| it.to_str().unwrap().to_string());
+        traceparent.unwrap_or_default()
+    };
+    if method == "image_status" {
+        INCOMING.lock().unwrap().push(traceparent(request.metadata()));
+    } else {
+        let mut metadata = MetadataMap::new();
+        CriTraceContext::inject(&mut metadata);
+        OUTGOING.lock().unwrap().push(traceparent(&metadata));
+    }
+    Ok(())
+}
+
+fixture! {
+    answer = answer;
+    #[auto_impl(image, server, client, opentelemetry)]
+    pub struct Traced;
+}
+
+#[test]
+fn extracts_the_callers_context() {
+    let mut metadata = MetadataMap::new();
+    metadata.insert("traceparent", TRACEPARENT.parse().unwrap());
+    let context = CriTraceContext::extract(&metadata);
+    assert_eq!(context.span().span_context().trace_id().to_string(), TRACE_ID);
+
+    let context = CriTraceContext::extract(&MetadataMap::new());
+    assert!(!context.span().span_context().is_valid());
+}
+
+#[tokio::test]
+async fn handlers_run_in_the_callers_trace() {
+    let provider = opentelemetry::sdk::trace::TracerProvider::builder().build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("trace_context"));
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+    let mut request = Request::new(v1::ListImagesRequest::default());
+    request.metadata_mut().insert("traceparent", TRACEPARENT.parse().unwrap());
+    ImageService_v1::list_images(&Traced, request).await.unwrap();
+    // without a `traceparent` the span starts a trace of its own
+    ImageService_v1::list_images(&Traced, Request::new(v1::ListImagesRequest::default())).await.unwrap();
+
+    let outgoing = OUTGOING.lock().unwrap();
+    assert_eq!(outgoing.len(), 2);
+    let parts = outgoing[0].split('-').collect::<Vec<_>>();
+    assert_eq!(parts[1], TRACE_ID);
+    // the span of the call, not the caller's
+    assert_ne!(parts[2], "00f067aa0ba902b7");
+    assert!(!outgoing[1].is_empty());
+    assert!(!outgoing[1].contains(TRACE_ID));
+}
+
+#[tokio::test]
+async fn clients_send_the_current_context() {
+    let provider = opentelemetry::sdk::trace::TracerProvider::builder().build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("trace_context"));
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+    let path = common::temp_path("trace-context.sock");
+    tokio::spawn(Arc::new(Traced).serve_unix(path.clone()));
+    common::connect_unix(&path).await;
+
+    let client = v1::CriClient::connect_unix(&path).await.unwrap();
+    let span = tracing::info_span!("kubelet");
+    let trace_id = span.context().span().span_context().trace_id().to_string();
+    client.image_status(v1::ImageStatusRequest::default()).instrument(span).await.unwrap();
+
+    let incoming = INCOMING.lock().unwrap();
+    let traced = incoming.iter().filter(|it| it.split('-').nth(1) == Some(trace_id.as_str()));
+    assert_eq!(traced.count(), 1);
+}
+
+mod shim {
+    use auto_impl_trait::auto_impl;
+
+    #[auto_impl(image, bridge = v1, opentelemetry)]
+    pub struct Shim {
+        pub upstream: CriUpstream,
+    }
+
+    impl v1::ImageServiceProxy for Shim {}
+    impl v1alpha2::ImageServiceProxy for Shim {}
+}
+
+#[tokio::test]
+async fn proxies_forward_their_own_span() {
+    use shim::v1::image_service_server::ImageService as ShimV1;
+    use shim::v1alpha2::image_service_server::ImageService as ShimV1alpha2;
+
+    let provider = opentelemetry::sdk::trace::TracerProvider::builder().build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("trace_context"));
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+    let path = common::temp_path("trace-context-upstream.sock");
+    tokio::spawn(Arc::new(Traced).serve_unix(path.clone()));
+    common::connect_unix(&path).await;
+    let shim = shim::Shim {
+        upstream: shim::CriUpstream::connect_unix(&path).await.unwrap(),
+    };
+
+    let mut request = Request::new(shim::v1::ImageStatusRequest::default());
+    request.metadata_mut().insert("traceparent", TRACEPARENT.parse().unwrap());
+    ShimV1::image_status(&shim, request).await.unwrap();
+    // bridged to v1
+    let mut request = Request::new(shim::v1alpha2::ImageStatusRequest::default());
+    request.metadata_mut().insert("traceparent", TRACEPARENT.parse().unwrap());
+    ShimV1alpha2::image_status(&shim, request).await.unwrap();
+
+    let incoming = INCOMING.lock().unwrap();
+    let traced = incoming.iter().filter(|it| it.contains(TRACE_ID)).collect::<Vec<_>>();
+    assert_eq!(traced.len(), 2);
+    for traceparent in traced {
+        // parented to the shim's span, not the kubelet's
+        assert_ne!(traceparent.split('-').nth(2), Some("00f067aa0ba902b7"));
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use auto_impl_trait::auto_impl;
+use tonic::{Code, Request, Status};
+
+#[macro_use]
+mod common;
+
+/// Counts the requests that got past validation, which runs before the hooks.
+#[derive(Default)]
+pub struct Forwarded(AtomicUsize);
+
+impl v1::ImageServiceHooks for Forwarded {
+    fn before_list_images(&self, _request: &mut Request<v1::ListImagesRequest>) -> Result<(), Status> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn before_pull_image(&self, _request: &mut Request<v1::PullImageRequest>) -> Result<(), Status> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl v1alpha2::ImageServiceHooks for Forwarded {
+    fn before_list_images(&self, _request: &mut Request<v1alpha2::ListImagesRequest>) -> Result<(), Status> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fixture! {
+    #[auto_impl(
+        image,
+        layer = Forwarded,
+        validate(required = [ListImagesRequest.filter], non_empty = [ImageSpec.annotations]),
+    )]
+    #[derive(Default)]
+    pub struct Validated {
+        forwarded: Forwarded,
+    }
+}
+
+fn spec(image: &str, annotations: &[(&str, &str)]) -> Option<v1::ImageSpec> {
+    Some(v1::ImageSpec {
+        image: image.to_string(),
+        annotations: annotations.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect::<HashMap<_, _>>(),
+    })
+}
+
+async fn pull(validated: &Validated, image: Option<v1::ImageSpec>) -> Result<(), Status> {
+    let request = v1::PullImageRequest {
+        image,
+        ..Default::default()
+    };
+    ImageService_v1::pull_image(validated, Request::new(request)).await.map(drop)
+}
+
+#[track_caller]
+fn assert_invalid(result: Result<(), Status>, message: &str) {
+    let e = result.unwrap_err();
+    assert_eq!(e.code(), Code::InvalidArgument);
+    assert_eq!(e.message(), message);
+}
+
+#[tokio::test]
+async fn built_in_rules() {
+    let validated = Validated::default();
+    assert_invalid(pull(&validated, None).await, "image is required");
+    assert_invalid(pull(&validated, spec("", &[("a", "b")])).await, "image.image must not be empty");
+    assert_eq!(validated.forwarded.0.load(Ordering::Relaxed), 0);
+
+    pull(&validated, spec("busybox", &[("a", "b")])).await.unwrap();
+    assert_eq!(validated.forwarded.0.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn added_rules_name_the_field_path() {
+    let validated = Validated::default();
+    assert_invalid(pull(&validated, spec("busybox", &[])).await, "image.annotations must not be empty");
+
+    let list = |filter: Option<v1::ImageFilter>| {
+        let request = Request::new(v1::ListImagesRequest { filter });
+        ImageService_v1::list_images(&validated, request)
+    };
+    assert_invalid(list(None).await.map(drop), "filter is required");
+    let filter = v1::ImageFilter {
+        image: spec("busybox", &[]),
+    };
+    assert_invalid(list(Some(filter)).await.map(drop), "filter.image.annotations must not be empty");
+    list(Some(v1::ImageFilter::default())).await.unwrap();
+    assert_eq!(validated.forwarded.0.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn both_versions_are_validated() {
+    let validated = Validated::default();
+    let request = Request::new(v1alpha2::ListImagesRequest { filter: None });
+    let e = ImageService_v1alpha2::list_images(&validated, request).await.unwrap_err();
+    assert_eq!(e.code(), Code::InvalidArgument);
+    assert_eq!(e.message(), "filter is required");
+    assert_eq!(validated.forwarded.0.load(Ordering::Relaxed), 0);
+}